@@ -0,0 +1,187 @@
+//a language server for the tests of src/lsp.rs, it talks json-rpc over stdin and stdout
+//it keeps the text of the open documents and reports every line containing "bad" as an error,
+//opening a document twice or changing one that is not open is complained about,
+//hover, definition and completion get made up answers
+//with the argument "exit" it quits right after answering initialize
+#[macro_use]
+extern crate serde_json;
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::thread;
+use std::time::Duration;
+
+fn main()
+{
+	let exit = env::args().any(|arg| arg == "exit");
+	let stdin = io::stdin();
+	let mut input = stdin.lock();
+	let mut documents: HashMap<String, Vec<Vec<u16>>> = HashMap::new(); //uri -> lines in UTF-16 code units
+	let mut initialized = false;
+	while let Some(message) = read_message(&mut input)
+	{
+		let method = message["method"].as_str().unwrap_or("").to_owned();
+		let id = message["id"].clone();
+		let params = &message["params"];
+		let uri = params["textDocument"]["uri"].as_str().unwrap_or("").to_owned();
+		if method == "initialize"
+		{
+			//answer late, the client has to hold back everything else meanwhile
+			thread::sleep(Duration::from_millis(200));
+			respond(id, json!({"capabilities": {"textDocumentSync": {"openClose": true, "change": 2}, "hoverProvider": true}}));
+			if exit
+			{
+				return;
+			}
+			continue;
+		}
+		if method == "initialized"
+		{
+			initialized = true;
+			continue;
+		}
+		if initialized == false
+		{
+			send(json!({"jsonrpc": "2.0", "method": "window/showMessage", "params": {"type": 1, "message": format!("{} before initialized", method)}}));
+			continue;
+		}
+		match &method[..]
+		{
+			"textDocument/didOpen" => {
+				if documents.contains_key(&uri)
+				{
+					complain(format!("{} is open already", uri));
+					continue;
+				}
+				let text = params["textDocument"]["text"].as_str().unwrap_or("");
+				documents.insert(uri.clone(), split(text));
+				publish(&uri, &documents[&uri]);
+			},
+			"textDocument/didClose" => {
+				documents.remove(&uri);
+			},
+			"textDocument/didChange" => {
+				let lines = match documents.get_mut(&uri)
+				{
+					Some(v) => v,
+					None => {
+						complain(format!("{} is not open", uri));
+						continue;
+					}
+				};
+				for change in params["contentChanges"].as_array().unwrap()
+				{
+					let text = change["text"].as_str().unwrap_or("");
+					if change["range"].is_null()
+					{
+						*lines = split(text);
+						continue;
+					}
+					let (first, start) = position(&change["range"]["start"]);
+					let (last, end) = position(&change["range"]["end"]);
+					let mut new = split(text);
+					let mut head = lines[first][..start].to_vec();
+					let mut tail = lines[last][end..].to_vec();
+					head.append(&mut new[0]);
+					new[0] = head;
+					new.last_mut().unwrap().append(&mut tail);
+					lines.splice(first .. last+1, new);
+				}
+				publish(&uri, lines);
+			},
+			"textDocument/hover" => {
+				let (line, column) = position(&params["position"]);
+				respond(id, json!({"contents": {"kind": "plaintext", "value": format!("hover {}:{}", line, column)}}));
+			},
+			"textDocument/definition" => {
+				respond(id, json!([{"uri": uri, "range": {"start": {"line": 1, "character": 2}, "end": {"line": 1, "character": 5}}}]));
+			},
+			"textDocument/completion" => {
+				respond(id, json!({"isIncomplete": false, "items": [{"label": "foo", "insertText": "foo(${1:x})$0", "insertTextFormat": 2}]}));
+			},
+			_ => {
+				if id.is_null() == false
+				{
+					respond(id, Value::Null);
+				}
+			}
+		}
+	}
+}
+
+fn split(text: &str) -> Vec<Vec<u16>>
+{
+	text.split('\n').map(|line| line.encode_utf16().collect()).collect()
+}
+
+fn position(position: &Value) -> (usize, usize)
+{
+	(position["line"].as_u64().unwrap() as usize, position["character"].as_u64().unwrap() as usize)
+}
+
+//one error per line containing "bad", with the line as message
+fn publish(uri: &str, lines: &[Vec<u16>])
+{
+	let mut diagnostics = Vec::new();
+	for (index, line) in lines.iter().enumerate()
+	{
+		let line = String::from_utf16_lossy(line);
+		if line.contains("bad")
+		{
+			diagnostics.push(json!({"range": {"start": {"line": index, "character": 0}, "end": {"line": index, "character": 0}}, "severity": 1, "message": line}));
+		}
+	}
+	send(json!({"jsonrpc": "2.0", "method": "textDocument/publishDiagnostics", "params": {"uri": uri, "diagnostics": diagnostics}}));
+}
+
+fn complain(message: String)
+{
+	send(json!({"jsonrpc": "2.0", "method": "window/showMessage", "params": {"type": 1, "message": message}}));
+}
+
+fn respond(id: Value, result: Value)
+{
+	send(json!({"jsonrpc": "2.0", "id": id, "result": result}));
+}
+
+fn send(message: Value)
+{
+	let message = message.to_string();
+	let stdout = io::stdout();
+	let mut output = stdout.lock();
+	let _ = write!(output, "Content-Length: {}\r\n\r\n{}", message.len(), message);
+	let _ = output.flush();
+}
+
+fn read_message<R: BufRead>(reader: &mut R) -> Option<Value>
+{
+	let mut length = 0;
+	loop
+	{
+		let mut header = String::new();
+		match reader.read_line(&mut header)
+		{
+			Ok(0) | Err(_) => {return None;},
+			Ok(_) => {}
+		}
+		let header = header.trim();
+		if header.is_empty()
+		{
+			break;
+		}
+		if header.to_lowercase().starts_with("content-length:")
+		{
+			length = header["content-length:".len()..].trim().parse::<usize>().unwrap_or(0);
+		}
+	}
+	let mut body = vec![0; length];
+	if reader.read_exact(&mut body).is_err()
+	{
+		return None;
+	}
+	serde_json::from_slice(&body).ok()
+}
@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+pub const SIDEBAR_WIDTH: usize = 30;
+
+//a file or directory shown in the sidebar
+pub struct Entry
+{
+	pub path: PathBuf, //path relative to the working directory
+	pub name: String, //file name shown
+	pub depth: usize, //number of parent directories below the root
+	pub is_dir: bool,
+	pub expanded: bool, //the content of the directory is shown below it
+	canonical: Option<PathBuf> //absolute path, to compare with the open file
+}
+
+//the directory tree in the sidebar
+pub struct Browser
+{
+	root: PathBuf,
+	expanded: HashSet<PathBuf>, //directories whose content is shown
+	entries: Vec<Entry>, //the visible entries, in display order
+	pub selected: usize, //index into entries
+	pub scroll: usize //index of the first visible entry
+}
+
+impl Browser
+{
+	pub fn new(root: PathBuf) -> Browser
+	{
+		let mut browser = Browser
+		{
+			root,
+			expanded: HashSet::new(),
+			entries: Vec::new(),
+			selected: 0,
+			scroll: 0
+		};
+		browser.refresh();
+		browser
+	}
+
+	//read the tree again, the selection stays on the same path if it still exists
+	pub fn refresh(&mut self)
+	{
+		let selected_path = self.get_selected().map(|entry| entry.path.clone());
+		let mut entries = Vec::new();
+		let root = self.root.clone();
+		self.read_dir(&root, 0, &mut entries);
+		self.entries = entries;
+		if let Some(path) = selected_path
+		{
+			if let Some(index) = self.entries.iter().position(|entry| entry.path == path)
+			{
+				self.selected = index;
+			}
+		}
+		if self.selected >= self.entries.len()
+		{
+			self.selected = self.entries.len().saturating_sub(1);
+		}
+	}
+
+	//add the content of dir and of its expanded subdirectories, directories first
+	fn read_dir(&self, dir: &Path, depth: usize, entries: &mut Vec<Entry>)
+	{
+		let mut children: Vec<(bool, String)> = match fs::read_dir(dir)
+		{
+			Ok(v) => v.filter_map(|entry| entry.ok()).map(|entry| {
+				let is_dir = entry.file_type().map(|file_type| file_type.is_dir()).unwrap_or(false);
+				(is_dir == false, entry.file_name().to_string_lossy().into_owned())
+			}).collect(),
+			Err(_) => {return;}
+		};
+		children.sort();
+		for (is_file, name) in children
+		{
+			if name == ".git"
+			{
+				continue;
+			}
+			let path = if dir == Path::new(".") { PathBuf::from(&name) } else { dir.join(&name) };
+			let expanded = self.expanded.contains(&path);
+			entries.push(Entry
+			{
+				canonical: fs::canonicalize(&path).ok(),
+				path: path.clone(),
+				name,
+				depth,
+				is_dir: is_file == false,
+				expanded
+			});
+			if expanded
+			{
+				self.read_dir(&path, depth + 1, entries);
+			}
+		}
+	}
+
+	pub fn get_entries(&self) -> &Vec<Entry>
+	{
+		&self.entries
+	}
+
+	pub fn get_selected(&self) -> Option<&Entry>
+	{
+		self.entries.get(self.selected)
+	}
+
+	//the directory new files are created in: the selected directory or the parent of the selected file
+	pub fn get_selected_dir(&self) -> PathBuf
+	{
+		match self.get_selected()
+		{
+			Some(entry) if entry.is_dir => entry.path.clone(),
+			Some(entry) => entry.path.parent().map(|parent| parent.to_path_buf()).unwrap_or(PathBuf::new()),
+			None => PathBuf::new()
+		}
+	}
+
+	//true if the entry is the file at path
+	pub fn is_open_file(&self, entry: &Entry, path: &Option<PathBuf>) -> bool
+	{
+		match (&entry.canonical, path)
+		{
+			(Some(a), Some(b)) => a == b,
+			_ => false
+		}
+	}
+
+	pub fn move_selection(&mut self, up: bool, rows: usize)
+	{
+		if up && self.selected > 0
+		{
+			self.selected -= 1;
+		}
+		else if up == false && self.selected + 1 < self.entries.len()
+		{
+			self.selected += 1;
+		}
+		//keep the selection on screen
+		if self.selected < self.scroll
+		{
+			self.scroll = self.selected;
+		}
+		if rows > 0 && self.selected >= self.scroll + rows
+		{
+			self.scroll = self.selected + 1 - rows;
+		}
+	}
+
+	//show or hide the content of the selected directory
+	pub fn toggle_selected(&mut self)
+	{
+		let path = match self.get_selected()
+		{
+			Some(entry) if entry.is_dir => entry.path.clone(),
+			_ => {return;}
+		};
+		if self.expanded.remove(&path) == false
+		{
+			self.expanded.insert(path);
+		}
+		self.refresh();
+	}
+
+	//collapse the selected directory, or select the parent directory
+	pub fn collapse_selected(&mut self)
+	{
+		let (path, is_dir) = match self.get_selected()
+		{
+			Some(entry) => (entry.path.clone(), entry.is_dir),
+			None => {return;}
+		};
+		if is_dir && self.expanded.remove(&path)
+		{
+			self.refresh();
+			return;
+		}
+		if let Some(parent) = path.parent()
+		{
+			if let Some(index) = self.entries.iter().position(|entry| entry.path == parent)
+			{
+				self.selected = index;
+				if self.selected < self.scroll
+				{
+					self.scroll = self.selected;
+				}
+			}
+		}
+	}
+}
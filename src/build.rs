@@ -0,0 +1,264 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::TryRecvError;
+use std::thread;
+use shell;
+
+pub const BUILD_BUFFER: &str = "[build]";
+
+//one message of the compiler
+pub struct Diagnostic
+{
+	pub path: String,
+	pub line: usize, //zero based
+	pub column: usize, //zero based, 0 if the compiler gave none
+	pub message: String,
+	pub is_error: bool //false for warnings and notes
+}
+
+//a build running in another thread and the messages of the last finished one
+pub struct Build
+{
+	receiver: Option<Receiver<Result<(bool, String), String>>>, //result of the build command, None when done
+	pub diagnostics: Vec<Diagnostic>,
+	canonical: Vec<Option<PathBuf>>, //absolute path for every diagnostic, to compare with open files
+	pub current: Option<usize> //index into diagnostics of the one jumped to last
+}
+
+impl Build
+{
+	pub fn start(command: String) -> Build
+	{
+		let (sender, receiver) = mpsc::channel();
+		thread::spawn(move || {
+			let _ = sender.send(shell::run_combined(&command));
+		});
+		Build
+		{
+			receiver: Some(receiver),
+			diagnostics: Vec::new(),
+			canonical: Vec::new(),
+			current: None
+		}
+	}
+
+	pub fn is_running(&self) -> bool
+	{
+		self.receiver.is_some()
+	}
+
+	//look if the build is done, returns if it succeeded and its output once it is
+	pub fn poll(&mut self) -> Option<Result<(bool, String), String>>
+	{
+		let result = match self.receiver
+		{
+			Some(ref receiver) => receiver.try_recv(),
+			None => {return None;}
+		};
+		let result = match result
+		{
+			Ok(v) => v,
+			Err(TryRecvError::Empty) => {return None;},
+			Err(TryRecvError::Disconnected) => Err(String::from("build stopped"))
+		};
+		self.receiver = None;
+		if let Ok((_, ref output)) = result
+		{
+			self.diagnostics = parse_output(output);
+			self.canonical = self.diagnostics.iter().map(|diagnostic| fs::canonicalize(&diagnostic.path).ok()).collect();
+		}
+		Some(result)
+	}
+
+	pub fn error_count(&self) -> usize
+	{
+		self.diagnostics.iter().filter(|diagnostic| diagnostic.is_error).count()
+	}
+
+	//select the next or previous diagnostic, wrapping around at the ends
+	pub fn step(&mut self, forward: bool) -> Option<&Diagnostic>
+	{
+		let len = self.diagnostics.len();
+		if len == 0
+		{
+			return None;
+		}
+		let next = match (self.current, forward)
+		{
+			(None, true) => 0,
+			(None, false) => len - 1,
+			(Some(v), true) => (v + 1) % len,
+			(Some(v), false) => (v + len - 1) % len
+		};
+		self.current = Some(next);
+		self.diagnostics.get(next)
+	}
+
+	//the first diagnostic for a line of a file
+	pub fn diagnostic_at(&self, path: &Path, line: usize) -> Option<&Diagnostic>
+	{
+		(0..self.diagnostics.len()).find(|index| {
+			self.diagnostics[*index].line == line && self.canonical[*index].as_ref().map(|canonical| canonical == path).unwrap_or(false)
+		}).map(|index| &self.diagnostics[index])
+	}
+
+	//the diagnostics of a file as (line, is_error)
+	pub fn lines_in(&self, path: &Path) -> Vec<(usize, bool)>
+	{
+		let mut result = Vec::new();
+		for (index, diagnostic) in self.diagnostics.iter().enumerate()
+		{
+			if self.canonical[index].as_ref().map(|canonical| canonical == path).unwrap_or(false)
+			{
+				result.push((diagnostic.line, diagnostic.is_error));
+			}
+		}
+		result
+	}
+}
+
+//find the "file:line:column: message" lines in the output of a compiler
+pub fn parse_output(output: &str) -> Vec<Diagnostic>
+{
+	output.lines().filter_map(parse_line).collect()
+}
+
+//read one "file:line[:column]: message" line, None if the line has another form
+pub fn parse_line(line: &str) -> Option<Diagnostic>
+{
+	let line = line.trim();
+	let mut parts = line.splitn(4, ':');
+	let path = match parts.next()
+	{
+		Some(v) if is_path(v) => v.to_owned(),
+		_ => {return None;}
+	};
+	let line_number = match parts.next().and_then(|v| v.parse::<usize>().ok())
+	{
+		Some(v) if v > 0 => v - 1,
+		_ => {return None;}
+	};
+	let third = parts.next().unwrap_or("");
+	let rest = parts.next();
+	let (column, message) = match third.parse::<usize>()
+	{
+		Ok(v) if v > 0 => (v - 1, rest.unwrap_or("").trim().to_owned()),
+		_ => {
+			match rest
+			{
+				Some(rest) => (0, format!("{}:{}", third, rest).trim().to_owned()),
+				None => (0, third.trim().to_owned())
+			}
+		}
+	};
+	if message.is_empty()
+	{
+		return None;
+	}
+	let lower = message.to_lowercase();
+	let is_error = (lower.starts_with("warning") || lower.starts_with("note") || lower.starts_with("help")) == false;
+	Some(Diagnostic
+	{
+		path,
+		line: line_number,
+		column,
+		message,
+		is_error
+	})
+}
+
+//paths may contain spaces, but then they have to look like one, so that other lines with
+//numbers between colons (e.g. "took 12:30:01 minutes") don't count, gcc's source excerpts have a '|'
+fn is_path(path: &str) -> bool
+{
+	path.is_empty() == false && path.contains('|') == false && (path.contains(' ') == false || path.contains('/') || path.contains('.'))
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	//cargo build --message-format=short in "/tmp/cap/my proj"
+	const CARGO: &str = "   Compiling cap v0.1.0 (/tmp/cap/my proj)
+src/other.rs:2:18: error[E0308]: mismatched types: expected `u32`, found `&str`
+src/main.rs:3:9: warning: unused variable: `unused`: help: if this is intentional, prefix it with an underscore: `_unused`
+warning: `cap` (bin \"cap\") generated 1 warning
+error: could not compile `cap` (bin \"cap\") due to 1 previous error; 1 warning emitted
+";
+
+	//gcc -Wall -c \"c dir/main.c\"
+	const GCC: &str = "c dir/main.c: In function 'main':
+c dir/main.c:5:24: error: 'missing' undeclared (first use in this function)
+    5 |         printf(\"%d\\n\", missing);
+      |                        ^~~~~~~
+c dir/main.c:5:24: note: each undeclared identifier is reported only once for each function it appears in
+c dir/main.c:6:17: error: expected ';' before '}' token
+    6 |         return 0
+      |                 ^
+      |                 ;
+    7 | }
+      | ~                
+c dir/main.c:4:13: warning: unused variable 'unused' [-Wunused-variable]
+    4 |         int unused;
+      |             ^~~~~~
+";
+
+	//clang prints the same form, with included files first
+	const CLANG: &str = "In file included from /tmp/cap/c dir/main.c:1:
+/tmp/cap/c dir/other.h:2:1: error: unknown type name 'foo'
+foo bar;
+^
+/tmp/cap/c dir/main.c:4:6: warning: unused variable 'unused' [-Wunused-variable]
+        int unused;
+            ^
+1 warning and 1 error generated.
+";
+
+	fn summary(output: &str) -> Vec<(String, usize, usize, bool)>
+	{
+		parse_output(output).into_iter().map(|diagnostic| (diagnostic.path, diagnostic.line, diagnostic.column, diagnostic.is_error)).collect()
+	}
+
+	#[test]
+	fn cargo_output()
+	{
+		assert_eq!(summary(CARGO), vec![
+			(String::from("src/other.rs"), 1, 17, true),
+			(String::from("src/main.rs"), 2, 8, false)
+		]);
+		assert_eq!(parse_output(CARGO)[0].message, "error[E0308]: mismatched types: expected `u32`, found `&str`");
+	}
+
+	#[test]
+	fn gcc_output()
+	{
+		assert_eq!(summary(GCC), vec![
+			(String::from("c dir/main.c"), 4, 23, true),
+			(String::from("c dir/main.c"), 4, 23, false),
+			(String::from("c dir/main.c"), 5, 16, true),
+			(String::from("c dir/main.c"), 3, 12, false)
+		]);
+	}
+
+	#[test]
+	fn clang_output()
+	{
+		assert_eq!(summary(CLANG), vec![
+			(String::from("/tmp/cap/c dir/other.h"), 1, 0, true),
+			(String::from("/tmp/cap/c dir/main.c"), 3, 5, false)
+		]);
+	}
+
+	#[test]
+	fn other_lines()
+	{
+		assert!(parse_line("took 12:30:01 minutes").is_none());
+		assert!(parse_line("   12 | a ? b:1: c").is_none());
+		let diagnostic = parse_line("main.py:7: SyntaxError: invalid syntax").unwrap();
+		assert_eq!((diagnostic.line, diagnostic.column, &diagnostic.message[..]), (6, 0, "SyntaxError: invalid syntax"));
+	}
+}
@@ -0,0 +1,169 @@
+use std::fs;
+use std::path::Path;
+use config;
+
+//every command execute_internal knows, in the order they are offered
+pub const COMMANDS: &[&str] = &[
+	"new", "open", "edit", "save", "buffer", "bclose", "touch", "rename", "delete", "undo", "redo", "reflow", "grep", "build", "make", "nexterror", "preverror", "hover", "definition", "references", "complete", "nexthunk", "prevhunk", "hunk", "reverthunk", "blame", "showcommit", "diff", "nextchange", "prevchange", "push", "pull", "ours", "theirs", "both", "nextconflict", "prevconflict", "record", "play", "nextoccurrence", "cursorabove", "cursorbelow", "cursorlines", "blockcopy", "blockcut", "blockpaste", "blockfill", "fold", "foldall", "unfoldall", "set", "quit"
+];
+
+//commands that take a path as argument
+const PATH_COMMANDS: &[&str] = &["open", "edit", "save", "touch", "rename", "delete"];
+
+//a running completion in the commandline
+pub struct Completion
+{
+	pub start: usize, //index of the first char of the completed word in the commandline
+	pub candidates: Vec<String>, //possible replacements for the word
+	pub selected: Option<usize> //candidate currently in the commandline, None -> common prefix
+}
+
+impl Completion
+{
+	//select the next candidate, wrapping around at the end
+	pub fn cycle(&mut self) -> String
+	{
+		let next = match self.selected
+		{
+			Some(v) => (v + 1) % self.candidates.len(),
+			None => 0
+		};
+		self.selected = Some(next);
+		self.candidates[next].clone()
+	}
+}
+
+//find replacements for the last word of the commandline text
+//words: the words before the completed one, base_dir: directory of the current file
+pub fn candidates(words: &[&str], word: &str, base_dir: Option<&Path>, buffer_names: &[String]) -> Vec<String>
+{
+	let mut result: Vec<String> = match words.first()
+	{
+		None => {
+			COMMANDS.iter().filter(|command| command.starts_with(word)).map(|command| format!("{} ", command)).collect()
+		},
+		Some(command) if PATH_COMMANDS.contains(command) && (words.len() == 1 || (words.len() == 2 && *command == "rename")) => {
+			complete_path(word, base_dir)
+		},
+		Some(&"set") if words.len() == 1 => {
+			config::OPTIONS.iter().filter(|option| option.starts_with(word)).map(|option| format!("{} ", option)).collect()
+		},
+		Some(&"buffer") if words.len() == 1 => {
+			buffer_names.iter().filter(|name| name.starts_with(word)).cloned().collect()
+		},
+		_ => Vec::new()
+	};
+	result.dedup();
+	result
+}
+
+//the longest string all candidates start with
+pub fn common_prefix(candidates: &[String]) -> String
+{
+	let mut result = match candidates.first()
+	{
+		Some(v) => v.clone(),
+		None => {return String::new();}
+	};
+	for candidate in candidates
+	{
+		while candidate.starts_with(&result) == false
+		{
+			result.pop();
+		}
+	}
+	result
+}
+
+//complete a path, relative paths are looked up in base_dir unless they already start with it
+fn complete_path(word: &str, base_dir: Option<&Path>) -> Vec<String>
+{
+	let mut typed = word.to_owned();
+	if let Some(base) = base_dir
+	{
+		let base = base.to_string_lossy().into_owned();
+		let base_prefix = format!("{}/", base.trim_end_matches('/'));
+		if base.is_empty() == false && word.starts_with('/') == false && word.starts_with(&base_prefix) == false
+		{
+			typed = format!("{}{}", base_prefix, word);
+		}
+	}
+	//split into the directory to list and the start of the file name
+	let (dir, file_start) = match typed.rfind('/')
+	{
+		Some(v) => (typed[..v+1].to_owned(), typed[v+1..].to_owned()),
+		None => (String::new(), typed.clone())
+	};
+	let list_dir = if dir.is_empty() { String::from(".") } else { dir.clone() };
+	let entries = match fs::read_dir(&list_dir)
+	{
+		Ok(v) => v,
+		Err(_) => {return Vec::new();}
+	};
+	let mut result = Vec::new();
+	for entry in entries
+	{
+		let entry = match entry
+		{
+			Ok(v) => v,
+			Err(_) => {continue;}
+		};
+		let name = entry.file_name().to_string_lossy().into_owned();
+		//hidden files are only offered if the user asked for them
+		if name.starts_with(&file_start) == false || (name.starts_with('.') && file_start.starts_with('.') == false)
+		{
+			continue;
+		}
+		//metadata follows symlinks, so linked directories complete like directories
+		let is_dir = fs::metadata(entry.path()).map(|metadata| metadata.is_dir()).unwrap_or(false);
+		if is_dir
+		{
+			result.push(format!("{}{}/", dir, name));
+		}
+		else
+		{
+		    result.push(format!("{}{}", dir, name));
+		}
+	}
+	result.sort();
+	result
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn commands_and_options()
+	{
+		assert_eq!(candidates(&[], "blo", None, &[]), vec!["blockcopy ", "blockcut ", "blockpaste ", "blockfill "]);
+		assert_eq!(candidates(&["set"], "tabc", None, &[]), vec!["tabchar "]);
+		assert_eq!(candidates(&["quit"], "", None, &[]), Vec::<String>::new());
+		let buffers = vec![String::from("main.rs"), String::from("mod.rs"), String::from("lib.rs")];
+		assert_eq!(candidates(&["buffer"], "m", None, &buffers), vec!["main.rs", "mod.rs"]);
+	}
+
+	#[test]
+	fn paths()
+	{
+		//the tests run in the root of the crate
+		assert_eq!(candidates(&["open"], "src/fi", None, &[]), vec!["src/filedata.rs", "src/finder.rs"]);
+		assert_eq!(candidates(&["open"], "fi", Some(Path::new("src")), &[]), vec!["src/filedata.rs", "src/finder.rs"]);
+		assert_eq!(candidates(&["open"], "src/fi", Some(Path::new("src")), &[]), vec!["src/filedata.rs", "src/finder.rs"]);
+		assert_eq!(candidates(&["rename", "a"], "exam", None, &[]), vec!["examples/"]);
+		assert_eq!(candidates(&["open", "a"], "src/", None, &[]), Vec::<String>::new());
+	}
+
+	#[test]
+	fn prefix_and_cycling()
+	{
+		let names = vec![String::from("src/filedata.rs"), String::from("src/finder.rs")];
+		assert_eq!(common_prefix(&names), "src/fi");
+		assert_eq!(common_prefix(&[]), "");
+		let mut completion = Completion { start: 5, candidates: names, selected: None };
+		assert_eq!(completion.cycle(), "src/filedata.rs");
+		assert_eq!(completion.cycle(), "src/finder.rs");
+		assert_eq!(completion.cycle(), "src/filedata.rs");
+	}
+}
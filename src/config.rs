@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+
+pub const CONFIG_FILE: &str = ".micerc";
+
+//names of all options, for completion
+pub const OPTIONS: &[&str] = &[
+	"expandtab", "whitespace", "tabchar", "spacechar", "eolchar", "numbers", "wrap", "wordwrap", "wrapchar", "textwidth", "formatonsave", "buildcmd", "autocomplete", "matchbrackets", "autopair", "overtype", "pairdelete", "foldby", "autosession"
+];
+
+pub struct Config
+{
+	pub expand_tab: bool, //insert spaces instead of '\t' when tab is pressed
+	pub show_whitespace: bool, //draw tabs and trailing spaces with glyphs
+	pub tab_glyph: Option<char>, //shown at the start of a tab in whitespace mode
+	pub space_glyph: Option<char>, //shown for trailing spaces in whitespace mode
+	pub eol_glyph: Option<char>, //shown after the end of every line
+	pub line_numbers: bool, //draw a gutter with line numbers left of the text
+	pub soft_wrap: bool, //wrap long lines at the window width instead of scrolling
+	pub word_wrap: bool, //when wrapping, break lines at spaces
+	pub wrap_glyph: Option<char>, //shown at the end of a row that is continued below
+	pub text_width: usize, //default width for the reflow command
+	pub format_on_save: bool, //run the formatters before saving
+	pub formatters: HashMap<String, String>, //file extension -> command that formats stdin to stdout
+	pub build_command: String, //run by the build command
+	pub servers: HashMap<String, String>, //file extension -> command that starts a language server
+	pub auto_complete: bool, //offer words from the open buffers while typing
+	pub match_brackets: bool, //highlight the bracket matching the one at the cursor
+	pub auto_pair: bool, //insert the closing bracket or quote when typing the opening one
+	pub overtype: bool, //typing a closing bracket or quote in front of the same char moves over it
+	pub pair_delete: bool, //backspace between an empty pair removes both chars
+	pub fold_brackets: bool, //fold by brackets instead of by indentation
+	pub auto_session: bool, //started without a file, open the files left open in the same directory
+	pub bindings: HashMap<u16, String>, //key code -> macro played when the key is pressed
+}
+
+impl Config
+{
+	pub fn new() -> Config
+	{
+		Config
+		{
+			expand_tab: false,
+			show_whitespace: false,
+			tab_glyph: Some('»'),
+			space_glyph: Some('·'),
+			eol_glyph: Some('´'),
+			line_numbers: false,
+			soft_wrap: false,
+			word_wrap: true,
+			wrap_glyph: Some('\\'),
+			text_width: 72,
+			format_on_save: true,
+			formatters: HashMap::new(),
+			build_command: String::from("cargo build --message-format=short"),
+			servers: HashMap::new(),
+			auto_complete: true,
+			match_brackets: true,
+			auto_pair: false,
+			overtype: false,
+			pair_delete: false,
+			fold_brackets: false,
+			auto_session: false,
+			bindings: HashMap::new()
+		}
+	}
+
+	//load ~/.micerc, unknown or broken lines are collected as errors
+	pub fn load() -> (Config, Vec<String>)
+	{
+		let mut config = Config::new();
+		let mut errors = Vec::new();
+		let path = match home_dir()
+		{
+			Some(v) => v.join(CONFIG_FILE),
+			None => {return (config, errors);}
+		};
+		let mut content = String::new();
+		match File::open(&path)
+		{
+			Ok(mut file) => {
+				if let Err(e) = file.read_to_string(&mut content)
+				{
+					errors.push(format!("{}: {}", CONFIG_FILE, e));
+				}
+			},
+			Err(_) => {return (config, errors);}
+		}
+		for (index, line) in content.lines().enumerate()
+		{
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#')
+			{
+				continue;
+			}
+			let mut split = line.splitn(2, '=');
+			let key = split.next().unwrap_or("").trim();
+			let value = match split.next()
+			{
+				Some(v) => v.trim(),
+				None => {
+					errors.push(format!("{}:{}: expected <option> = <value>", CONFIG_FILE, index+1));
+					continue;
+				}
+			};
+			if let Err(e) = config.set(key, value)
+			{
+				errors.push(format!("{}:{}: {}", CONFIG_FILE, index+1, e));
+			}
+		}
+		(config, errors)
+	}
+
+	//change an option by name, used by the config file and the 'set' command
+	pub fn set(&mut self, key: &str, value: &str) -> Result<(), String>
+	{
+		match key
+		{
+			"expandtab" => {
+				self.expand_tab = try!(parse_bool(value));
+			},
+			"whitespace" => {
+				self.show_whitespace = try!(parse_bool(value));
+			},
+			"tabchar" => {
+				self.tab_glyph = try!(parse_glyph(value));
+			},
+			"spacechar" => {
+				self.space_glyph = try!(parse_glyph(value));
+			},
+			"eolchar" => {
+				self.eol_glyph = try!(parse_glyph(value));
+			},
+			"numbers" => {
+				self.line_numbers = try!(parse_bool(value));
+			},
+			"wrap" => {
+				self.soft_wrap = try!(parse_bool(value));
+			},
+			"wordwrap" => {
+				self.word_wrap = try!(parse_bool(value));
+			},
+			"wrapchar" => {
+				self.wrap_glyph = try!(parse_glyph(value));
+			},
+			"textwidth" => {
+				self.text_width = try!(parse_number(value));
+			},
+			"formatonsave" => {
+				self.format_on_save = try!(parse_bool(value));
+			},
+			"autocomplete" => {
+				self.auto_complete = try!(parse_bool(value));
+			},
+			"matchbrackets" => {
+				self.match_brackets = try!(parse_bool(value));
+			},
+			"autopair" => {
+				self.auto_pair = try!(parse_bool(value));
+			},
+			"overtype" => {
+				self.overtype = try!(parse_bool(value));
+			},
+			"pairdelete" => {
+				self.pair_delete = try!(parse_bool(value));
+			},
+			"foldby" => {
+				self.fold_brackets = match value
+				{
+					"indent" => false,
+					"brackets" => true,
+					_ => {return Err(format!("expected indent or brackets, got: {}", value));}
+				};
+			},
+			"autosession" => {
+				self.auto_session = try!(parse_bool(value));
+			},
+			"buildcmd" => {
+				self.build_command = value.to_owned();
+			},
+			_ if key.starts_with("format.") && key.len() > "format.".len() => {
+				set_command(&mut self.formatters, &key["format.".len()..], value);
+			},
+			_ if key.starts_with("lsp.") && key.len() > "lsp.".len() => {
+				set_command(&mut self.servers, &key["lsp.".len()..], value);
+			},
+			_ if key.starts_with("bind.") => {
+				let code = try!(parse_key(&key["bind.".len()..]));
+				if value == "none"
+				{
+					self.bindings.remove(&code);
+				}
+				else
+				{
+				    self.bindings.insert(code, value.to_owned());
+				}
+			},
+			_ => {
+				return Err(format!("unknown option: {}", key));
+			}
+		}
+		Ok(())
+	}
+
+	//the formatter command for a file, by its extension
+	pub fn formatter(&self, path: &str) -> Option<String>
+	{
+		command_for(&self.formatters, path)
+	}
+
+	//the language server command for a file, by its extension
+	pub fn server(&self, path: &str) -> Option<String>
+	{
+		command_for(&self.servers, path)
+	}
+}
+
+//add a command for an extension, or remove it with 'none'
+fn set_command(commands: &mut HashMap<String, String>, extension: &str, value: &str)
+{
+	if value == "none"
+	{
+		commands.remove(extension);
+	}
+	else
+	{
+	    commands.insert(extension.to_owned(), value.to_owned());
+	}
+}
+
+fn command_for(commands: &HashMap<String, String>, path: &str) -> Option<String>
+{
+	let extension = match Path::new(path).extension()
+	{
+		Some(v) => v.to_string_lossy().into_owned(),
+		None => {return None;}
+	};
+	commands.get(&extension).cloned()
+}
+
+pub fn home_dir() -> Option<PathBuf>
+{
+	match env::var("HOME")
+	{
+		Ok(v) => Some(PathBuf::from(v)),
+		Err(_) => None
+	}
+}
+
+//a key name like "f5" or "ctrl-g" to the key code of rustbox
+fn parse_key(name: &str) -> Result<u16, String>
+{
+	let lower = name.to_lowercase();
+	if let Some(number) = lower.strip_prefix('f')
+	{
+		if let Ok(number) = number.parse::<u16>()
+		{
+			if (1 ..= 12).contains(&number)
+			{
+				return Ok(65535 - (number - 1));
+			}
+		}
+	}
+	if lower.starts_with("ctrl-") && lower.chars().count() == 6
+	{
+		let letter = lower.chars().last().unwrap();
+		if letter.is_ascii_lowercase()
+		{
+			return Ok(letter as u16 - 'a' as u16 + 1);
+		}
+	}
+	Err(format!("unknown key: {}, expected f1 to f12 or ctrl-<letter>", name))
+}
+
+fn parse_bool(value: &str) -> Result<bool, String>
+{
+	match value
+	{
+		"true" | "on" | "yes" | "1" => Ok(true),
+		"false" | "off" | "no" | "0" => Ok(false),
+		_ => Err(format!("expected true or false, got: {}", value))
+	}
+}
+
+fn parse_number(value: &str) -> Result<usize, String>
+{
+	match value.parse::<usize>()
+	{
+		Ok(v) if v > 0 => Ok(v),
+		_ => Err(format!("expected a positive number, got: {}", value))
+	}
+}
+
+//a single char, or 'none' to draw nothing
+fn parse_glyph(value: &str) -> Result<Option<char>, String>
+{
+	if value == "none"
+	{
+		return Ok(None);
+	}
+	let mut chars = value.chars();
+	match (chars.next(), chars.next())
+	{
+		(Some(ch), None) => Ok(Some(ch)),
+		_ => Err(format!("expected a single char or none, got: {}", value))
+	}
+}
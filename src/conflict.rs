@@ -0,0 +1,150 @@
+//a merge conflict left by git, the numbers are the lines of its markers
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Conflict
+{
+	pub start: usize, //<<<<<<< ours
+	pub base: Option<usize>, //||||||| base, only with merge.conflictStyle diff3
+	pub middle: usize, //=======
+	pub end: usize //>>>>>>> theirs
+}
+
+//which part of a conflict a line belongs to, for highlighting
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Region
+{
+	Marker,
+	Ours,
+	Base,
+	Theirs
+}
+
+impl Conflict
+{
+	pub fn contains(&self, line: usize) -> bool
+	{
+		line >= self.start && line <= self.end
+	}
+
+	pub fn region(&self, line: usize) -> Region
+	{
+		if line == self.start || Some(line) == self.base || line == self.middle || line == self.end
+		{
+			Region::Marker
+		}
+		else if line > self.middle
+		{
+			Region::Theirs
+		}
+		else if self.base.map(|base| line > base).unwrap_or(false)
+		{
+			Region::Base
+		}
+		else
+		{
+		    Region::Ours
+		}
+	}
+
+	//the lines that replace the conflict when it is resolved with "ours", "theirs" or "both"
+	pub fn resolve(&self, content: &[Vec<char>], choice: &str) -> Vec<Vec<char>>
+	{
+		let ours_end = self.base.unwrap_or(self.middle);
+		let ours = &content[self.start+1 .. ours_end];
+		let theirs = &content[self.middle+1 .. self.end];
+		match choice
+		{
+			"ours" => ours.to_vec(),
+			"theirs" => theirs.to_vec(),
+			_ => ours.iter().chain(theirs.iter()).cloned().collect()
+		}
+	}
+}
+
+//the complete conflicts of a text, markers without their counterparts are ignored
+pub fn find(content: &[Vec<char>]) -> Vec<Conflict>
+{
+	let mut result = Vec::new();
+	let mut current: Option<Conflict> = None;
+	for (index, line) in content.iter().enumerate()
+	{
+		if is_marker(line, '<')
+		{
+			current = Some(Conflict { start: index, base: None, middle: 0, end: 0 });
+			continue;
+		}
+		let conflict = match current
+		{
+			Some(ref mut v) => v,
+			None => {continue;}
+		};
+		if is_marker(line, '|') && conflict.base.is_none() && conflict.middle == 0
+		{
+			conflict.base = Some(index);
+		}
+		else if is_marker(line, '=') && conflict.middle == 0
+		{
+			conflict.middle = index;
+		}
+		else if is_marker(line, '>') && conflict.middle != 0
+		{
+			conflict.end = index;
+			result.push(*conflict);
+			current = None;
+		}
+	}
+	result
+}
+
+//seven marker chars at the start of the line, followed by nothing or a space and a label
+fn is_marker(line: &[char], ch: char) -> bool
+{
+	line.len() >= 7 && line[..7].iter().all(|c| *c == ch) && (line.len() == 7 || line[7] == ' ')
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn content(text: &str) -> Vec<Vec<char>>
+	{
+		text.split('\n').map(|line| line.chars().collect()).collect()
+	}
+
+	fn text(lines: Vec<Vec<char>>) -> String
+	{
+		lines.iter().map(|line| line.iter().collect::<String>()).collect::<Vec<String>>().join("\n")
+	}
+
+	#[test]
+	fn find_conflicts()
+	{
+		let content = content("a\n<<<<<<< HEAD\nb\n=======\nc\n>>>>>>> topic\n<<<<<<< HEAD\nd\n||||||| base\ne\n=======\nf\n>>>>>>> topic\n=======\n>>>>>>>");
+		assert_eq!(find(&content), vec![
+			Conflict { start: 1, base: None, middle: 3, end: 5 },
+			Conflict { start: 6, base: Some(8), middle: 10, end: 12 }
+		]);
+	}
+
+	#[test]
+	fn markers_need_seven_chars_and_a_space()
+	{
+		assert_eq!(find(&content("<<<<<<\nb\n=======\n>>>>>>>")), vec![]);
+		assert_eq!(find(&content("<<<<<<<<\nb\n=======\n>>>>>>>")), vec![]);
+		assert_eq!(find(&content("<<<<<<<\nb\n=======x\n>>>>>>>")), vec![]);
+		assert_eq!(find(&content("<<<<<<<\n=======\n>>>>>>>")), vec![Conflict { start: 0, base: None, middle: 1, end: 2 }]);
+	}
+
+	#[test]
+	fn regions_and_resolutions()
+	{
+		let content = content("<<<<<<< HEAD\nours\n||||||| base\nbase\n=======\ntheirs\n>>>>>>> topic");
+		let conflict = find(&content)[0];
+		let regions: Vec<Region> = (0 .. 7).map(|line| conflict.region(line)).collect();
+		assert_eq!(regions, vec![Region::Marker, Region::Ours, Region::Marker, Region::Base, Region::Marker, Region::Theirs, Region::Marker]);
+		assert!(conflict.contains(6) && conflict.contains(7) == false);
+		assert_eq!(text(conflict.resolve(&content, "ours")), "ours");
+		assert_eq!(text(conflict.resolve(&content, "theirs")), "theirs");
+		assert_eq!(text(conflict.resolve(&content, "both")), "ours\ntheirs");
+	}
+}
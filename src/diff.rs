@@ -0,0 +1,313 @@
+use filedata::FileData;
+
+pub const MAX_EDITS: usize = 500; //above this many changed lines the middle is treated as one block
+
+//a block of lines that differ, lines old_start.. of the old text were replaced by new_start.. of the new text
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Hunk
+{
+	pub old_start: usize,
+	pub old_len: usize,
+	pub new_start: usize,
+	pub new_len: usize
+}
+
+//compare two lists of lines, returns the changed blocks in order
+pub fn diff<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Hunk>
+{
+	//the unchanged start and end are skipped, typing usually only changes a few lines in the middle
+	let mut start = 0;
+	while start < old.len() && start < new.len() && old[start] == new[start]
+	{
+		start += 1;
+	}
+	let mut end = 0;
+	while end < old.len() - start && end < new.len() - start && old[old.len() - 1 - end] == new[new.len() - 1 - end]
+	{
+		end += 1;
+	}
+	let old_middle = &old[start .. old.len() - end];
+	let new_middle = &new[start .. new.len() - end];
+	if old_middle.is_empty() && new_middle.is_empty()
+	{
+		return Vec::new();
+	}
+	let pairs = common_lines(old_middle, new_middle).unwrap_or_default();
+	//the lines between two common lines form a hunk
+	let mut hunks = Vec::new();
+	let (mut old_pos, mut new_pos) = (0, 0);
+	for (old_index, new_index) in pairs.into_iter().chain(Some((old_middle.len(), new_middle.len())))
+	{
+		if old_index > old_pos || new_index > new_pos
+		{
+			hunks.push(Hunk
+			{
+				old_start: start + old_pos,
+				old_len: old_index - old_pos,
+				new_start: start + new_pos,
+				new_len: new_index - new_pos
+			});
+		}
+		old_pos = old_index + 1;
+		new_pos = new_index + 1;
+	}
+	hunks
+}
+
+//the (old index, new index) pairs of a longest common subsequence, found with Myers' algorithm
+//None if the texts differ in more than MAX_EDITS lines
+fn common_lines<T: PartialEq>(old: &[T], new: &[T]) -> Option<Vec<(usize, usize)>>
+{
+	let n = old.len() as isize;
+	let m = new.len() as isize;
+	let max = n + m;
+	let offset = max as usize;
+	let mut v = vec![0isize; 2 * offset + 2];
+	let mut trace: Vec<Vec<isize>> = Vec::new(); //v[-d..=d] after every step d, enough to walk back
+	let mut found = false;
+	'search: for d in 0 .. max+1
+	{
+		if d as usize > MAX_EDITS
+		{
+			return None;
+		}
+		let mut k = -d;
+		while k <= d
+		{
+			let index = (k + max) as usize;
+			let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) { v[index + 1] } else { v[index - 1] + 1 };
+			let mut y = x - k;
+			while x < n && y < m && old[x as usize] == new[y as usize]
+			{
+				x += 1;
+				y += 1;
+			}
+			v[index] = x;
+			if x >= n && y >= m
+			{
+				found = true;
+				trace.push(v[(max - d) as usize .. (max + d) as usize + 1].to_vec());
+				break 'search;
+			}
+			k += 2;
+		}
+		trace.push(v[(max - d) as usize .. (max + d) as usize + 1].to_vec());
+	}
+	if found == false
+	{
+		return None;
+	}
+	//walk back through the saved states and collect the diagonal moves
+	let mut pairs = Vec::new();
+	let (mut x, mut y) = (n, m);
+	for d in (0 .. trace.len() as isize).rev()
+	{
+		let k = x - y;
+		let (prev_x, prev_y) = if d == 0
+		{
+			(0, 0)
+		}
+		else
+		{
+		    let previous = &trace[d as usize - 1];
+		    let at = |k: isize| previous[(k + d - 1) as usize];
+		    let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) { k + 1 } else { k - 1 };
+		    (at(prev_k), at(prev_k) - prev_k)
+		};
+		while x > prev_x && y > prev_y
+		{
+			x -= 1;
+			y -= 1;
+			pairs.push((x as usize, y as usize));
+		}
+		x = prev_x;
+		y = prev_y;
+	}
+	pairs.reverse();
+	Some(pairs)
+}
+
+//a row of the side by side view, None -> the other side has lines here that this side lacks
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DiffRow
+{
+	pub left: Option<usize>,
+	pub right: Option<usize>,
+	pub changed: bool
+}
+
+//the current buffer (left) compared with another buffer or its file on disk (right)
+pub struct DiffView
+{
+	pub other: FileData,
+	pub from_disk: bool, //the right side is the file on disk and is not changed by push
+	pub rows: Vec<DiffRow>, //lines of both sides aligned, unchanged lines share a row
+	pub hunks: Vec<Hunk>, //old -> left, new -> right
+	pub scroll: usize, //first row shown, both sides scroll together
+	pub column_scroll: usize,
+	versions: Option<(usize, usize)> //versions of both sides the rows belong to
+}
+
+impl DiffView
+{
+	pub fn new(other: FileData, from_disk: bool) -> DiffView
+	{
+		DiffView
+		{
+			other,
+			from_disk,
+			rows: Vec::new(),
+			hunks: Vec::new(),
+			scroll: 0,
+			column_scroll: 0,
+			versions: None
+		}
+	}
+
+	//compare again if one of the sides changed
+	pub fn update(&mut self, left: &FileData)
+	{
+		let versions = (left.get_version(), self.other.get_version());
+		if self.versions == Some(versions)
+		{
+			return;
+		}
+		self.versions = Some(versions);
+		self.hunks = diff(left.get_content(), self.other.get_content());
+		self.rows = Vec::new();
+		let (mut left_line, mut right_line) = (0, 0);
+		for hunk in &self.hunks
+		{
+			while left_line < hunk.old_start
+			{
+				self.rows.push(DiffRow { left: Some(left_line), right: Some(right_line), changed: false });
+				left_line += 1;
+				right_line += 1;
+			}
+			let len = if hunk.old_len > hunk.new_len { hunk.old_len } else { hunk.new_len };
+			for index in 0 .. len
+			{
+				self.rows.push(DiffRow
+				{
+					left: if index < hunk.old_len { Some(left_line + index) } else { None },
+					right: if index < hunk.new_len { Some(right_line + index) } else { None },
+					changed: true
+				});
+			}
+			left_line += hunk.old_len;
+			right_line += hunk.new_len;
+		}
+		while left_line < left.get_lines()
+		{
+			self.rows.push(DiffRow { left: Some(left_line), right: Some(right_line), changed: false });
+			left_line += 1;
+			right_line += 1;
+		}
+	}
+
+	//the row a line of the left side is shown in
+	pub fn row_of(&self, line: usize) -> usize
+	{
+		self.rows.iter().position(|row| row.left == Some(line)).unwrap_or(0)
+	}
+
+	//the hunk at a line of the left side, lines missing on the left belong to the line below them
+	pub fn hunk_at(&self, line: usize, left_lines: usize) -> Option<Hunk>
+	{
+		self.hunks.iter().find(|hunk| hunk_line(hunk, left_lines) == line || (line >= hunk.old_start && line < hunk.old_start + hunk.old_len)).cloned()
+	}
+
+	//the left line of the next or previous hunk, wrapping around at the ends
+	pub fn step(&self, line: usize, left_lines: usize, forward: bool) -> Option<usize>
+	{
+		let lines: Vec<usize> = self.hunks.iter().map(|hunk| hunk_line(hunk, left_lines)).collect();
+		if forward
+		{
+			lines.iter().find(|start| **start > line).or(lines.first()).cloned()
+		}
+		else
+		{
+		    lines.iter().rev().find(|start| **start < line).or(lines.last()).cloned()
+		}
+	}
+}
+
+//the first left line of a hunk, the last line if the hunk only adds lines at the end
+fn hunk_line(hunk: &Hunk, left_lines: usize) -> usize
+{
+	if hunk.old_start < left_lines { hunk.old_start } else { left_lines.saturating_sub(1) }
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn lines(text: &str) -> Vec<char>
+	{
+		text.chars().collect()
+	}
+
+	fn hunk(old_start: usize, old_len: usize, new_start: usize, new_len: usize) -> Hunk
+	{
+		Hunk { old_start, old_len, new_start, new_len }
+	}
+
+	#[test]
+	fn hunks()
+	{
+		assert_eq!(diff(&lines("abc"), &lines("abc")), vec![]);
+		assert_eq!(diff(&lines("abc"), &lines("abxc")), vec![hunk(2, 0, 2, 1)]);
+		assert_eq!(diff(&lines("abc"), &lines("ac")), vec![hunk(1, 1, 1, 0)]);
+		assert_eq!(diff(&lines("abcdef"), &lines("xbcdyf")), vec![hunk(0, 1, 0, 1), hunk(4, 1, 4, 1)]);
+		assert_eq!(diff(&lines(""), &lines("ab")), vec![hunk(0, 0, 0, 2)]);
+		assert_eq!(diff(&lines("ab"), &lines("")), vec![hunk(0, 2, 0, 0)]);
+	}
+
+	#[test]
+	fn too_many_edits_are_one_block()
+	{
+		let old: Vec<usize> = (0 .. MAX_EDITS + 10).map(|index| index * 2).collect();
+		let new: Vec<usize> = (0 .. MAX_EDITS + 10).map(|index| index * 2 + 1).collect();
+		assert_eq!(diff(&old, &new), vec![hunk(0, old.len(), 0, new.len())]);
+	}
+
+	//length of a longest common subsequence, the slow way
+	fn lcs(old: &[u8], new: &[u8]) -> usize
+	{
+		let mut table = vec![vec![0; new.len() + 1]; old.len() + 1];
+		for i in 0 .. old.len()
+		{
+			for j in 0 .. new.len()
+			{
+				table[i + 1][j + 1] = if old[i] == new[j] { table[i][j] + 1 } else { table[i][j + 1].max(table[i + 1][j]) };
+			}
+		}
+		table[old.len()][new.len()]
+	}
+
+	#[test]
+	fn common_lines_are_a_longest_common_subsequence()
+	{
+		let mut seed = 7u32;
+		let mut random = move |limit: u32| {
+			seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+			(seed >> 16) % limit
+		};
+		for _ in 0 .. 200
+		{
+			let old: Vec<u8> = (0 .. random(12)).map(|_| random(4) as u8).collect();
+			let new: Vec<u8> = (0 .. random(12)).map(|_| random(4) as u8).collect();
+			let pairs = common_lines(&old, &new).unwrap();
+			assert_eq!(pairs.len(), lcs(&old, &new));
+			for window in pairs.windows(2)
+			{
+				assert!(window[0].0 < window[1].0 && window[0].1 < window[1].1);
+			}
+			for &(old_index, new_index) in &pairs
+			{
+				assert_eq!(old[old_index], new[new_index]);
+			}
+		}
+	}
+}
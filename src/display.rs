@@ -4,13 +4,50 @@ use rustbox::Color;
 use rustbox;
 use std::char;
 use filedata::FileData;
+use config::Config;
+use history::History;
+use complete;
+use complete::Completion;
+use text;
 use std::collections::VecDeque;
 use std::path::Path;
+use std::mem;
 use std::time::SystemTime;
+use std::time::Duration;
+use finder::Finder;
+use browser;
+use browser::Browser;
+use std::path::PathBuf;
+use std::fs;
+use grep;
+use grep::Grep;
+use shell;
+use build;
+use build::Build;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::env;
+use filedata;
+use lsp;
+use popup;
+use popup::Popup;
+use words;
+use git;
+use git::GitDiff;
+use git::Blame;
+use diff::DiffView;
+use conflict;
+use conflict::Conflict;
+use macros;
+use macros::Macros;
+use state;
+use state::States;
 
 pub const COLOR: Color = Color::Yellow;
 pub const TAB_SIZE: isize = 4;
 pub const FRAME_LIMIT: u32 = 20000000;//20mil ca. 60 fps
+pub const POLL_TIMEOUT: u64 = 50; //ms to wait for input before looking at background work
+pub const KEY_BACK_TAB: u16 = 65500; //Shift+Tab, termbox has no key for it, see read_escape
 
 pub struct Display
 {
@@ -27,19 +64,46 @@ pub struct Display
 	yn_question: Option<YNQuestion>, //if not none -> question to the user
 	yn_question_state: bool, //if yn_question is answered with yes or no, true -> yes
 	draw_cursor_only: bool, //don't update text buffer for speed
+	brackets_shown: bool, //a matching bracket was highlighted by the last draw of the text
 	draw_xoff: isize,
 	screen_cursor_char: isize,
 	last_draw: SystemTime,
 	skipped_draw: bool,
 	run_low: bool,
+	config: Config, //options from ~/.micerc and the 'set' command
+	history: History, //commands entered in the commandline
+	input_scroll: usize, //first visible column of the commandline
+	completion: Option<Completion>, //candidates shown after pressing tab in the commandline
+	buffers: Vec<FileData>, //open files other than data
+	finder: Option<Finder>, //if not none -> the fuzzy file finder is shown
+	browser: Option<Browser>, //if not none -> the file tree is shown left of the text
+	browser_focus: bool, //true -> keys go to the file tree
+	grep: Option<Grep>, //the running or last project search
+	build: Option<Build>, //the running or last build and its messages
+	signs: HashMap<usize, (char, Color)>, //marks in the gutter for lines of the current buffer
+	signs_key: Option<(String, usize)>, //title and version of the buffer the signs are for, None -> collect them again
+	servers: HashMap<String, lsp::Client>, //running language servers by their command
+	broken_servers: HashSet<String>, //commands of servers that failed, they are not started again
+	popup: Option<Popup>, //if not none -> hover text or completions shown below the cursor
+	git: HashMap<String, GitDiff>, //changes of the open files against the git repository, by path
+	show_blame: bool, //true -> the commit that changed each line last is shown left of the text
+	blames: HashMap<String, Blame>, //git blame of the files by path, kept for switching back
+	diff: Option<DiffView>, //if not none -> the buffer is shown next to another one or its file on disk
+	conflicts: Vec<Conflict>, //merge conflicts in the current buffer
+	conflicts_key: Option<(String, usize)>, //title and version of the buffer the conflicts were found in
+	macros: Macros, //recorded key presses, kept in ~/.mice_macros
+	block_clipboard: Vec<String>, //rows of the last copied or cut block
+	states: States, //cursor, scroll and folds of recently edited files, kept in ~/.mice_state
+	session: Option<String>, //name of the session the open files are kept in when quitting
 }
 
 impl Display
 {
-	pub fn new(data: FileData) -> Display
+	pub fn new(data: FileData, session: Option<String>) -> Display
 	{
 		let rbox = Display::init_rustbox();
-		Display
+		let (config, config_errors) = Config::load();
+		let mut display = Display
 		{
 			running: true,
 			rustbox: rbox,
@@ -54,11 +118,63 @@ impl Display
 			yn_question: None,
 			yn_question_state: false,
 			draw_cursor_only: false,
+			brackets_shown: false,
 			draw_xoff: 0,
 			screen_cursor_char: 0,
 			last_draw: SystemTime::now(),
 			skipped_draw: false,
-			run_low: true
+			run_low: true,
+			config,
+			history: History::load(),
+			input_scroll: 0,
+			completion: None,
+			buffers: Vec::new(),
+			finder: None,
+			browser: None,
+			browser_focus: false,
+			grep: None,
+			build: None,
+			signs: HashMap::new(),
+			signs_key: None,
+			servers: HashMap::new(),
+			broken_servers: HashSet::new(),
+			popup: None,
+			git: HashMap::new(),
+			show_blame: false,
+			blames: HashMap::new(),
+			diff: None,
+			conflicts: Vec::new(),
+			conflicts_key: None,
+			macros: Macros::load(),
+			block_clipboard: Vec::new(),
+			states: States::load(),
+			session
+		};
+		display.apply_config();
+		display.check_conflicts();
+		display.line_scroll = display.states.restore(&mut display.data);
+		//without a file to edit, the files open when mice was last quit in this directory come back
+		if display.session.is_none() && display.config.auto_session && display.data.get_path().is_none()
+		{
+			display.session = env::current_dir().ok().map(|dir| dir.to_string_lossy().into_owned());
+		}
+		display.restore_session();
+		for error in config_errors
+		{
+			display.notify(format!("error: {}", error));
+		}
+		display
+	}
+
+	//pass options that live in the buffers down to them
+	fn apply_config(&mut self)
+	{
+		self.data.set_indent(self.config.expand_tab, TAB_SIZE as usize);
+		self.data.set_pairing(self.config.auto_pair, self.config.overtype, self.config.pair_delete);
+		for buffer in self.buffers.iter_mut()
+		{
+			buffer.set_indent(self.config.expand_tab, TAB_SIZE as usize);
+			buffer.set_pairing(self.config.auto_pair, self.config.overtype, self.config.pair_delete);
 		}
 	}
 
@@ -79,20 +195,25 @@ impl Display
 		self.draw_all();
 		loop
 		{
-			match self.rustbox.poll_event(true)
+			match self.rustbox.peek_event(Duration::from_millis(POLL_TIMEOUT), true)
 			{
 				Ok(Event::KeyEventRaw(_, key, charval)) =>
 				{
-					match char::from_u32(charval)
+					let keys = if key == 27 { self.read_escape() } else { vec![(key, charval)] };
+					for (key, charval) in keys
 					{
-						Some(character) => {
-							self.key_event(key, character);
-							self.draw_after_keypress();
-						},
-						None => {}
-					};
+						match char::from_u32(charval)
+						{
+							Some(character) if self.running => {
+								self.key_event(key, character);
+								self.draw_after_keypress();
+							},
+							_ => {}
+						};
+					}
 					if self.running == false
 					{
+						self.remember_states();
 						break;
 					}
 				},
@@ -103,7 +224,11 @@ impl Display
 				},
 				Err(_) => {},
 				_ => {
-					if self.skipped_draw
+					if self.poll_background()
+					{
+						self.draw_all();
+					}
+					else if self.skipped_draw
 					{
 						self.redo_skipped_draw();
 					}
@@ -112,6 +237,87 @@ impl Display
 		}
 	}
 
+	//termbox doesn't know the sequence ESC [ Z of Shift+Tab, it arrives as Esc, '[' and 'Z' at once
+	//returns the keys read after an Esc, with Shift+Tab as KEY_BACK_TAB
+	fn read_escape(&mut self) -> Vec<(u16, u32)>
+	{
+		let mut keys = vec![(27, 0)];
+		for expected in ['[', 'Z'].iter()
+		{
+			match self.rustbox.peek_event(Duration::from_millis(0), true)
+			{
+				Ok(Event::KeyEventRaw(_, key, charval)) => {
+					keys.push((key, charval));
+					if key != 0 || charval != *expected as u32
+					{
+						return keys;
+					}
+				},
+				Ok(Event::ResizeEvent(width, height)) => {
+					self.resize_event(width as usize, height as usize);
+					return keys;
+				},
+				_ => {return keys;}
+			}
+		}
+		vec![(KEY_BACK_TAB, 0)]
+	}
+
+	//collect results of work done in other threads, returns true if the screen has to be redrawn
+	fn poll_background(&mut self) -> bool
+	{
+		let mut redraw = false;
+		if let Some(ref mut finder) = self.finder
+		{
+			redraw |= finder.poll();
+		}
+		let mut grep_lines = Vec::new();
+		let mut grep_done = None;
+		if let Some(ref mut grep) = self.grep
+		{
+			let was_running = grep.is_running();
+			grep_lines = grep.poll();
+			if was_running && grep.is_running() == false
+			{
+				grep_done = Some((grep.matches, grep.files));
+			}
+		}
+		if grep_lines.is_empty() == false
+		{
+			if let Some(buffer) = self.find_scratch(grep::GREP_BUFFER)
+			{
+				buffer.append_lines(grep_lines);
+			}
+			redraw = true;
+		}
+		if let Some((matches, files)) = grep_done
+		{
+			self.notify(format!("grep: {} matches in {} files", matches, files));
+			redraw = true;
+		}
+		let build_result = match self.build
+		{
+			Some(ref mut build) => build.poll(),
+			None => None
+		};
+		if let Some(result) = build_result
+		{
+			self.build_finished(result);
+			redraw = true;
+		}
+		redraw |= self.poll_servers();
+		redraw |= self.poll_blame();
+		for git in self.git.values_mut()
+		{
+			redraw |= git.poll();
+		}
+		if redraw
+		{
+			self.signs_key = None; //build messages, diagnostics or git changes may have arrived
+		}
+		redraw
+	}
+
 	//update dimension
 	fn resize_event(&mut self, width: usize, height: usize)
 	{
@@ -121,34 +327,283 @@ impl Display
 
 	fn check_scroll(&mut self)
 	{
-		while self.data.get_cursor_line() < self.line_scroll
+		let cursor_line = self.data.get_cursor_line();
+		//the first line shown is never inside a fold
+		let shown = self.data.shown_line(self.line_scroll);
+		if shown != self.line_scroll
 		{
-			self.line_scroll -= 1;
+			self.line_scroll = shown;
 			self.draw_cursor_only = false;
 		}
-		while self.data.get_cursor_line() >= self.line_scroll + self.height - 1
+		if cursor_line < self.line_scroll
 		{
-			self.line_scroll += 1;
+			self.line_scroll = cursor_line;
 			self.draw_cursor_only = false;
 		}
+		if self.config.soft_wrap
+		{
+			//walk up from the cursor until the rows fill the screen, that is the lowest first line
+			//keeping the cursor on screen, a single line taller than the screen can't be helped
+			self.char_scroll = 0;
+			let text_rows = self.height.saturating_sub(1);
+			let mut used = match self.data.get_line(cursor_line)
+			{
+				Some(v) => text::row_of(&self.line_rows(v), self.data.get_cursor_char()) + 1,
+				None => 1
+			};
+			let mut first = cursor_line;
+			while first > self.line_scroll
+			{
+				let above = match self.data.visible_line(first, false)
+				{
+					Some(v) => v,
+					None => {break;}
+				};
+				used += self.line_rows(self.data.get_line(above).unwrap()).len();
+				if used > text_rows
+				{
+					break;
+				}
+				first = above;
+			}
+			if first > self.line_scroll
+			{
+				self.line_scroll = first;
+				self.draw_cursor_only = false;
+			}
+			self.draw_cursor();
+			return;
+		}
+		//the line that puts the cursor into the last text row, folded lines take no rows
+		let mut first = Some(cursor_line);
+		for _ in 0 .. self.height.saturating_sub(2)
+		{
+			first = first.and_then(|line| self.data.visible_line(line, false));
+		}
+		if let Some(first) = first
+		{
+			if first > self.line_scroll
+			{
+				self.line_scroll = first;
+				self.draw_cursor_only = false;
+			}
+		}
 		//horizontal scroll
-		if self.screen_cursor_char < 0
+		if self.screen_cursor_char < self.draw_xoff
 		{
 		    self.char_scroll = 0;
 		    self.draw_cursor_only = false;
 		}
 		self.draw_cursor();//recalc in case of switching from higher scroll to lesser scroll != 0
-		if self.screen_cursor_char >= self.width as isize
+		//the whole char under the cursor has to be visible, wide chars take two cells
+		let cursor_width = match self.data.get_line(self.data.get_cursor_line())
+		{
+			Some(v) => text::width_at(v, self.data.get_cursor_char(), TAB_SIZE as usize) as isize,
+			None => 1
+		};
+		if self.screen_cursor_char + cursor_width > self.width as isize
 		{
-			let delta = self.screen_cursor_char - self.width as isize + 1;
+			let delta = self.screen_cursor_char + cursor_width - self.width as isize;
 			self.char_scroll += delta as usize;
 			self.draw_cursor_only = false;
 		}
 	}
 
+	//the screen width left for text after the gutter
+	fn text_width(&self) -> usize
+	{
+		self.width.saturating_sub(self.draw_xoff as usize)
+	}
+
+	//recalculate the space taken by the gutter
+	fn update_xoff(&mut self)
+	{
+		self.data.update_folds();
+		self.update_git();
+		self.update_conflicts();
+		let key = (self.data.get_title(), self.data.get_version());
+		if self.signs_key.as_ref() != Some(&key)
+		{
+			self.signs = self.gutter_signs();
+			self.signs_key = Some(key);
+		}
+		let mut xoff = self.sidebar_width();
+		if self.config.line_numbers
+		{
+			xoff += format!("{}", self.data.get_lines()).len() + 1;
+		}
+		if self.signs.is_empty() == false
+		{
+			xoff += 2;
+		}
+		if self.current_blame().is_some()
+		{
+			xoff += git::BLAME_WIDTH + 1;
+		}
+		self.draw_xoff = xoff as isize;
+	}
+
+	//the space taken by the file tree, it is hidden on narrow screens
+	fn sidebar_width(&self) -> usize
+	{
+		if self.browser.is_some() && self.width > browser::SIDEBAR_WIDTH * 2
+		{
+			browser::SIDEBAR_WIDTH
+		}
+		else 
+		{
+		    0
+		}
+	}
+
+	//the rows a line is drawn in, without wrapping this is one row shifted by char_scroll
+	fn line_rows(&self, line: &[char]) -> Vec<text::Row>
+	{
+		if self.config.soft_wrap
+		{
+			//the last column is kept free for the wrap indicator
+			let width = if self.text_width() > 2 { self.text_width() - 1 } else { 1 };
+			text::wrap(line, TAB_SIZE as usize, width, self.config.word_wrap)
+		}
+		else 
+		{
+		    vec![text::Row
+			{
+				start: 0,
+				end: line.len(),
+				column: self.char_scroll
+			}]
+		}
+	}
+
+	//number of screen rows the lines from 'from' up to (not including) 'to' take
+	fn rows_between(&self, from: usize, to: usize) -> usize
+	{
+		let mut result = 0;
+		for line in from .. to
+		{
+			if self.data.is_hidden(line)
+			{
+				continue;
+			}
+			result += match self.data.get_line(line)
+			{
+				Some(v) => self.line_rows(v).len(),
+				None => 0
+			};
+		}
+		result
+	}
+
+	//the screen row of the cursor relative to the first text row
+	fn cursor_screen_row(&self) -> isize
+	{
+		let cursor_line = self.data.get_cursor_line();
+		if cursor_line < self.line_scroll
+		{
+			return -1;
+		}
+		let row = match self.data.get_line(cursor_line)
+		{
+			Some(v) => text::row_of(&self.line_rows(v), self.data.get_cursor_char()),
+			None => 0
+		};
+		(self.rows_between(self.line_scroll, cursor_line) + row) as isize
+	}
+
+	//move the cursor up or down one screen row when lines are wrapped,
+	//keeping the cursor in the same screen column if possible
+	fn move_cursor_row(&mut self, up: bool)
+	{
+		let cursor_line = self.data.get_cursor_line();
+		let line = self.data.get_line(cursor_line).unwrap().clone();
+		let rows = self.line_rows(&line);
+		let row = text::row_of(&rows, self.data.get_cursor_char());
+		let x = text::column_of(&line, self.data.get_cursor_char(), TAB_SIZE as usize) - rows[row].column;
+		let (target_line, target_row) = if up
+		{
+			if row > 0
+			{
+				(cursor_line, Some(row - 1))
+			}
+			else if let Some(line) = self.data.visible_line(cursor_line, false)
+			{
+				(line, None) //last row of the line above
+			}
+			else 
+			{
+			    return;
+			}
+		}
+		else 
+		{
+		    if row + 1 < rows.len()
+			{
+				(cursor_line, Some(row + 1))
+			}
+			else if let Some(line) = self.data.visible_line(cursor_line, true)
+			{
+				(line, Some(0))
+			}
+			else 
+			{
+			    return;
+			}
+		};
+		let target = self.data.get_line(target_line).unwrap().clone();
+		let target_rows = self.line_rows(&target);
+		let target_row = match target_row
+		{
+			Some(v) => v,
+			None => target_rows.len() - 1
+		};
+		let row = &target_rows[target_row];
+		let mut pos = row.start;
+		for cell in text::layout(&target, TAB_SIZE as usize)
+		{
+			if cell.start >= row.start && cell.start < row.end && cell.column - row.column <= x
+			{
+				pos = cell.start;
+			}
+		}
+		//past the end of the last row, the cursor can stand behind the last char
+		if target_row == target_rows.len() - 1 && text::column_of(&target, row.end, TAB_SIZE as usize) - row.column <= x
+		{
+			pos = row.end;
+		}
+		self.data.set_cursor(target_line, pos);
+	}
+
 	//handle incoming events
 	fn key_event(&mut self, key: u16, character: char)
 	{
+		if key == 65532 && self.yn_question.is_none() //F4
+		{
+			if self.macros.is_recording()
+			{
+				self.stop_recording(false);
+			}
+			else
+			{
+			    let name = self.macros.last.clone();
+			    self.macros.start(&name);
+			    self.notify(format!("recording into {}", name));
+			}
+			return;
+		}
+		if self.macros.is_recording() && self.macros.playing == 0
+		{
+			self.macros.record(key, character, self.input_active == false);
+		}
+		if self.yn_question.is_none() && self.finder.is_none()
+		{
+			let binding = if key == 65531 { Some(self.macros.last.clone()) } else { self.config.bindings.get(&key).cloned() }; //F5 plays the last macro
+			if let Some(name) = binding
+			{
+				self.play_macro(&name, 1);
+				return;
+			}
+		}
 		match self.yn_question //capture input when question is asked
 		{
 			Some(_) => {
@@ -157,12 +612,33 @@ impl Display
 			},
 			None => {}
 		}
+		if self.finder.is_some() //the finder captures input as well
+		{
+			self.finder_key_event(key, character);
+			return;
+		}
+		if self.browser_focus && self.input_active == false && self.browser_key_event(key, character)
+		{
+			return;
+		}
+		if self.popup.is_some() && self.input_active == false && self.popup_key_event(key)
+		{
+			return;
+		}
 
 		if self.input_active && key == 13 //had to move here for ownership reasons
 		{
 			self.execute_input();
 			return;
 		}
+		if self.input_active && self.input_key_event(key)
+		{
+			return;
+		}
+		if self.input_active
+		{
+			self.history.reset(); //typing ends browsing through the history
+		}
 
 		//ctrl keys
 		if key == 14 //^N
@@ -190,6 +666,129 @@ impl Display
 			self.preset_input(String::from("save "));
 			return;
 		}
+		else if key == 20 //^T
+		{
+			self.toggle_browser();
+			return;
+		}
+		else if key == 4 && !self.input_active //^D
+		{
+			self.execute_internal(String::from("nextoccurrence"));
+			return;
+		}
+		else if key == 12 && !self.input_active //^L
+		{
+			self.execute_internal(String::from("cursorlines"));
+			return;
+		}
+		else if key == 16 //^P
+		{
+			self.input_active = false;
+			self.input.clear();
+			self.finder = Some(Finder::new());
+			return;
+		}
+		else if key == 2 && !self.input_active //^B
+		{
+			self.data.toggle_block();
+			return;
+		}
+		else if key == 0 && character == '\0' && !self.input_active //^Space
+		{
+			self.data.toggle_mark();
+			return;
+		}
+		else if (key == 21 || key == KEY_BACK_TAB) && !self.input_active //^U or Shift+Tab
+		{
+			self.data.outdent_selection();
+			return;
+		}
+		else if key == 26 && !self.input_active //^Z
+		{
+			self.execute_internal(String::from("undo"));
+			return;
+		}
+		else if key == 25 && !self.input_active //^Y
+		{
+			self.execute_internal(String::from("redo"));
+			return;
+		}
+		else if key == 65528 //F8
+		{
+			self.execute_internal(String::from("nexterror"));
+			return;
+		}
+		else if key == 65529 //F7
+		{
+			self.execute_internal(String::from("preverror"));
+			return;
+		}
+		else if key == 65527 && !self.input_active //F9
+		{
+			let command = if self.diff.is_some() { "prevchange" } else { "prevhunk" };
+			self.execute_internal(String::from(command));
+			return;
+		}
+		else if key == 65526 && !self.input_active //F10
+		{
+			let command = if self.diff.is_some() { "nextchange" } else { "nexthunk" };
+			self.execute_internal(String::from(command));
+			return;
+		}
+		else if key == 65535 && !self.input_active //F1
+		{
+			self.execute_internal(String::from("hover"));
+			return;
+		}
+		else if key == 65534 && !self.input_active //F2
+		{
+			self.execute_internal(String::from("complete"));
+			return;
+		}
+		else if key == 65533 && !self.input_active //F3
+		{
+			self.execute_internal(String::from("references"));
+			return;
+		}
+		else if key == 65530 && !self.input_active //F6
+		{
+			self.execute_internal(String::from("fold"));
+			return;
+		}
+		else if key == 65524 && !self.input_active //F12
+		{
+			self.execute_internal(String::from("definition"));
+			return;
+		}
+		else if key == 10 && !self.input_active //^J
+		{
+			self.execute_internal(String::from("reflow"));
+			return;
+		}
+		else if (key == 65517 || key == 65516) && !self.input_active && self.config.soft_wrap //up, down by screen rows
+		{
+			self.move_cursor_row(key == 65517);
+			self.draw_cursor_only = !self.data.has_mark();
+			return;
+		}
+
+		if self.browser_focus && self.input_active == false //the text is not edited while the file tree has focus
+		{
+			return;
+		}
+		if key == 13 && !self.input_active && self.is_list_buffer() //enter in search results, build messages or references
+		{
+			if self.data.get_title() == build::BUILD_BUFFER
+			{
+				let line = self.data.get_cursor_line();
+				if let Some(ref mut build) = self.build
+				{
+					build.current = if line < build.diagnostics.len() { Some(line) } else { None };
+				}
+			}
+			self.jump_to_location();
+			return;
+		}
 
 		//match pressed key
 		let in_active = self.input_active;
@@ -204,23 +803,31 @@ impl Display
 				if !self.input_active
 				{
 					mod_data.move_cursor_up();
-					self.draw_cursor_only = true;
+					self.draw_cursor_only = !mod_data.has_mark();
 				}
 			},
 			65515 => { //left
 				mod_data.move_cursor_left();
-				self.draw_cursor_only = true;
+				self.draw_cursor_only = !mod_data.has_mark();
 			},
 			65516 => { //down
 				if !self.input_active
 				{
 					mod_data.move_cursor_down();
-					self.draw_cursor_only = true;
+					self.draw_cursor_only = !mod_data.has_mark();
 				}
 			},
 			65514 => { //right
 				mod_data.move_cursor_right();
-				self.draw_cursor_only = true;
+				self.draw_cursor_only = !mod_data.has_mark();
+			},
+			65521 => { //home
+				mod_data.move_cursor_home();
+				self.draw_cursor_only = !mod_data.has_mark();
+			},
+			65520 => { //end
+				mod_data.move_cursor_end();
+				self.draw_cursor_only = !mod_data.has_mark();
 			},
 			127 => { //bsp
 				mod_data.backspace();
@@ -231,12 +838,22 @@ impl Display
 			9 => { //tab
 				if !self.input_active
 				{
-					mod_data.write_char('\t');
+					if mod_data.has_mark()
+					{
+						mod_data.indent_selection();
+					}
+					else 
+					{
+					    mod_data.tab();
+					}
 				}
 			},
 			13 => { //enter, other half moved to start of function
 				mod_data.enter();
 			},
+			27 if in_active == false && mod_data.get_cursors().is_empty() == false => { //esc drops the extra cursors first
+				mod_data.clear_cursors();
+			},
 			27 => { //esc
 				self.input_active = !self.input_active;
 				if self.input_active == false //condition for mod_data = self.input
@@ -254,6 +871,293 @@ impl Display
 				}
 			}
 		}
+		self.update_popup();
+		if in_active == false && key == 0 && filedata::is_word_char(character)
+		{
+			self.complete_word();
+		}
+	}
+
+	//key handle while a popup is shown, returns false if the key goes on to the text
+	fn popup_key_event(&mut self, key: u16) -> bool
+	{
+		let completing = match self.popup
+		{
+			Some(ref v) => v.is_completion(),
+			None => {return false;}
+		};
+		match key
+		{
+			65517 | 65516 => { //up, down
+				if let Some(ref mut popup) = self.popup
+				{
+					popup.move_selection(key == 65517);
+				}
+				true
+			},
+			9 if completing => { //tab
+				if let Some(ref mut popup) = self.popup
+				{
+					popup.move_selection(false);
+				}
+				true
+			},
+			13 if completing => { //enter
+				self.accept_completion();
+				true
+			},
+			27 => { //esc
+				self.popup = None;
+				true
+			},
+			_ => {
+				if completing == false
+				{
+					self.popup = None;
+				}
+				false
+			}
+		}
+	}
+
+	//replace the typed start of the word with the selected completion
+	fn accept_completion(&mut self)
+	{
+		let (line, word_start, insert) = match self.popup.take()
+		{
+			Some(popup) => match popup.get_selected()
+			{
+				Some(insert) => (popup.line, popup.word_start, insert.to_owned()),
+				None => {return;}
+			},
+			None => {return;}
+		};
+		let cursor_char = self.data.get_cursor_char();
+		self.data.replace_text((line, word_start), (line, cursor_char), &insert);
+	}
+
+	//filter the completions by the typed part of the word, close the popup if the cursor left the word
+	fn update_popup(&mut self)
+	{
+		let prefix = match self.popup
+		{
+			Some(ref popup) if popup.is_completion() => {
+				let cursor_char = self.data.get_cursor_char();
+				let line = self.data.get_line(popup.line);
+				match line
+				{
+					Some(line) if self.data.get_cursor_line() == popup.line && cursor_char >= popup.word_start && cursor_char <= line.len() => {
+						let typed: String = line[popup.word_start..cursor_char].iter().collect();
+						if typed.chars().all(filedata::is_word_char) { Some(typed) } else { None }
+					},
+					_ => None
+				}
+			},
+			_ => {return;}
+		};
+		let keep = match (prefix, self.popup.as_mut())
+		{
+			(Some(prefix), Some(popup)) => popup.filter(&prefix),
+			_ => false
+		};
+		if keep == false
+		{
+			self.popup = None;
+		}
+	}
+
+	//offer the words of all buffers that start with the word typed at the cursor
+	fn complete_word(&mut self)
+	{
+		if self.config.auto_complete == false || self.popup.is_some()
+		{
+			return;
+		}
+		let line = self.data.get_cursor_line();
+		let cursor_char = self.data.get_cursor_char();
+		let content = self.data.get_line(line).unwrap().clone();
+		if cursor_char < content.len() && filedata::is_word_char(content[cursor_char]) //not in the middle of a word
+		{
+			return;
+		}
+		let mut word_start = cursor_char;
+		while word_start > 0 && filedata::is_word_char(content[word_start - 1])
+		{
+			word_start -= 1;
+		}
+		if cursor_char - word_start < words::MIN_PREFIX_LEN
+		{
+			return;
+		}
+		let prefix: String = content[word_start..cursor_char].iter().collect();
+		let mut counts: HashMap<String, usize> = HashMap::new();
+		for buffer in Some(&mut self.data).into_iter().chain(self.buffers.iter_mut().filter(|buffer| buffer.is_scratch() == false))
+		{
+			for (word, count) in buffer.get_words().iter().filter(|&(word, _)| word.starts_with(&prefix))
+			{
+				*counts.entry(word.clone()).or_insert(0) += *count;
+			}
+		}
+		let nearby = words::nearby(&self.data, line);
+		let ranked = words::rank(&counts, &nearby, &prefix);
+		if ranked.is_empty() == false
+		{
+			let items = ranked.into_iter().map(|word| (word.clone(), word)).collect();
+			self.popup = Some(Popup::completion(items, line, word_start));
+		}
+	}
+
+	//show the file tree, give it focus, or hide it
+	fn toggle_browser(&mut self)
+	{
+		if self.browser.is_none()
+		{
+			self.browser = Some(Browser::new(PathBuf::from(".")));
+			self.browser_focus = true;
+		}
+		else if self.browser_focus == false
+		{
+			self.browser_focus = true;
+		}
+		else 
+		{
+		    self.browser = None;
+			self.browser_focus = false;
+		}
+		self.input_active = false;
+	}
+
+	//key handle while the file tree has focus, returns false for keys it doesn't use
+	fn browser_key_event(&mut self, key: u16, character: char) -> bool
+	{
+		let rows = self.height.saturating_sub(1);
+		let (path, is_dir) = {
+			let browser = match self.browser
+			{
+				Some(ref mut v) => v,
+				None => {
+					self.browser_focus = false;
+					return false;
+				}
+			};
+			match key
+			{
+				65517 => { //up
+					browser.move_selection(true, rows);
+					return true;
+				},
+				65516 => { //down
+					browser.move_selection(false, rows);
+					return true;
+				},
+				65515 => { //left
+					browser.collapse_selected();
+					return true;
+				},
+				27 => { //esc
+					self.browser_focus = false;
+					return true;
+				},
+				_ => {}
+			}
+			match browser.get_selected()
+			{
+				Some(entry) => (entry.path.to_string_lossy().into_owned(), entry.is_dir),
+				None => (String::new(), false)
+			}
+		};
+		match key
+		{
+			65514 | 13 => { //right, enter
+				if is_dir
+				{
+					if let Some(ref mut browser) = self.browser
+					{
+						browser.toggle_selected();
+					}
+				}
+				else if key == 13 && path.is_empty() == false
+				{
+					self.open_file(path);
+					self.browser_focus = false;
+				}
+				true
+			},
+			_ => {
+				match character
+				{
+					'a' => { //add a file in the selected directory
+						let dir = self.browser.as_ref().map(|browser| browser.get_selected_dir()).unwrap_or_default();
+						let dir = dir.to_string_lossy().into_owned();
+						if dir.is_empty()
+						{
+							self.preset_input(String::from("touch "));
+						}
+						else 
+						{
+						    self.preset_input(format!("touch {}/", dir));
+						}
+						true
+					},
+					'r' if path.is_empty() == false => {
+						self.preset_input(format!("rename {} {}", path, path));
+						true
+					},
+					'd' if path.is_empty() == false => {
+						self.execute_internal(format!("delete {}", path));
+						true
+					},
+					//ctrl keys keep working, everything else is swallowed
+					_ => key != 0 && key < 32
+				}
+			}
+		}
+	}
+
+	//key handle while the fuzzy finder is shown
+	fn finder_key_event(&mut self, key: u16, character: char)
+	{
+		let mut finder = match self.finder.take()
+		{
+			Some(v) => v,
+			None => {return;}
+		};
+		match key
+		{
+			27 => { //esc
+				return;
+			},
+			13 => { //enter
+				match finder.get_selected_path()
+				{
+					Some(path) => self.open_file(path),
+					None => {
+						self.notify(String::from("error: no matching file"));
+					}
+				}
+				return;
+			},
+			65517 => { //up
+				finder.move_selection(true);
+			},
+			65516 => { //down
+				finder.move_selection(false);
+			},
+			127 => { //bsp
+				let mut query = finder.query.clone();
+				query.pop();
+				finder.set_query(query);
+			},
+			_ => {
+				if key == 32 || character.is_control() == false
+				{
+					let mut query = finder.query.clone();
+					query.push(if key == 32 { ' ' } else { character });
+					finder.set_query(query);
+				}
+			}
+		}
+		self.finder = Some(finder);
 	}
 
 	//key handle if the user is being asked a question
@@ -275,6 +1179,9 @@ impl Display
 	fn question_answered(&mut self)
 	{
 		let mut notification_vec = Vec::new();
+		let mut close = false;
+		let mut save = None;
+		let mut opened = false;
 		{
 			let answer = self.yn_question_state.clone();
 			let question = match self.yn_question
@@ -290,16 +1197,21 @@ impl Display
 				YNOption::NewIgnoreModified => {
 					if answer == true
 					{
+						self.close_server_document();
 						self.data.clear();
 					}
 				},
 				YNOption::OpenIgnoreModified(ref path) => {
 					if answer == true
 					{
-						match self.data.open(path.clone())
+						let path = path.clone();
+						self.states.remember(&self.data, self.line_scroll);
+						self.close_server_document();
+						match self.data.open(path)
 			    		{
 			    			Ok(_) => {
 			    				notification_vec.push(format!("opened"));
+			    				opened = true;
 			    			},
 			    			Err(e) => {
 			    				notification_vec.push(format!("error: {}", e));
@@ -310,15 +1222,7 @@ impl Display
 				YNOption::SaveIgnoreExisting(ref path) => {
 					if answer == true
 					{
-						match self.data.save_to(path.clone())
-						{
-							Ok(_) => {
-								notification_vec.push(format!("saved"));
-							},
-							Err(e) => {
-								notification_vec.push(format!("error: {}", e));
-							}
-						}
+						save = Some(path.clone());
 					}
 				},
 				YNOption::QuitIgnoreModified => {
@@ -326,13 +1230,67 @@ impl Display
 					{
 						self.running = false;
 					}
+				},
+				YNOption::CloseIgnoreModified => {
+					if answer == true
+					{
+						close = true;
+					}
+				},
+				YNOption::CreateFile(ref path) => {
+					if answer == true
+					{
+						notification_vec.push(match fs::File::create(path)
+						{
+							Ok(_) => format!("created {}", path),
+							Err(e) => format!("error: {}", e)
+						});
+					}
+				},
+				YNOption::RenameFile(ref from, ref to) => {
+					if answer == true
+					{
+						notification_vec.push(match fs::rename(from, to)
+						{
+							Ok(_) => format!("renamed {} to {}", from, to),
+							Err(e) => format!("error: {}", e)
+						});
+					}
+				},
+				YNOption::DeleteFile(ref path) => {
+					if answer == true
+					{
+						let result = if Path::new(path).is_dir() { fs::remove_dir(path) } else { fs::remove_file(path) };
+						notification_vec.push(match result
+						{
+							Ok(_) => format!("deleted {}", path),
+							Err(e) => format!("error: {}", e)
+						});
+					}
 				}
 			}
 		}
+		if let Some(ref mut browser) = self.browser
+		{
+			browser.refresh();
+		}
+		if close
+		{
+			self.close_buffer();
+		}
+		if let Some(path) = save
+		{
+			self.save_data(path);
+		}
 		for notification in notification_vec
 		{
 			self.notify(notification);
 		}
+		if opened
+		{
+			self.check_conflicts();
+			self.line_scroll = self.states.restore(&mut self.data);
+		}
 		self.yn_question = None;
 	}
 
@@ -382,10 +1340,18 @@ impl Display
 
 	fn draw_optimized(&mut self)
 	{
+		if self.diff.is_some()
+		{
+			self.draw_all();
+			return;
+		}
+		self.update_xoff();
 		self.draw_cursor();
 		self.check_scroll();
 		self.draw_title();
-		if self.draw_cursor_only
+		//messages for the cursor line change with the cursor, extra cursors and matching brackets are drawn with the text
+		let brackets = self.brackets_shown || (self.config.match_brackets && self.data.matching_bracket().is_some());
+		if self.draw_cursor_only && self.signs.is_empty() && self.data.get_cursors().is_empty() && brackets == false
 		{
 			self.rustbox.present();
 			self.draw_cursor_only = false;
@@ -398,10 +1364,28 @@ impl Display
 
 	fn draw_all(&mut self)
 	{
+		self.update_xoff();
 		self.rustbox.clear();
-		self.draw_text();
-		self.draw_cursor();
+		self.draw_browser();
+		//another file was opened in place of the one compared with its version on disk
+		if self.diff.as_ref().map(|view| view.from_disk && view.other.get_path() != self.data.get_path()).unwrap_or(false)
+		{
+			self.end_diff();
+		}
+		if self.diff.is_some()
+		{
+			self.draw_diff();
+		}
+		else
+		{
+		    self.draw_text();
+		    self.draw_cursor();
+		}
 		self.draw_title();
+		self.draw_popup();
+		self.draw_diagnostic();
+		self.draw_completion();
+		self.draw_finder();
 		self.draw_question();
 		self.draw_message();
 		self.rustbox.present();
@@ -413,7 +1397,7 @@ impl Display
 		if self.message_queue.is_empty() == false
 		{
 			let message = self.message_queue.pop_back().unwrap();
-			let pos_x = self.width/2-message.len()/2;
+			let pos_x = self.width/2-text::str_width(&message)/2;
 			self.rustbox.print(pos_x, self.height-1, rustbox::RB_NORMAL, Color::Black, COLOR, &format!("{}", message));
 		}
 	}
@@ -443,251 +1427,2307 @@ impl Display
 		}
 	}
 
-	//fill a white rectangle on screen
-	fn fill_rect(&self, x: usize, y: usize, width: usize, height: usize)
+	//draw the completion candidates in a list below the commandline
+	fn draw_completion(&self)
 	{
-		let line = pad_to(String::new(), width);//create line string
-		for pos_y in y .. (height+y)
+		let completion = match self.completion
 		{
-			self.rustbox.print(x, pos_y, rustbox::RB_NORMAL, Color::White, Color::White, &line);
+			Some(ref v) if self.input_active => v,
+			_ => {return;}
+		};
+		let max_rows = if self.height > 4 { self.height / 2 } else { 1 };
+		let rows = if completion.candidates.len() < max_rows { completion.candidates.len() } else { max_rows };
+		let selected = completion.selected.unwrap_or(0);
+		let first = if selected >= rows { selected + 1 - rows } else { 0 };
+		let box_width = completion.candidates.iter().map(|candidate| text::str_width(candidate)).max().unwrap_or(0) + 2;
+		let line = self.input.get_line(0).unwrap();
+		let mut box_x = text::column_of(line, completion.start, TAB_SIZE as usize).saturating_sub(self.input_scroll);
+		if box_x + box_width > self.width
+		{
+			box_x = self.width.saturating_sub(box_width);
+		}
+		self.fill_rect(box_x, 1, box_width, rows);
+		for row in 0..rows
+		{
+			let index = first + row;
+			let bg = if completion.selected == Some(index) { COLOR } else { Color::White };
+			let text = pad_to(format!(" {}", completion.candidates[index]), box_width);
+			self.rustbox.print(box_x, 1 + row, rustbox::RB_NORMAL, Color::Black, bg, &text);
 		}
 	}
 
-	//draw status bar/commandline
-	fn draw_title(&self)
+	//draw hover text or completions below the cursor, or above it if there is no room
+	fn draw_popup(&self)
 	{
-		let mut title = String::new();
-		if self.input_active
+		let popup = match self.popup
+		{
+			Some(ref v) if self.input_active == false => v,
+			_ => {return;}
+		};
+		let rows = if popup.shown.len() < popup::POPUP_ROWS { popup.shown.len() } else { popup::POPUP_ROWS };
+		let visible = popup.scroll .. (popup.scroll + rows).min(popup.shown.len());
+		let mut box_width = visible.clone().map(|index| text::str_width(popup.get_label(index))).max().unwrap_or(0) + 2;
+		if box_width > popup::POPUP_WIDTH
 		{
-			let input_text = self.input.to_string_copy();
-			title.push_str(&input_text.trim());
-			self.rustbox.set_cursor(self.input.get_cursor_char() as isize, 0);
+			box_width = popup::POPUP_WIDTH;
 		}
-		else 
+		if box_width > self.width
 		{
-			if self.data.is_modified()
-			{
-				title.push('~');
-			}
-			title.push_str(&self.data.get_title());
-			let cursor_pos_text = format!("  [{},{}]  lines: {}", self.data.get_cursor_line()+1, self.data.get_cursor_char()+1, self.data.get_lines());
-			title.push_str(&cursor_pos_text);
-		}	
-		self.rustbox.print(0, 0, rustbox::RB_NORMAL, Color::Black, COLOR, &pad_to(title, self.width));
+			box_width = self.width;
+		}
+		let cursor_row = match self.diff
+		{
+			Some(ref view) => view.row_of(self.data.get_cursor_line()) as isize - view.scroll as isize + 1,
+			None => self.cursor_screen_row() + 1
+		};
+		if cursor_row < 1 || rows == 0
+		{
+			return;
+		}
+		let cursor_row = cursor_row as usize;
+		let box_y = if cursor_row + 1 + rows <= self.height { cursor_row + 1 } else { cursor_row.saturating_sub(rows) };
+		let mut box_x = if self.screen_cursor_char > 0 { self.screen_cursor_char as usize } else { 0 };
+		if box_x + box_width > self.width
+		{
+			box_x = self.width - box_width;
+		}
+		self.fill_rect(box_x, box_y, box_width, rows);
+		for (row, index) in visible.enumerate()
+		{
+			let bg = if popup.selected == Some(index) { COLOR } else { Color::White };
+			self.print_clipped((box_x, box_y + row), box_width, &pad_to(format!(" {}", popup.get_label(index)), box_width), rustbox::RB_NORMAL, Color::Black, bg);
+		}
 	}
 
-	fn draw_cursor(&mut self)
+	//show the build or language server message for the cursor line in the bottom row
+	fn draw_diagnostic(&self)
 	{
-		let cursor_line = self.data.get_cursor_line() as isize - self.line_scroll as isize + 1;
-		let mut cursor_char = self.data.get_cursor_char() as isize;
-		
-		//take into account that tabs use more space
-		let cur_line = match self.data.get_line(self.data.get_cursor_line())
+		if self.input_active || self.message_queue.is_empty() == false || self.height < 2
 		{
-			None => {return;},
-			Some(v) => v
+			return;
+		}
+		if let Some(message) = self.cursor_diagnostic()
+		{
+			let x = self.draw_xoff as usize;
+			let width = self.width.saturating_sub(x);
+			self.print_clipped((x, self.height - 1), width, &pad_to(message, width), rustbox::RB_NORMAL, Color::Black, COLOR);
+		}
+	}
+
+	//draw the file tree left of the text, the open file is highlighted
+	fn draw_browser(&self)
+	{
+		let browser = match self.browser
+		{
+			Some(ref v) => v,
+			None => {return;}
 		};
-		let mut char_counter = 0;
-		for ch in cur_line
+		let width = self.sidebar_width();
+		if width == 0
+		{
+			return;
+		}
+		let open_file = self.data.get_path().and_then(|path| fs::canonicalize(path).ok());
+		let rows = self.height.saturating_sub(1);
+		for (row, entry) in browser.get_entries().iter().skip(browser.scroll).take(rows).enumerate()
 		{
-			if char_counter >= self.data.get_cursor_char()
+			let index = browser.scroll + row;
+			let y = row + 1;
+			let marker = match (entry.is_dir, entry.expanded)
 			{
-				break;
+				(true, true) => "- ",
+				(true, false) => "+ ",
+				_ => "  "
+			};
+			let mut label = String::new();
+			for _ in 0..entry.depth
+			{
+				label.push_str("  ");
 			}
-			char_counter += 1;
-			if ch == &'\t'
+			label.push_str(marker);
+			label.push_str(&entry.name);
+			if entry.is_dir
 			{
-				cursor_char += TAB_SIZE - 1;
+				label.push('/');
 			}
+			let (fg, bg) = if index == browser.selected && self.browser_focus
+			{
+				(Color::Black, Color::White)
+			}
+			else if browser.is_open_file(entry, &open_file)
+			{
+				(COLOR, Color::Default)
+			}
+			else 
+			{
+			    (Color::White, Color::Default)
+			};
+			self.print_clipped((0, y), width - 1, &pad_to(label, width - 1), rustbox::RB_NORMAL, fg, bg);
 		}
-		let draw_x = self.draw_xoff - self.char_scroll as isize + cursor_char;
-		if cursor_line > 0 && draw_x >= 0
+		for y in 1..self.height
 		{
-			self.rustbox.set_cursor(draw_x, cursor_line);
-			
+			self.rustbox.print_char(width - 1, y, rustbox::RB_NORMAL, COLOR, Color::Default, '│');
 		}
-		self.screen_cursor_char = draw_x;
 	}
 
-	//draw the editor pane
-	//differentiates between the on-screen and in-data position of the cursor
-	//cur_line_data, cur_char_data -> data pointer position
-	//cur_line, cur_char -> display pointer position
-	fn draw_text(&mut self)
+	//draw the fuzzy finder box: query, ranked paths and a preview of the selected file
+	fn draw_finder(&self)
 	{
-		let mut cur_line = 1;
-		let mut cur_line_data = self.line_scroll;
-		'line: while cur_line < self.height
+		let finder = match self.finder
 		{
-			let line_content = match self.data.get_line(cur_line_data)
+			Some(ref v) => v,
+			None => {return;}
+		};
+		let box_width = if self.width > 25 { self.width * 4 / 5 } else { self.width };
+		let box_height = if self.height > 8 { self.height * 4 / 5 } else { self.height };
+		let box_x = (self.width - box_width) / 2;
+		let box_y = (self.height - box_height) / 2;
+		self.fill_rect(box_x, box_y, box_width, box_height);
+		let status = if finder.is_indexing()
+		{
+			format!("{}/{} indexing...", finder.result_count(), finder.file_count())
+		}
+		else 
+		{
+		    format!("{}/{}", finder.result_count(), finder.file_count())
+		};
+		let query = format!(" > {}", finder.query);
+		self.print_clipped((box_x, box_y), box_width, &query, rustbox::RB_BOLD, Color::Black, Color::White);
+		let status_width = text::str_width(&status);
+		if status_width + text::str_width(&query) + 2 < box_width
+		{
+			self.print_clipped((box_x + box_width - status_width - 1, box_y), status_width, &status, rustbox::RB_NORMAL, Color::Black, Color::White);
+		}
+		self.rustbox.set_cursor((box_x + text::str_width(&query)) as isize, box_y as isize);
+		//the list takes the whole box on small screens, half of it otherwise
+		let list_width = if box_width >= 60 { box_width / 2 } else { box_width };
+		let rows = box_height.saturating_sub(2);
+		let first = if finder.selected >= rows { finder.selected + 1 - rows } else { 0 };
+		for row in 0..rows
+		{
+			let index = first + row;
+			let (path, positions) = match finder.get_result(index)
 			{
-				None => {break 'line;},
-				Some(v) => v
+				Some(v) => v,
+				None => {break;}
 			};
-			let mut cur_char = self.draw_xoff - self.char_scroll as isize;
-			let mut cur_char_data = 0;
-			'char: while cur_char < self.width as isize
+			let y = box_y + 2 + row;
+			let bg = if index == finder.selected { COLOR } else { Color::White };
+			self.print_clipped((box_x, y), list_width, &pad_to(String::new(), list_width), rustbox::RB_NORMAL, Color::Black, bg);
+			let mut x = box_x + 1;
+			for (char_index, ch) in path.chars().enumerate()
 			{
-				let char_content = match line_content.get(cur_char_data)
-				{
-					None => {break 'char;},
-					Some(v) => v
-				};
-				if char_content != &'\t'
+				let width = text::char_width(ch);
+				if x + width >= box_x + list_width
 				{
-					if cur_char >= self.draw_xoff
-					{
-						self.rustbox.print(cur_char as usize, cur_line, rustbox::RB_NORMAL, Color::White, Color::Default, &format!("{}", char_content));
-					}
-					cur_char += 1;
+					break;
 				}
-				else
+				let (style, fg) = if positions.contains(&char_index) { (rustbox::RB_BOLD, Color::Red) } else { (rustbox::RB_NORMAL, Color::Black) };
+				self.rustbox.print_char(x, y, style, fg, bg, ch);
+				x += width;
+			}
+		}
+		if list_width < box_width
+		{
+			if let Some(lines) = finder.get_preview()
+			{
+				let preview_x = box_x + list_width + 1;
+				for (row, line) in lines.iter().take(rows).enumerate()
 				{
-					cur_char += TAB_SIZE;
+					self.print_clipped((preview_x, box_y + 2 + row), box_width - list_width - 2, line, rustbox::RB_NORMAL, Color::Blue, Color::White);
 				}
-				cur_char_data += 1;
 			}
-			self.rustbox.print(cur_char as usize, cur_line, rustbox::RB_NORMAL, COLOR, Color::Default, "´");
-			cur_line += 1;
-			cur_line_data += 1;
 		}
 	}
 
-	//open the commandline with a preset command
-	fn preset_input(&mut self, command: String)
+	//print a string, but no further than width columns
+	fn print_clipped(&self, (x, y): (usize, usize), width: usize, string: &str, style: rustbox::Style, fg: Color, bg: Color)
 	{
-		self.input_active = true;
-		self.input.clear();
-		for ch in command.chars()
+		let mut used = 0;
+		for ch in string.chars()
 		{
-			self.input.write_char(ch);
+			let ch_width = text::char_width(ch);
+			if used + ch_width > width
+			{
+				break;
+			}
+			self.rustbox.print_char(x + used, y, style, fg, bg, ch);
+			used += ch_width;
 		}
 	}
 
-	//execute the command entered in the commandline
-	fn execute_input(&mut self)
+	//fill a white rectangle on screen
+	fn fill_rect(&self, x: usize, y: usize, width: usize, height: usize)
 	{
-		let command = self.input.to_string_copy();
-		self.execute_internal(command);
+		let line = pad_to(String::new(), width);//create line string
+		for pos_y in y .. (height+y)
+		{
+			self.rustbox.print(x, pos_y, rustbox::RB_NORMAL, Color::White, Color::White, &line);
+		}
 	}
 
-	//match command + execute
-	fn execute_internal(&mut self, mut command: String)
+	//draw status bar/commandline
+	fn draw_title(&mut self)
 	{
-		command = command.trim().to_owned();
-		self.input.clear();
-		self.input_active = false;
-		let mut split_iter = command.split_whitespace();
-		let op: String = match split_iter.next()
+		let mut title = String::new();
+		if self.input_active
 		{
-			Some(v) => v.to_owned(),
-			None => {
-				self.notify(format!("error: no input"));
-				return;
+			//scroll the commandline horizontally so the cursor stays visible
+			let line = self.input.get_line(0).unwrap().clone();
+			let cursor_x = text::column_of(&line, self.input.get_cursor_char(), TAB_SIZE as usize);
+			if cursor_x < self.input_scroll
+			{
+				self.input_scroll = cursor_x;
 			}
-		};
-		if &op == "new"
+			if self.width > 0 && cursor_x >= self.input_scroll + self.width
+			{
+				self.input_scroll = cursor_x + 1 - self.width;
+			}
+			for cell in text::layout(&line, TAB_SIZE as usize)
+			{
+				if cell.column >= self.input_scroll && cell.column + cell.width <= self.input_scroll + self.width
+				{
+					title.push(cell.ch);
+				}
+			}
+			self.rustbox.set_cursor((cursor_x - self.input_scroll) as isize, 0);
+		}
+		else 
 		{
 			if self.data.is_modified()
 			{
-				self.create_yn_req(YNOption::NewIgnoreModified);
+				title.push('~');
 			}
-			else 
+			title.push_str(&self.data.get_title());
+			if let Some(ref view) = self.diff
 			{
-			    self.data.clear();
+				let other = if view.from_disk { String::from("disk") } else { view.other.get_title() };
+				title.push_str(&format!(" <> {}", other));
+			}
+			let column = match self.data.get_line(self.data.get_cursor_line())
+			{
+				Some(v) => text::column_of(v, self.data.get_cursor_char(), TAB_SIZE as usize),
+				None => 0
+			};
+			let cursor_pos_text = format!("  [{},{}]  col: {}  lines: {}", self.data.get_cursor_line()+1, self.data.get_cursor_char()+1, column+1, self.data.get_lines());
+			title.push_str(&cursor_pos_text);
+			if self.conflicts.is_empty() == false
+			{
+				title.push_str(&format!("  conflicts: {}", self.conflicts.len()));
+			}
+			if self.data.get_cursors().is_empty() == false
+			{
+				title.push_str(&format!("  cursors: {}", self.data.get_cursors().len() + 1));
+			}
+		}	
+		self.rustbox.print(0, 0, rustbox::RB_NORMAL, Color::Black, COLOR, &pad_to(title, self.width));
+	}
+
+	fn draw_cursor(&mut self)
+	{
+		//take into account that tabs and wide characters use more space
+		let (cursor_char, row_column) = match self.data.get_line(self.data.get_cursor_line())
+		{
+			None => {return;},
+			Some(v) => {
+				let rows = self.line_rows(v);
+				let row = text::row_of(&rows, self.data.get_cursor_char());
+				(text::column_of(v, self.data.get_cursor_char(), TAB_SIZE as usize) as isize, rows[row].column as isize)
 			}
+		};
+		let cursor_line = self.cursor_screen_row() + 1;
+		let draw_x = self.draw_xoff - row_column + cursor_char;
+		if cursor_line > 0 && draw_x >= 0
+		{
+			self.rustbox.set_cursor(draw_x, cursor_line);
+			
 		}
-		else if &op == "save"
+		if self.browser_focus && self.input_active == false
 		{
-			//save current file if no arg was given
-			let path = match split_iter.next()
+			self.rustbox.set_cursor(-1, -1); //the file tree has focus, hide the text cursor
+		}
+		self.screen_cursor_char = draw_x;
+	}
+
+	//bring the changes of the current buffer against its version in git up to date
+	fn update_git(&mut self)
+	{
+		let path = match self.data.get_path()
+		{
+			Some(v) => v,
+			None => {return;}
+		};
+		if self.git.contains_key(&path) == false
+		{
+			if Path::new(&path).exists() == false //not saved yet
 			{
-				Some(v) => v.to_owned(),
-				None => {
-					if self.data.get_path() == None
-					{
-						self.notify(format!("error: file is unnamed"))
-					}
-					else 
-					{
-						match self.data.save()
-						{
-							Ok(_) => {
-								self.notify(format!("saved"))
-							},
-							Err(e) => {
-								self.notify(format!("error: {}", e));
-							}
-						}
-					}
-					return;
+				return;
+			}
+			self.git.insert(path.clone(), GitDiff::load(&path));
+		}
+		if let Some(git) = self.git.get_mut(&path)
+		{
+			git.update(self.data.get_content(), self.data.get_version());
+		}
+	}
+
+	//the git changes of the current buffer, None if its file is not tracked
+	fn current_git(&self) -> Option<&GitDiff>
+	{
+		self.data.get_path().and_then(|path| self.git.get(&path)).filter(|git| git.is_tracked())
+	}
+
+	//end recording a macro, from_commandline -> the keys of the command that ended it are left out
+	fn stop_recording(&mut self, from_commandline: bool)
+	{
+		match self.macros.stop(from_commandline)
+		{
+			Some((name, 0)) => self.notify(format!("nothing recorded, {} is unchanged", name)),
+			Some((name, len)) => self.notify(format!("recorded {} keys into {}", len, name)),
+			None => {}
+		}
+	}
+
+	//press the keys of a macro count times
+	fn play_macro(&mut self, name: &str, count: usize)
+	{
+		let keys = match self.macros.get(name)
+		{
+			Some(v) => v,
+			None => {
+				self.notify(format!("error: no macro: {}", name));
+				return;
+			}
+		};
+		if self.macros.playing >= macros::MAX_DEPTH
+		{
+			self.notify(String::from("error: macros play each other too deep"));
+			return;
+		}
+		self.macros.last = name.to_owned();
+		self.macros.playing += 1;
+		'repeat: for _ in 0..count
+		{
+			for &(key, character) in &keys
+			{
+				self.key_event(key, character);
+				if self.running == false
+				{
+					break 'repeat;
 				}
-			};
-			match self.data.get_path()
+			}
+		}
+		self.macros.playing -= 1;
+	}
+
+	//fill a part of a screen row with a color
+	fn fill_row(&self, x: usize, y: usize, width: usize, color: Color)
+	{
+		let spaces: String = (0..width).map(|_| ' ').collect();
+		self.rustbox.print(x, y, rustbox::RB_NORMAL, Color::White, color, &spaces);
+	}
+
+	//the text color of a line, parts of merge conflicts are colored
+	fn conflict_color(&self, line: usize) -> Color
+	{
+		match self.conflicts.iter().find(|conflict| conflict.contains(line)).map(|conflict| conflict.region(line))
+		{
+			Some(conflict::Region::Marker) => COLOR,
+			Some(conflict::Region::Ours) => Color::Green,
+			Some(conflict::Region::Base) => Color::Magenta,
+			Some(conflict::Region::Theirs) => Color::Cyan,
+			None => Color::White
+		}
+	}
+
+	//find the merge conflicts of the current buffer again if it changed
+	fn update_conflicts(&mut self)
+	{
+		let key = (self.data.get_title(), self.data.get_version());
+		if self.conflicts_key.as_ref() == Some(&key)
+		{
+			return;
+		}
+		self.conflicts = conflict::find(self.data.get_content());
+		self.conflicts_key = Some(key);
+	}
+
+	//tell about merge conflicts in a file that was just opened and go to the first one
+	fn check_conflicts(&mut self)
+	{
+		self.update_conflicts();
+		if let Some(first) = self.conflicts.first().cloned()
+		{
+			self.data.set_cursor(first.start, 0);
+			self.notify(format!("{} merge conflicts, resolve them with ours, theirs or both", self.conflicts.len()));
+		}
+	}
+
+	//the blame shown for the current buffer
+	fn current_blame(&self) -> Option<&Blame>
+	{
+		if self.show_blame == false
+		{
+			return None;
+		}
+		self.data.get_path().and_then(|path| self.blames.get(&path))
+	}
+
+	//collect finished blames, blame the current buffer again if it changed since
+	fn poll_blame(&mut self) -> bool
+	{
+		let mut redraw = false;
+		let mut errors = Vec::new();
+		for blame in self.blames.values_mut()
+		{
+			match blame.poll()
 			{
-				Some(v) => {
-					if v != path
-					{
-						let path_copy = path.clone();
-						let new_file = Path::new(&path_copy);
-						if new_file.exists() == true
-						{
-							self.create_yn_req(YNOption::SaveIgnoreExisting(path));
-							return;
-						}
-					}
-				},
+				Some(Ok(_)) => redraw = true,
+				Some(Err(e)) => errors.push(e),
 				None => {}
 			}
-			match self.data.save_to(path.clone())
+		}
+		for e in errors
+		{
+			self.notify(format!("error: {}", e));
+			self.show_blame = false;
+			redraw = true;
+		}
+		let path = match self.data.get_path()
+		{
+			Some(ref v) if self.show_blame => v.clone(),
+			_ => {return redraw;}
+		};
+		//only started while no key is pressed, typing does not wait for git
+		let version = self.data.get_version();
+		let start = match self.blames.get(&path)
+		{
+			Some(blame) => blame.is_running() == false && blame.is_current(version) == false,
+			None => true
+		};
+		if start
+		{
+			let text = self.data.to_string_copy();
+			self.blames.entry(path.clone()).or_insert_with(|| Blame::new(path)).start(text, version);
+			redraw = true;
+		}
+		redraw
+	}
+
+	//the marks for lines of the current buffer, e.g. build errors
+	fn gutter_signs(&self) -> HashMap<usize, (char, Color)>
+	{
+		let mut result = HashMap::new();
+		let path = match self.data.get_path().and_then(|path| fs::canonicalize(path).ok())
+		{
+			Some(v) => v,
+			None => {return result;}
+		};
+		if let Some(ref build) = self.build
+		{
+			for (line, is_error) in build.lines_in(&path)
 			{
-				Ok(_) => {
-					self.data.set_path(Some(path));
-					self.notify(format!("saved"));
-				},
-				Err(e) => self.notify(format!("error: {}", e))
+				if is_error
+				{
+					result.insert(line, ('E', Color::Red));
+				}
+				else
+				{
+				    result.entry(line).or_insert(('W', COLOR));
+				}
 			}
 		}
-		else if &op == "open"
+		let path = path.to_string_lossy().into_owned();
+		for client in self.servers.values()
 		{
-			let path = match split_iter.next()
+			for diagnostic in client.get_diagnostics(&path).into_iter().flat_map(|list| list.iter())
 			{
-				Some(v) => v.to_owned(),
-				None => {
-					self.notify(format!("error: usage: open <file>"));
-					return;
+				if diagnostic.is_error
+				{
+					result.insert(diagnostic.line, ('E', Color::Red));
 				}
-			};
-			if self.data.is_modified()
+				else
+				{
+				    result.entry(diagnostic.line).or_insert(('W', COLOR));
+				}
+			}
+		}
+		if let Some(git) = self.current_git()
+		{
+			for (line, change) in git.changes()
 			{
-				self.create_yn_req(YNOption::OpenIgnoreModified(path));
+				let sign = match change
+				{
+					git::Change::Added => ('+', Color::Green),
+					git::Change::Modified => ('~', Color::Blue),
+					git::Change::Deleted => ('_', Color::Red)
+				};
+				result.entry(line).or_insert(sign);
 			}
-			else 
+		}
+		result
+	}
+
+	//the first build or language server message for the cursor line
+	fn cursor_diagnostic(&self) -> Option<String>
+	{
+		let line = self.data.get_cursor_line();
+		if self.signs.contains_key(&line) == false
+		{
+			return None;
+		}
+		let path = match self.data.get_path().and_then(|path| fs::canonicalize(path).ok())
+		{
+			Some(v) => v,
+			None => {return None;}
+		};
+		if let Some(diagnostic) = self.build.as_ref().and_then(|build| build.diagnostic_at(&path, line))
+		{
+			return Some(diagnostic.message.clone());
+		}
+		let path = path.to_string_lossy().into_owned();
+		for client in self.servers.values()
+		{
+			if let Some(list) = client.get_diagnostics(&path)
 			{
-			    match self.data.open(path)
-			    {
-			    	Ok(_) => {
-			    		self.notify(format!("opened"));
-			    	},
-			    	Err(e) => {self.notify(format!("error: {}", e))}
-			    }
+				if let Some(diagnostic) = list.iter().find(|diagnostic| diagnostic.line == line)
+				{
+					return Some(diagnostic.message.clone());
+				}
 			}
 		}
-		else if &op == "quit"
+		None
+	}
+
+	//draw the sign and line number of a line, or nothing if this is a continued row
+	fn draw_gutter(&self, screen_line: usize, data_line: usize, first_row: bool)
+	{
+		let mut gutter_x = self.sidebar_width();
+		if let Some(blame) = self.current_blame()
 		{
-			if self.data.is_modified()
+			if let (true, Some(line)) = (first_row, blame.line(data_line))
 			{
-				self.create_yn_req(YNOption::QuitIgnoreModified);
+				self.print_clipped((gutter_x, screen_line), git::BLAME_WIDTH, &line.label(), rustbox::RB_NORMAL, Color::Blue, Color::Default);
 			}
-			else 
+			gutter_x += git::BLAME_WIDTH + 1;
+		}
+		if self.signs.is_empty() == false
+		{
+			if let (true, Some(&(sign, color))) = (first_row, self.signs.get(&data_line))
 			{
-			    self.running = false;
+				self.rustbox.print_char(gutter_x, screen_line, rustbox::RB_BOLD, color, Color::Default, sign);
 			}
+			gutter_x += 2;
+		}
+		if self.config.line_numbers == false
+		{
+			return;
+		}
+		let number = if first_row
+		{
+			format!("{}", data_line + 1)
 		}
 		else 
 		{
-		    self.notify(format!("error: unknown command: {}", op));
+		    String::new()
+		};
+		let gutter_width = self.draw_xoff as usize - gutter_x;
+		let mut text = String::new();
+		while text.len() + number.len() + 1 < gutter_width
+		{
+			text.push(' ');
+		}
+		text.push_str(&number);
+		self.rustbox.print(gutter_x, screen_line, rustbox::RB_NORMAL, COLOR, Color::Default, &text);
+	}
+
+	//draw the editor pane
+	//differentiates between the on-screen and in-data position of the cursor
+	//cur_line_data, cell.start -> data pointer position
+	//cur_line, cur_char -> display pointer position
+	fn draw_text(&mut self)
+	{
+		let mut cur_line = 1;
+		let mut cur_line_data = self.line_scroll;
+		let block = self.data.get_block();
+		let selection = if block.is_some() { None } else { self.data.get_selection() };
+		let cursors: HashSet<(usize, usize)> = self.data.get_cursors().iter().cloned().collect();
+		let brackets = if self.config.match_brackets { self.data.matching_bracket() } else { None };
+		self.brackets_shown = brackets.is_some();
+		'line: while cur_line < self.height
+		{
+			let line_content = match self.data.get_line(cur_line_data)
+			{
+				None => {break 'line;},
+				Some(v) => v
+			};
+			//spaces after the last visible char are shown in whitespace mode
+			let trailing_start = match line_content.iter().rposition(|ch| *ch != ' ' && *ch != '\t')
+			{
+				Some(v) => v + 1,
+				None => 0
+			};
+			let cells = text::layout(line_content, TAB_SIZE as usize);
+			let rows = self.line_rows(line_content);
+			let line_color = self.conflict_color(cur_line_data);
+			let fold_end = self.data.fold_end(cur_line_data);
+			for (row_index, row) in rows.iter().enumerate()
+			{
+				if cur_line >= self.height
+				{
+					break 'line;
+				}
+				self.draw_gutter(cur_line, cur_line_data, row_index == 0);
+				let line_start = self.draw_xoff - row.column as isize;
+				let mut cur_char = line_start + text::column_of(line_content, row.start, TAB_SIZE as usize) as isize;
+				for cell in cells.iter().filter(|cell| cell.start >= row.start && cell.start < row.end)
+				{
+					cur_char = line_start + cell.column as isize;
+					if cur_char >= self.width as isize
+					{
+						break;
+					}
+					let in_block = match block
+					{
+						Some((first, last, left, right)) => cur_line_data >= first && cur_line_data <= last && cell.column >= left && (cell.column < right || (left == right && cell.column == left)),
+						None => false
+					};
+					let (fg, bg) = match selection
+					{
+						_ if cursors.contains(&(cur_line_data, cell.start)) => (Color::Black, COLOR),
+						_ if in_block => (Color::Black, Color::White),
+						_ if brackets.map(|(here, other)| here == (cur_line_data, cell.start) || other == (cur_line_data, cell.start)).unwrap_or(false) => (Color::Black, Color::Cyan),
+						Some((start, end)) if (cur_line_data, cell.start) >= start && (cur_line_data, cell.start) < end => (Color::Black, Color::White),
+						_ => (line_color, Color::Default)
+					};
+					//clusters cut off by the left or right edge are not drawn
+					let visible = cur_char >= self.draw_xoff && cur_char + cell.width as isize <= self.width as isize;
+					let glyph = match cell.ch
+					{
+						'\t' if self.config.show_whitespace => self.config.tab_glyph,
+						' ' if self.config.show_whitespace && cell.start >= trailing_start => self.config.space_glyph,
+						_ => None
+					};
+					if let Some(glyph) = glyph
+					{
+						if visible
+						{
+							self.rustbox.print_char(cur_char as usize, cur_line, rustbox::RB_NORMAL, COLOR, bg, glyph);
+						}
+					}
+					else if cell.ch != '\t'
+					{
+						//a termbox cell holds one code point, so only the first char of a cluster is shown,
+						//combining marks and the rest of emoji sequences are dropped but keep their columns
+						if visible
+						{
+							self.rustbox.print_char(cur_char as usize, cur_line, rustbox::RB_NORMAL, fg, bg, cell.ch);
+						}
+					}
+					if cell.ch == '\t' && bg != Color::Default
+					{
+						let first = if glyph.is_some() { 1 } else { 0 };
+						for offset in first..cell.width as isize
+						{
+							if cur_char + offset >= self.draw_xoff && cur_char + offset < self.width as isize
+							{
+								self.rustbox.print((cur_char + offset) as usize, cur_line, rustbox::RB_NORMAL, fg, bg, " ");
+							}
+						}
+					}
+					cur_char += cell.width as isize;
+				}
+				let glyph = if row_index + 1 < rows.len()
+				{
+					cur_char = self.width as isize - 1;
+					self.config.wrap_glyph
+				}
+				else 
+				{
+				    self.config.eol_glyph
+				};
+				if let Some(glyph) = glyph
+				{
+					if cur_char >= self.draw_xoff && cur_char < self.width as isize
+					{
+						self.rustbox.print_char(cur_char as usize, cur_line, rustbox::RB_NORMAL, COLOR, Color::Default, glyph);
+					}
+				}
+				//an extra cursor at the end of the line
+				if row_index + 1 == rows.len() && cursors.contains(&(cur_line_data, line_content.len())) && cur_char >= self.draw_xoff && cur_char < self.width as isize
+				{
+					self.rustbox.print_char(cur_char as usize, cur_line, rustbox::RB_NORMAL, Color::Black, COLOR, ' ');
+				}
+				//a folded region is summed up behind its first line
+				if let (true, Some(last)) = (row_index + 1 == rows.len(), fold_end)
+				{
+					let x = cur_char + 2;
+					if x >= self.draw_xoff && x < self.width as isize
+					{
+						let summary = format!("... {} lines", last - cur_line_data);
+						self.print_clipped((x as usize, cur_line), self.width - x as usize, &summary, rustbox::RB_NORMAL, COLOR, Color::Default);
+					}
+				}
+				cur_line += 1;
+			}
+			//folded lines are skipped
+			cur_line_data = match self.data.visible_line(cur_line_data, true)
+			{
+				Some(v) => v,
+				None => {break 'line;}
+			};
+		}
+	}
+
+	//draw the buffer and the one it is compared with side by side, lines that differ are colored
+	fn draw_diff(&mut self)
+	{
+		let mut view = match self.diff.take()
+		{
+			Some(v) => v,
+			None => {return;}
+		};
+		view.update(&self.data);
+		let left_x = self.sidebar_width();
+		let pane_width = self.width.saturating_sub(left_x + 1) / 2;
+		let right_x = left_x + pane_width + 1;
+		let text_rows = self.height.saturating_sub(1);
+		//both sides scroll with the cursor in the left one
+		let cursor_row = view.row_of(self.data.get_cursor_line());
+		if cursor_row < view.scroll
+		{
+			view.scroll = cursor_row;
+		}
+		if text_rows > 0 && cursor_row >= view.scroll + text_rows
+		{
+			view.scroll = cursor_row + 1 - text_rows;
+		}
+		let cursor_column = match self.data.get_line(self.data.get_cursor_line())
+		{
+			Some(v) => text::column_of(v, self.data.get_cursor_char(), TAB_SIZE as usize),
+			None => 0
+		};
+		if cursor_column < view.column_scroll
+		{
+			view.column_scroll = cursor_column;
+		}
+		if pane_width > 0 && cursor_column >= view.column_scroll + pane_width
+		{
+			view.column_scroll = cursor_column + 1 - pane_width;
+		}
+		for (index, row) in view.rows.iter().skip(view.scroll).take(text_rows).enumerate()
+		{
+			//red: only on the left, green: only on the right, blue: different on both sides
+			let bg = match (row.changed, row.left.is_some(), row.right.is_some())
+			{
+				(false, _, _) => Color::Default,
+				(true, true, false) => Color::Red,
+				(true, false, true) => Color::Green,
+				_ => Color::Blue
+			};
+			let left = row.left.and_then(|line| self.data.get_line(line));
+			let right = row.right.and_then(|line| view.other.get_line(line));
+			self.draw_diff_line(left_x, index + 1, pane_width, view.column_scroll, left, bg);
+			self.rustbox.print_char(left_x + pane_width, index + 1, rustbox::RB_NORMAL, COLOR, Color::Default, '|');
+			self.draw_diff_line(right_x, index + 1, pane_width, view.column_scroll, right, bg);
+		}
+		self.screen_cursor_char = (left_x + cursor_column - view.column_scroll) as isize;
+		self.rustbox.set_cursor(self.screen_cursor_char, (cursor_row - view.scroll + 1) as isize);
+		self.diff = Some(view);
+	}
+
+	//draw one side of a row of the diff view, lines that differ are filled with the color
+	fn draw_diff_line(&self, x: usize, y: usize, width: usize, column_scroll: usize, line: Option<&Vec<char>>, bg: Color)
+	{
+		let line = match line
+		{
+			Some(v) => v,
+			None => {return;}
+		};
+		if bg != Color::Default
+		{
+			self.fill_row(x, y, width, bg);
+		}
+		for cell in text::layout(line, TAB_SIZE as usize)
+		{
+			if cell.column < column_scroll || cell.ch == '\t'
+			{
+				continue;
+			}
+			let column = cell.column - column_scroll;
+			if column + cell.width > width
+			{
+				break;
+			}
+			self.rustbox.print_char(x + column, y, rustbox::RB_NORMAL, Color::White, bg, cell.ch); //first char of the cluster, see draw_text
+		}
+	}
+
+	//compare the current buffer with another buffer, or with its file on disk if name is None
+	fn start_diff(&mut self, name: Option<String>)
+	{
+		self.end_diff();
+		let view = match name
+		{
+			Some(name) => {
+				let index = match self.find_buffer(&name)
+				{
+					Some(0) => {
+						self.notify(String::from("error: can't compare the buffer with itself"));
+						return;
+					},
+					Some(v) => v - 1,
+					None => {
+						self.notify(format!("error: no buffer: {}", name));
+						return;
+					}
+				};
+				DiffView::new(self.buffers.remove(index), false)
+			},
+			None => {
+				let path = match self.data.get_path()
+				{
+					Some(ref v) if Path::new(v).exists() => v.clone(),
+					_ => {
+						self.notify(String::from("error: the buffer has no file on disk"));
+						return;
+					}
+				};
+				match FileData::from(path)
+				{
+					Ok(v) => DiffView::new(v, true),
+					Err(e) => {
+						self.notify(format!("error: {}", e));
+						return;
+					}
+				}
+			}
+		};
+		self.diff = Some(view);
+		self.popup = None;
+	}
+
+	//leave the diff view, the buffer it was compared with is listed again
+	fn end_diff(&mut self)
+	{
+		if let Some(view) = self.diff.take()
+		{
+			if view.from_disk == false
+			{
+				self.buffers.push(view.other);
+			}
+		}
+	}
+
+	//open the commandline with a preset command
+	fn preset_input(&mut self, command: String)
+	{
+		self.input_active = true;
+		self.input.clear();
+		self.input_scroll = 0;
+		for ch in command.chars()
+		{
+			self.input.write_char(ch);
+		}
+	}
+
+	//the text in the commandline without the line break
+	fn input_text(&self) -> String
+	{
+		self.input.get_line(0).unwrap().iter().cloned().collect()
+	}
+
+	//key handle for keys with a special meaning in the commandline, returns true if the key was used
+	fn input_key_event(&mut self, key: u16) -> bool
+	{
+		if key == 9 //tab
+		{
+			self.complete_input();
+			return true;
+		}
+		self.completion = None;
+		let entry = match key
+		{
+			65517 => { //up
+				let current = self.input_text();
+				self.history.older(current)
+			},
+			65516 => { //down
+				self.history.newer()
+			},
+			18 => { //^R
+				let current = self.input_text();
+				match self.history.search(current)
+				{
+					Some(v) => Some(v),
+					None => {
+						self.notify(String::from("no older match in history"));
+						return true;
+					}
+				}
+			},
+			65521 | 1 => { //home, ^A
+				self.input.move_cursor_home();
+				return true;
+			},
+			65520 | 5 => { //end, ^E
+				self.input.move_cursor_end();
+				return true;
+			},
+			2 => { //^B
+				self.input.move_word_left();
+				return true;
+			},
+			6 => { //^F
+				self.input.move_word_right();
+				return true;
+			},
+			_ => {
+				return false;
+			}
+		};
+		if let Some(entry) = entry
+		{
+			self.preset_input(entry);
+		}
+		true
+	}
+
+	//complete the word before the cursor, or cycle through the candidates if tab was pressed before
+	fn complete_input(&mut self)
+	{
+		let line = self.input.get_line(0).unwrap().clone();
+		let cycled = match self.completion
+		{
+			Some(ref mut completion) => Some((completion.start, completion.cycle())),
+			None => None
+		};
+		if let Some((start, candidate)) = cycled
+		{
+			self.replace_input_word(start, candidate);
+			return;
+		}
+		let before: String = line[..self.input.get_cursor_char()].iter().cloned().collect();
+		let start = match before.rfind(' ')
+		{
+			Some(v) => before[..v+1].chars().count(),
+			None => 0
+		};
+		let word: String = line[start..self.input.get_cursor_char()].iter().cloned().collect();
+		//the words before the completed one
+		let mut previous: Vec<&str> = before.split_whitespace().collect();
+		if word.is_empty() == false
+		{
+			previous.pop();
+		}
+		let base_dir = match self.data.get_path()
+		{
+			Some(v) => Path::new(&v).parent().map(|parent| parent.to_path_buf()),
+			None => None
+		};
+		let candidates = complete::candidates(&previous, &word, base_dir.as_deref(), &self.buffer_names());
+		if candidates.is_empty()
+		{
+			self.notify(String::from("no completions"));
+			return;
+		}
+		let replacement = if candidates.len() == 1
+		{
+			candidates[0].clone()
+		}
+		else 
+		{
+		    complete::common_prefix(&candidates)
+		};
+		self.replace_input_word(start, replacement);
+		if candidates.len() > 1
+		{
+			self.completion = Some(Completion
+			{
+				start,
+				candidates,
+				selected: None
+			});
+		}
+	}
+
+	//replace the commandline text between start and the cursor, the text after the cursor stays
+	fn replace_input_word(&mut self, start: usize, replacement: String)
+	{
+		let line = self.input.get_line(0).unwrap().clone();
+		let mut new_line: String = line[..start].iter().cloned().collect();
+		new_line.push_str(&replacement);
+		let cursor = new_line.chars().count();
+		new_line.extend(line[self.input.get_cursor_char()..].iter());
+		self.preset_input(new_line);
+		self.input.set_cursor(0, cursor);
+	}
+
+	//execute the command entered in the commandline
+	fn execute_input(&mut self)
+	{
+		let command = self.input_text();
+		self.history.add(command.trim().to_owned());
+		self.execute_internal(command);
+	}
+
+	//match command + execute
+	fn execute_internal(&mut self, mut command: String)
+	{
+		command = command.trim().to_owned();
+		self.input.clear();
+		self.input_active = false;
+		//shell commands take the rest of the line as it is
+		if let Some(shell_command) = command.strip_prefix('!')
+		{
+			self.run_shell(shell_command.trim(), false);
+			return;
+		}
+		if command.starts_with("r !") || command.starts_with("r!")
+		{
+			let shell_command = command[1..].trim()[1..].trim().to_owned();
+			self.run_shell(&shell_command, true);
+			return;
+		}
+		if let Some(filter_command) = command.strip_prefix('|')
+		{
+			self.filter_text(filter_command.trim());
+			return;
+		}
+		let mut split_iter = command.split_whitespace();
+		let op: String = match split_iter.next()
+		{
+			Some(v) => v.to_owned(),
+			None => {
+				self.notify(format!("error: no input"));
+				return;
+			}
+		};
+		if &op == "new"
+		{
+			if self.data.is_modified()
+			{
+				self.create_yn_req(YNOption::NewIgnoreModified);
+			}
+			else 
+			{
+			    self.close_server_document();
+			    self.data.clear();
+			}
+		}
+		else if &op == "save"
+		{
+			//save current file if no arg was given
+			let path = match split_iter.next()
+			{
+				Some(v) => v.to_owned(),
+				None => {
+					match self.data.get_path()
+					{
+						Some(v) => {self.save_data(v);},
+						None => self.notify(String::from("error: file is unnamed"))
+					}
+					return;
+				}
+			};
+			match self.data.get_path()
+			{
+				Some(v) => {
+					if v != path
+					{
+						let path_copy = path.clone();
+						let new_file = Path::new(&path_copy);
+						if new_file.exists() == true
+						{
+							self.create_yn_req(YNOption::SaveIgnoreExisting(path));
+							return;
+						}
+					}
+				},
+				None => {}
+			}
+			if self.save_data(path.clone())
+			{
+				self.data.set_path(Some(path));
+			}
+		}
+		else if &op == "open"
+		{
+			let path = match split_iter.next()
+			{
+				Some(v) => v.to_owned(),
+				None => {
+					self.notify(format!("error: usage: open <file>"));
+					return;
+				}
+			};
+			self.open_file(path);
+		}
+		else if &op == "edit"
+		{
+			let path = match split_iter.next()
+			{
+				Some(v) => v.to_owned(),
+				None => {
+					self.notify(String::from("error: usage: edit <file>"));
+					return;
+				}
+			};
+			self.edit_file(path);
+		}
+		else if &op == "buffer"
+		{
+			match split_iter.next()
+			{
+				Some(v) => {
+					let name = v.to_owned();
+					self.select_buffer(name);
+				},
+				None => {
+					let names = self.buffer_names();
+					let list: Vec<String> = names.iter().enumerate().map(|(index, name)| format!("{}:{}", index+1, name)).collect();
+					self.notify(list.join("  "));
+				}
+			}
+		}
+		else if &op == "bclose"
+		{
+			if self.data.is_modified()
+			{
+				self.create_yn_req(YNOption::CloseIgnoreModified);
+			}
+			else 
+			{
+			    self.close_buffer();
+			}
+		}
+		else if &op == "touch" || &op == "delete"
+		{
+			let path = match split_iter.next()
+			{
+				Some(v) => v.to_owned(),
+				None => {
+					self.notify(format!("error: usage: {} <file>", op));
+					return;
+				}
+			};
+			if &op == "touch"
+			{
+				if Path::new(&path).exists()
+				{
+					self.notify(format!("error: {} already exists", path));
+					return;
+				}
+				self.create_yn_req(YNOption::CreateFile(path));
+			}
+			else 
+			{
+			    if Path::new(&path).exists() == false
+				{
+					self.notify(format!("error: {} does not exist", path));
+					return;
+				}
+				self.create_yn_req(YNOption::DeleteFile(path));
+			}
+		}
+		else if &op == "rename"
+		{
+			let (from, to) = match (split_iter.next(), split_iter.next())
+			{
+				(Some(from), Some(to)) => (from.to_owned(), to.to_owned()),
+				_ => {
+					self.notify(String::from("error: usage: rename <file> <new name>"));
+					return;
+				}
+			};
+			if from == to
+			{
+				return;
+			}
+			if Path::new(&to).exists()
+			{
+				self.notify(format!("error: {} already exists", to));
+				return;
+			}
+			self.create_yn_req(YNOption::RenameFile(from, to));
+		}
+		else if &op == "undo"
+		{
+			if self.data.undo() == false
+			{
+				self.notify(String::from("nothing to undo"));
+			}
+		}
+		else if &op == "redo"
+		{
+			if self.data.redo() == false
+			{
+				self.notify(String::from("nothing to redo"));
+			}
+		}
+		else if &op == "reflow"
+		{
+			let width = match split_iter.next()
+			{
+				Some(v) => match v.parse::<usize>()
+				{
+					Ok(v) if v > 0 => v,
+					_ => {
+						self.notify(String::from("error: usage: reflow [width]"));
+						return;
+					}
+				},
+				None => self.config.text_width
+			};
+			if self.data.reflow(width) == false
+			{
+				self.notify(String::from("error: no paragraph at the cursor"));
+			}
+		}
+		else if &op == "grep"
+		{
+			let pattern = match split_iter.next()
+			{
+				Some(v) => v.to_owned(),
+				None => {
+					self.notify(String::from("error: usage: grep <pattern> [path]"));
+					return;
+				}
+			};
+			let root = PathBuf::from(split_iter.next().unwrap_or("."));
+			if root.is_dir() == false
+			{
+				self.notify(format!("error: {} is not a directory", root.to_string_lossy()));
+				return;
+			}
+			self.show_scratch(grep::GREP_BUFFER, Vec::new()); //a new search replaces the results of the last one
+			self.grep = Some(Grep::start(pattern, root));
+		}
+		else if &op == "build" || &op == "make"
+		{
+			if self.build.as_ref().map(|build| build.is_running()).unwrap_or(false)
+			{
+				self.notify(String::from("error: a build is running already"));
+				return;
+			}
+			//the rest of the line replaces the configured command
+			let words: Vec<&str> = split_iter.collect();
+			let build_command = if words.is_empty() { self.config.build_command.clone() } else { words.join(" ") };
+			self.notify(format!("building: {}", build_command));
+			self.build = Some(Build::start(build_command));
+			self.signs_key = None;
+		}
+		else if &op == "nexterror" || &op == "preverror"
+		{
+			let location = match self.build
+			{
+				Some(ref mut build) => build.step(&op == "nexterror").map(|diagnostic| {
+					(diagnostic.path.clone(), diagnostic.line, diagnostic.column, diagnostic.message.clone())
+				}),
+				None => None
+			};
+			let (path, line, column, message) = match location
+			{
+				Some(v) => v,
+				None => {
+					self.notify(String::from("error: no build messages"));
+					return;
+				}
+			};
+			if Path::new(&path).exists() == false
+			{
+				self.notify(format!("error: {} does not exist", path));
+				return;
+			}
+			self.edit_file(path.clone());
+			if self.data.get_path() == Some(path)
+			{
+				self.data.set_cursor(line, column);
+			}
+			self.notify(message);
+		}
+		else if &op == "nexthunk" || &op == "prevhunk"
+		{
+			let line = match self.current_git()
+			{
+				Some(git) => git.step(self.data.get_cursor_line(), &op == "nexthunk"),
+				None => {
+					self.notify(String::from("error: the file is not tracked by git"));
+					return;
+				}
+			};
+			match line
+			{
+				Some(v) => self.data.set_cursor(v, 0),
+				None => self.notify(String::from("error: no changes"))
+			}
+		}
+		else if &op == "hunk" || &op == "reverthunk"
+		{
+			let line = self.data.get_cursor_line();
+			let change = match self.current_git()
+			{
+				Some(git) => git.hunk_at(line).map(|hunk| (hunk, git.base_lines(&hunk))),
+				None => {
+					self.notify(String::from("error: the file is not tracked by git"));
+					return;
+				}
+			};
+			let (hunk, lines) = match change
+			{
+				Some(v) => v,
+				None => {
+					self.notify(String::from("error: no change at the cursor"));
+					return;
+				}
+			};
+			if &op == "reverthunk"
+			{
+				self.data.replace_lines(hunk.new_start, hunk.new_len, lines);
+				self.notify(String::from("reverted"));
+			}
+			else if lines.is_empty()
+			{
+				self.notify(format!("{} lines added", hunk.new_len));
+			}
+			else
+			{
+			    let tab: String = (0..TAB_SIZE).map(|_| ' ').collect();
+			    let lines = lines.into_iter().map(|line| line.into_iter().collect::<String>().replace('\t', &tab)).collect();
+			    self.popup = Some(Popup::info(lines));
+			}
+		}
+		else if &op == "diff"
+		{
+			match split_iter.next()
+			{
+				Some(v) => self.start_diff(Some(v.to_owned())),
+				None if self.diff.is_some() => self.end_diff(),
+				None => self.start_diff(None)
+			}
+		}
+		else if &op == "nextchange" || &op == "prevchange" || &op == "push" || &op == "pull"
+		{
+			let mut view = match self.diff.take()
+			{
+				Some(v) => v,
+				None => {
+					self.notify(String::from("error: no diff is shown"));
+					return;
+				}
+			};
+			view.update(&self.data);
+			let line = self.data.get_cursor_line();
+			let lines = self.data.get_lines();
+			if &op == "nextchange" || &op == "prevchange"
+			{
+				match view.step(line, lines, &op == "nextchange")
+				{
+					Some(v) => self.data.set_cursor(v, 0),
+					None => self.notify(String::from("no differences"))
+				}
+			}
+			else
+			{
+				match view.hunk_at(line, lines)
+				{
+					Some(_) if &op == "push" && view.from_disk => {
+						self.notify(String::from("error: the file on disk is not changed by push, save instead"));
+					},
+					Some(hunk) if &op == "push" => {
+						let lines = self.data.get_content()[hunk.old_start .. hunk.old_start + hunk.old_len].to_vec();
+						view.other.replace_lines(hunk.new_start, hunk.new_len, lines);
+					},
+					Some(hunk) => {
+						let lines = view.other.get_content()[hunk.new_start .. hunk.new_start + hunk.new_len].to_vec();
+						self.data.replace_lines(hunk.old_start, hunk.old_len, lines);
+					},
+					None => self.notify(String::from("error: no difference at the cursor"))
+				}
+			}
+			self.diff = Some(view);
+		}
+		else if &op == "ours" || &op == "theirs" || &op == "both"
+		{
+			let line = self.data.get_cursor_line();
+			self.update_conflicts();
+			let conflict = match self.conflicts.iter().find(|conflict| conflict.contains(line)).cloned()
+			{
+				Some(v) => v,
+				None => {
+					self.notify(String::from("error: no conflict at the cursor"));
+					return;
+				}
+			};
+			let lines = conflict.resolve(self.data.get_content(), &op);
+			self.data.replace_lines(conflict.start, conflict.end + 1 - conflict.start, lines);
+			self.update_conflicts();
+			let left = self.conflicts.len();
+			self.notify(format!("{} conflicts left", left));
+		}
+		else if &op == "nextconflict" || &op == "prevconflict"
+		{
+			let line = self.data.get_cursor_line();
+			self.update_conflicts();
+			let starts: Vec<usize> = self.conflicts.iter().map(|conflict| conflict.start).collect();
+			let next = if &op == "nextconflict"
+			{
+				starts.iter().find(|start| **start > line).or(starts.first()).cloned()
+			}
+			else
+			{
+			    starts.iter().rev().find(|start| **start < line).or(starts.last()).cloned()
+			};
+			match next
+			{
+				Some(v) => self.data.set_cursor(v, 0),
+				None => self.notify(String::from("no conflicts"))
+			}
+		}
+		else if &op == "nextoccurrence" || &op == "cursorabove" || &op == "cursorbelow" || &op == "cursorlines"
+		{
+			let added = match op.as_str()
+			{
+				"nextoccurrence" => self.data.add_next_occurrence(),
+				"cursorlines" => self.data.add_cursors_on_selection(),
+				_ => self.data.add_cursor_vertical(&op == "cursorabove")
+			};
+			if added == false
+			{
+				let reason = match op.as_str()
+				{
+					"nextoccurrence" => "no other occurrence",
+					"cursorlines" => "nothing selected",
+					_ => "no line to add a cursor on"
+				};
+				self.notify(format!("error: {}", reason));
+			}
+		}
+		else if &op == "blockcopy" || &op == "blockcut"
+		{
+			match self.data.block_text()
+			{
+				Some(rows) =>
+				{
+					self.notify(format!("{} rows copied", rows.len()));
+					self.block_clipboard = rows;
+					if &op == "blockcut"
+					{
+						self.data.delete_block();
+					}
+				}
+				None => self.notify(String::from("error: no block selected, start one with ^B"))
+			}
+		}
+		else if &op == "blockpaste"
+		{
+			if self.block_clipboard.is_empty()
+			{
+				self.notify(String::from("error: no block copied"));
+				return;
+			}
+			let rows = self.block_clipboard.clone();
+			self.data.paste_block(&rows);
+		}
+		else if &op == "blockfill"
+		{
+			let ch = match split_iter.next().and_then(|arg| arg.chars().next())
+			{
+				Some(v) => v,
+				None => {
+					self.notify(String::from("error: usage: blockfill <char>"));
+					return;
+				}
+			};
+			if self.data.fill_block(ch) == false
+			{
+				self.notify(String::from("error: no block with columns selected"));
+			}
+		}
+		else if &op == "fold"
+		{
+			if self.data.toggle_fold(self.config.fold_brackets) == false
+			{
+				self.notify(String::from("error: nothing to fold here"));
+			}
+		}
+		else if &op == "foldall"
+		{
+			let count = self.data.fold_all(self.config.fold_brackets);
+			self.notify(format!("{} folds", count));
+		}
+		else if &op == "unfoldall"
+		{
+			self.data.unfold_all();
+		}
+		else if &op == "record"
+		{
+			if self.macros.is_recording()
+			{
+				self.stop_recording(true);
+				return;
+			}
+			let name = split_iter.next().unwrap_or(macros::DEFAULT_REGISTER).to_owned();
+			self.macros.start(&name);
+			self.notify(format!("recording into {}, stop with F4 or record", name));
+		}
+		else if &op == "play"
+		{
+			let mut name = self.macros.last.clone();
+			let mut count = 1;
+			for word in split_iter
+			{
+				match word.parse::<usize>()
+				{
+					Ok(v) => count = v,
+					Err(_) => name = word.to_owned()
+				}
+			}
+			self.play_macro(&name, count);
+		}
+		else if &op == "blame"
+		{
+			self.show_blame = !self.show_blame;
+			if self.show_blame
+			{
+				if self.data.get_path().is_none()
+				{
+					self.show_blame = false;
+					self.notify(String::from("error: the buffer has no file"));
+					return;
+				}
+				self.poll_blame();
+			}
+		}
+		else if &op == "showcommit"
+		{
+			let line = self.data.get_cursor_line();
+			let hash = match self.current_blame().and_then(|blame| blame.line(line))
+			{
+				Some(v) if v.is_committed() => v.hash.clone(),
+				Some(_) => {
+					self.notify(String::from("error: the line is not committed"));
+					return;
+				},
+				None => {
+					self.notify(String::from("error: no blame for the line, see the blame command"));
+					return;
+				}
+			};
+			let path = self.data.get_path().unwrap_or_default();
+			match git::commit_message(&path, &hash)
+			{
+				Ok(lines) => self.show_scratch(git::COMMIT_BUFFER, lines),
+				Err(e) => self.notify(format!("error: {}", e))
+			}
+		}
+		else if &op == "hover" || &op == "definition" || &op == "references" || &op == "complete"
+		{
+			self.server_request(&op);
+		}
+		else if &op == "set"
+		{
+			let key = split_iter.next().unwrap_or("").to_owned();
+			let value = split_iter.collect::<Vec<&str>>().join(" "); //formatter commands have arguments
+			if key.is_empty() || value.is_empty()
+			{
+				self.notify(String::from("error: usage: set <option> <value>"));
+				return;
+			}
+			match self.config.set(&key, &value)
+			{
+				Ok(_) => {
+					self.apply_config();
+					self.notify(format!("{} = {}", key, value));
+				},
+				Err(e) => self.notify(format!("error: {}", e))
+			}
+		}
+		else if &op == "quit"
+		{
+			//the other side of a diff is a buffer too, it is out of self.buffers while the diff is shown
+			let diff_modified = self.diff.as_ref().map(|view| view.other.is_modified()).unwrap_or(false);
+			if self.data.is_modified() || diff_modified || self.buffers.iter().any(|buffer| buffer.is_modified())
+			{
+				self.create_yn_req(YNOption::QuitIgnoreModified);
+			}
+			else 
+			{
+			    self.running = false;
+			}
+		}
+		else 
+		{
+		    self.notify(format!("error: unknown command: {}", op));
+		}
+	}
+
+	//replace the current buffer with a file, asks first if there are unsaved changes
+	fn open_file(&mut self, path: String)
+	{
+		if self.data.is_modified()
+		{
+			self.create_yn_req(YNOption::OpenIgnoreModified(path));
+		}
+		else 
+		{
+		    self.states.remember(&self.data, self.line_scroll);
+		    self.close_server_document();
+		    match self.data.open(path)
+		    {
+		    	Ok(_) => {
+		    		self.notify(String::from("opened"));
+		    		self.check_conflicts();
+		    		self.line_scroll = self.states.restore(&mut self.data);
+		    	},
+		    	Err(e) => {self.notify(format!("error: {}", e))}
+		    }
+		}
+	}
+
+	//titles of all buffers, the current one first
+	fn buffer_names(&self) -> Vec<String>
+	{
+		let mut result = vec![self.data.get_title()];
+		for buffer in &self.buffers
+		{
+			result.push(buffer.get_title());
+		}
+		result
+	}
+
+	//make another buffer the current one, the old current one goes to the end of the list
+	fn switch_buffer(&mut self, index: usize)
+	{
+		self.end_diff();
+		self.states.remember(&self.data, self.line_scroll);
+		let buffer = self.buffers.remove(index);
+		let old = mem::replace(&mut self.data, buffer);
+		self.buffers.push(old);
+		self.line_scroll = self.states.scroll(&self.data);
+		self.char_scroll = 0;
+	}
+
+	//switch to a buffer by number (as listed by 'buffer') or name
+	fn select_buffer(&mut self, name: String)
+	{
+		match self.find_buffer(&name)
+		{
+			Some(0) => {},
+			Some(v) => self.switch_buffer(v - 1),
+			None => self.notify(format!("error: no buffer: {}", name))
+		}
+	}
+
+	//the index of a buffer by number or name, as listed by 'buffer', 0 is the current buffer
+	fn find_buffer(&self, name: &str) -> Option<usize>
+	{
+		let names = self.buffer_names();
+		match name.parse::<usize>()
+		{
+			Ok(v) if v >= 1 && v <= names.len() => Some(v - 1),
+			_ => {
+				match names.iter().position(|title| title == name)
+				{
+					Some(v) => Some(v),
+					None => names.iter().position(|title| title.ends_with(name))
+				}
+			}
+		}
+	}
+
+	//open a file in a new buffer, or switch to it if it is open already
+	fn edit_file(&mut self, path: String)
+	{
+		if self.data.get_path() == Some(path.clone())
+		{
+			return;
+		}
+		if let Some(index) = self.buffers.iter().position(|buffer| buffer.get_path() == Some(path.clone()))
+		{
+			self.switch_buffer(index);
+			return;
+		}
+		let mut buffer = if Path::new(&path).exists()
+		{
+			match FileData::from(path)
+			{
+				Ok(v) => v,
+				Err(e) => {
+					self.notify(format!("error: {}", e));
+					return;
+				}
+			}
+		}
+		else 
+		{
+		    FileData::new_with_name(path)
+		};
+		buffer.set_indent(self.config.expand_tab, TAB_SIZE as usize);
+		buffer.set_pairing(self.config.auto_pair, self.config.overtype, self.config.pair_delete);
+		self.buffers.push(buffer);
+		let index = self.buffers.len() - 1;
+		self.switch_buffer(index);
+		self.check_conflicts();
+		self.line_scroll = self.states.restore(&mut self.data);
+	}
+
+	//format and write the current buffer to path, returns true if it was written
+	fn save_data(&mut self, path: String) -> bool
+	{
+		if self.config.format_on_save
+		{
+			match self.format_data(&path)
+			{
+				Ok(Some(warning)) => self.notify(format!("warning: {}", warning)),
+				Ok(None) => {},
+				Err(e) => self.notify(format!("warning: not formatted: {}", e))
+			}
+		}
+		let result = if self.data.get_path() == Some(path.clone())
+		{
+			self.data.save()
+		}
+		else
+		{
+		    self.data.save_to(path.clone())
+		};
+		match result
+		{
+			Ok(_) => {
+				self.update_conflicts();
+				let conflicts = self.conflicts.len();
+				if conflicts > 0
+				{
+					self.notify(format!("warning: saved with {} conflicts left", conflicts));
+				}
+				else
+				{
+				    self.notify(String::from("saved"));
+				}
+				self.git.remove(&path); //the index may have changed since the file was read
+				self.signs_key = None;
+				if self.diff.as_ref().map(|view| view.from_disk).unwrap_or(false)
+				{
+					self.start_diff(None); //compare with the saved file
+				}
+				true
+			},
+			Err(e) => {
+				self.notify(format!("error: {}", e));
+				false
+			}
+		}
+	}
+
+	//run the formatter configured for the extension of path over the current buffer
+	//returns what the formatter wrote to stderr, formatters may warn and still format
+	fn format_data(&mut self, path: &str) -> Result<Option<String>, String>
+	{
+		let command = match self.config.formatter(path)
+		{
+			Some(v) => v,
+			None => {return Ok(None);}
+		};
+		let input = self.data.to_string_copy();
+		let (mut output, warning) = try!(shell::run(&command, Some(input.clone())));
+		if output.trim().is_empty() && input.trim().is_empty() == false
+		{
+			return Err(format!("{} returned nothing", command));
+		}
+		if output == input
+		{
+			return Ok(warning);
+		}
+		//the buffer ends with an implicit newline
+		if output.ends_with('\n')
+		{
+			output.pop();
+		}
+		self.data.replace_all(&output);
+		Ok(warning)
+	}
+
+	//run a shell command, show its output in the shell buffer or insert it at the cursor
+	fn run_shell(&mut self, command: &str, insert: bool)
+	{
+		if command.is_empty()
+		{
+			self.notify(String::from("error: no shell command"));
+			return;
+		}
+		self.git.clear(); //e.g. git add or git checkout
+		self.signs_key = None;
+		match shell::run(command, None)
+		{
+			Ok((output, warning)) => {
+				if let Some(warning) = warning
+				{
+					self.notify(format!("warning: {}", warning));
+				}
+				if insert
+				{
+					self.data.insert_text(&output);
+				}
+				else
+				{
+				    let lines = output.lines().map(|line| line.to_owned()).collect();
+				    self.show_scratch(shell::SHELL_BUFFER, lines);
+				}
+			},
+			Err(e) => self.notify(format!("error: {}", e))
+		}
+	}
+
+	//pipe the selection or the whole buffer through a shell command and replace it with the output
+	fn filter_text(&mut self, command: &str)
+	{
+		if command.is_empty()
+		{
+			self.notify(String::from("error: no shell command"));
+			return;
+		}
+		let whole = self.data.has_mark() == false;
+		let (start, end, input) = match self.data.get_selection()
+		{
+			Some((start, end)) => (start, end, self.data.get_text(start, end)),
+			None => ((0, 0), self.data.get_end(), self.data.to_string_copy())
+		};
+		let mut output = match shell::run(command, Some(input.clone()))
+		{
+			Ok((output, warning)) => {
+				if let Some(warning) = warning
+				{
+					self.notify(format!("warning: {}", warning));
+				}
+				output
+			},
+			Err(e) => {
+				self.notify(format!("error: {}", e));
+				return;
+			}
+		};
+		//the buffer ends with an implicit newline, so only keep the output's last one if the input had one
+		if output.ends_with('\n') && (whole || input.ends_with('\n') == false)
+		{
+			output.pop();
+		}
+		let cursor = (self.data.get_cursor_line(), self.data.get_cursor_char());
+		self.data.replace_text(start, end, &output);
+		if whole
+		{
+			self.data.set_cursor(cursor.0, cursor.1);
+		}
+	}
+
+	//the absolute path of the current file and the command of its language server, if there is one
+	fn current_server(&self) -> Option<(String, String)>
+	{
+		let path = match self.data.get_path()
+		{
+			Some(v) => v,
+			None => {return None;}
+		};
+		let command = match self.config.server(&path)
+		{
+			Some(v) => v,
+			None => {return None;}
+		};
+		match fs::canonicalize(&path) //files that were never saved are not sent
+		{
+			Ok(v) => Some((v.to_string_lossy().into_owned(), command)),
+			Err(_) => None
+		}
+	}
+
+	//start the language server of the current file if needed and send it the current text
+	fn sync_server(&mut self)
+	{
+		let (path, command) = match self.current_server()
+		{
+			Some(v) => v,
+			None => {return;}
+		};
+		if self.servers.contains_key(&command) == false
+		{
+			if self.broken_servers.contains(&command)
+			{
+				return;
+			}
+			let root = env::current_dir().unwrap_or(PathBuf::from("."));
+			match lsp::Client::start(&command, &root)
+			{
+				Ok(client) => {
+					self.servers.insert(command.clone(), client);
+				},
+				Err(e) => {
+					self.broken_servers.insert(command.clone());
+					self.notify(format!("error: {}: {}", command, e));
+					return;
+				}
+			}
+		}
+		let version = self.data.get_version();
+		let synced = self.servers.get(&command).map(|client| client.is_synced(&path, version)).unwrap_or(true);
+		if synced
+		{
+			return;
+		}
+		let extension = Path::new(&path).extension().map(|v| v.to_string_lossy().into_owned()).unwrap_or_default();
+		if let Some(client) = self.servers.get_mut(&command)
+		{
+			client.sync(&path, &lsp::language_id(&extension), &self.data);
+		}
+	}
+
+	//tell the language server of the current buffer that its file is no longer edited there
+	fn close_server_document(&mut self)
+	{
+		let (path, command) = match self.current_server()
+		{
+			Some(v) => v,
+			None => {return;}
+		};
+		if let Some(client) = self.servers.get_mut(&command)
+		{
+			client.close(&path);
+		}
+	}
+
+	//keep the language servers up to date and handle their messages, returns true if something changed
+	fn poll_servers(&mut self) -> bool
+	{
+		self.sync_server();
+		let mut events = Vec::new();
+		for (command, client) in self.servers.iter_mut()
+		{
+			for event in client.poll()
+			{
+				events.push((command.clone(), event));
+			}
+		}
+		let redraw = events.is_empty() == false;
+		for (command, event) in events
+		{
+			match event
+			{
+				lsp::Event::Response(response) => self.server_response(response),
+				lsp::Event::Diagnostics => {},
+				lsp::Event::Message(message) => self.notify(format!("{}: {}", command, message)),
+				lsp::Event::Exited => {
+					self.servers.remove(&command);
+					self.broken_servers.insert(command.clone());
+					self.notify(format!("error: {} exited", command));
+				}
+			}
+		}
+		redraw
+	}
+
+	//ask the language server of the current file about the cursor position
+	fn server_request(&mut self, op: &str)
+	{
+		let (path, command) = match self.current_server()
+		{
+			Some(v) => v,
+			None => {
+				self.notify(String::from("error: no language server for this file (set lsp.<extension>)"));
+				return;
+			}
+		};
+		self.sync_server();
+		let line = self.data.get_cursor_line();
+		let column = lsp::utf16_column(self.data.get_line(line).unwrap(), self.data.get_cursor_char());
+		let client = match self.servers.get_mut(&command)
+		{
+			Some(v) => v,
+			None => {
+				self.notify(format!("error: {} is not running", command));
+				return;
+			}
+		};
+		match op
+		{
+			"hover" => client.hover(&path, line, column),
+			"definition" => client.definition(&path, line, column),
+			"references" => client.references(&path, line, column),
+			_ => client.completion(&path, line, column)
+		}
+	}
+
+	//show the answer of a language server
+	fn server_response(&mut self, response: lsp::Response)
+	{
+		match response
+		{
+			lsp::Response::Hover(lines) => {
+				if lines.iter().all(|line| line.trim().is_empty())
+				{
+					self.notify(String::from("no information"));
+					return;
+				}
+				self.popup = Some(Popup::info(lines));
+			},
+			lsp::Response::Definition(locations) => {
+				match locations.into_iter().next()
+				{
+					Some(location) => self.jump_to_server_location(location),
+					None => self.notify(String::from("no definition found"))
+				}
+			},
+			lsp::Response::References(locations) => {
+				if locations.is_empty()
+				{
+					self.notify(String::from("no references found"));
+					return;
+				}
+				let root = env::current_dir().ok().and_then(|dir| fs::canonicalize(dir).ok());
+				let mut files: HashMap<String, Vec<Vec<char>>> = HashMap::new();
+				let mut lines = Vec::new();
+				for location in locations
+				{
+					let content = files.entry(location.path.clone()).or_insert_with(|| {
+						let text = fs::read(&location.path).map(|bytes| String::from_utf8_lossy(&bytes).into_owned()).unwrap_or(String::new());
+						text.lines().map(|line| line.chars().collect()).collect()
+					});
+					let line_text = content.get(location.line).cloned().unwrap_or(Vec::new());
+					let column = lsp::char_column(&line_text, location.column);
+					let path = match root
+					{
+						Some(ref root) => Path::new(&location.path).strip_prefix(root).map(|rel| rel.to_string_lossy().into_owned()).unwrap_or(location.path.clone()),
+						None => location.path.clone()
+					};
+					let preview: String = line_text.iter().collect();
+					lines.push(format!("{}:{}:{}: {}", path, location.line + 1, column + 1, preview.trim()));
+				}
+				self.show_scratch(lsp::REFERENCES_BUFFER, lines);
+			},
+			lsp::Response::Completion(items) => {
+				let line = self.data.get_cursor_line();
+				let cursor_char = self.data.get_cursor_char();
+				let content = self.data.get_line(line).unwrap().clone();
+				let mut word_start = cursor_char;
+				while word_start > 0 && filedata::is_word_char(content[word_start - 1])
+				{
+					word_start -= 1;
+				}
+				let mut popup = Popup::completion(items, line, word_start);
+				let typed: String = content[word_start..cursor_char].iter().collect();
+				if popup.filter(&typed)
+				{
+					self.popup = Some(popup);
+				}
+				else
+				{
+				    self.notify(String::from("no completions"));
+				}
+			}
+		}
+	}
+
+	//open the file of a location from a language server, its column counts UTF-16 code units
+	fn jump_to_server_location(&mut self, location: lsp::Location)
+	{
+		let root = env::current_dir().ok().and_then(|dir| fs::canonicalize(dir).ok());
+		let path = match root
+		{
+			Some(ref root) => Path::new(&location.path).strip_prefix(root).map(|rel| rel.to_string_lossy().into_owned()).unwrap_or(location.path.clone()),
+			None => location.path.clone()
+		};
+		//the file may be open under another name
+		let same_file = self.data.get_path().and_then(|open| fs::canonicalize(open).ok()) == Some(PathBuf::from(&location.path));
+		if same_file == false
+		{
+			self.edit_file(path.clone());
+			if self.data.get_path() != Some(path)
+			{
+				return;
+			}
+		}
+		let column = match self.data.get_line(location.line)
+		{
+			Some(v) => lsp::char_column(v, location.column),
+			None => 0
+		};
+		self.data.set_cursor(location.line, column);
+	}
+
+	//show lines in a fresh scratch buffer, replacing an older one with the same name
+	fn show_scratch(&mut self, name: &str, lines: Vec<String>)
+	{
+		if let Some(index) = self.buffers.iter().position(|buffer| buffer.is_scratch() && buffer.get_title() == name)
+		{
+			self.buffers.remove(index);
+		}
+		let mut buffer = FileData::new_scratch(name);
+		buffer.append_lines(lines);
+		if self.data.is_scratch() && self.data.get_title() == name
+		{
+			self.data = buffer;
+		}
+		else
+		{
+		    self.buffers.push(buffer);
+		    let index = self.buffers.len() - 1;
+		    self.switch_buffer(index);
+		}
+		self.line_scroll = 0;
+		self.char_scroll = 0;
+	}
+
+	//show the messages of a finished build in the build buffer
+	fn build_finished(&mut self, result: Result<(bool, String), String>)
+	{
+		let (success, output) = match result
+		{
+			Ok(v) => v,
+			Err(e) => {
+				self.notify(format!("error: {}", e));
+				return;
+			}
+		};
+		let (lines, errors) = match self.build
+		{
+			Some(ref build) => {
+				let lines: Vec<String> = build.diagnostics.iter().map(|diagnostic| {
+					format!("{}:{}:{}: {}", diagnostic.path, diagnostic.line + 1, diagnostic.column + 1, diagnostic.message)
+				}).collect();
+				(lines, build.error_count())
+			},
+			None => {return;}
+		};
+		let warnings = lines.len() - errors;
+		if lines.is_empty()
+		{
+			if success == false //no messages to list, show what the command printed
+			{
+				self.show_scratch(build::BUILD_BUFFER, output.lines().map(|line| line.to_owned()).collect());
+				self.notify(String::from("build failed"));
+			}
+			else
+			{
+			    self.notify(String::from("build finished"));
+			}
+			return;
+		}
+		self.show_scratch(build::BUILD_BUFFER, lines);
+		let status = if success { "finished" } else { "failed" };
+		self.notify(format!("build {}: {} errors, {} warnings", status, errors, warnings));
+	}
+
+	//true if the current buffer lists "file:line:column: ..." locations to jump to
+	fn is_list_buffer(&self) -> bool
+	{
+		let title = self.data.get_title();
+		self.data.is_scratch() && (title == grep::GREP_BUFFER || title == build::BUILD_BUFFER || title == lsp::REFERENCES_BUFFER)
+	}
+
+	//the current or another buffer that is not backed by a file
+	fn find_scratch(&mut self, name: &str) -> Option<&mut FileData>
+	{
+		if self.data.is_scratch() && self.data.get_title() == name
+		{
+			return Some(&mut self.data);
+		}
+		self.buffers.iter_mut().find(|buffer| buffer.is_scratch() && buffer.get_title() == name)
+	}
+
+	//open the file named by a "file:line:column: ..." line at the cursor and go to that position
+	fn jump_to_location(&mut self)
+	{
+		let line: String = match self.data.get_line(self.data.get_cursor_line())
+		{
+			Some(v) => v.iter().collect(),
+			None => {return;}
+		};
+		let (path, line, column) = match grep::parse_location(&line)
+		{
+			Some(v) => v,
+			None => {
+				self.notify(String::from("error: no location in this line"));
+				return;
+			}
+		};
+		if Path::new(&path).exists() == false
+		{
+			self.notify(format!("error: {} does not exist", path));
+			return;
+		}
+		self.edit_file(path.clone());
+		if self.data.get_path() == Some(path)
+		{
+			self.data.set_cursor(line, column);
+		}
+	}
+
+	//keep the state of all buffers and the files of the session for the next start
+	fn remember_states(&mut self)
+	{
+		self.states.remember(&self.data, self.line_scroll);
+		for buffer in &self.buffers
+		{
+			let scroll = self.states.scroll(buffer);
+			self.states.remember(buffer, scroll);
+		}
+		if let Some(ref key) = self.session
+		{
+			let files = Some(&self.data).into_iter().chain(self.buffers.iter()).filter_map(|buffer| buffer.get_path()).filter(|path| Path::new(path).exists()).collect();
+			state::save_session(key, files);
+		}
+	}
+
+	//open the files of the session, the file given on the commandline or else the first file of the session is shown
+	fn restore_session(&mut self)
+	{
+		let files = match self.session
+		{
+			Some(ref key) => state::load_session(key),
+			None => {return;}
+		};
+		let shown = self.data.get_path().or(files.first().cloned());
+		let given = self.data.get_path().map(|path| state::absolute(&path));
+		for file in files.into_iter().filter(|file| Path::new(file).exists() && Some(file) != given.as_ref())
+		{
+			//the empty buffer mice starts with is replaced by the first file
+			if self.data.get_path().is_none() && self.data.is_scratch() == false && self.data.is_modified() == false
+			{
+				match self.data.open(file)
+				{
+					Ok(_) => {
+						self.check_conflicts();
+						self.line_scroll = self.states.restore(&mut self.data);
+					},
+					Err(e) => self.notify(format!("error: {}", e))
+				}
+			}
+			else
+			{
+			    self.edit_file(file);
+			}
+		}
+		if let Some(file) = shown
+		{
+			self.edit_file(file);
+		}
+	}
+
+	//drop the current buffer and show the most recently used other one
+	fn close_buffer(&mut self)
+	{
+		self.end_diff();
+		self.states.remember(&self.data, self.line_scroll);
+		self.close_server_document();
+		match self.buffers.pop()
+		{
+			Some(v) => {
+				self.data = v;
+			},
+			None => {
+				self.data.clear();
+			}
 		}
+		self.line_scroll = self.states.scroll(&self.data);
+		self.char_scroll = 0;
 	}
 
 	//setup a question
@@ -698,7 +3738,11 @@ impl Display
 			YNOption::NewIgnoreModified => format!("unsaved changes! continue?"),
 			YNOption::OpenIgnoreModified(_) => format!("unsaved changes! continue?"),
 			YNOption::SaveIgnoreExisting(_) => format!("file already exists! continue?"),
-			YNOption::QuitIgnoreModified => format!("unsaved changes! continue?")
+			YNOption::QuitIgnoreModified => String::from("unsaved changes! continue?"),
+			YNOption::CloseIgnoreModified => String::from("unsaved changes! continue?"),
+			YNOption::CreateFile(ref path) => format!("create {}?", path),
+			YNOption::RenameFile(ref from, ref to) => format!("rename {} to {}?", from, to),
+			YNOption::DeleteFile(ref path) => format!("delete {}?", path)
 		};
 		self.yn_question = Some(YNQuestion::new(message, option));
 		self.yn_question_state = false;
@@ -714,9 +3758,10 @@ impl Display
 //widen the string with spaces
 fn pad_to(mut string: String, width: usize) -> String
 {
-	if string.len() < width
+	let string_width = text::str_width(&string);
+	if string_width < width
 	{
-		for _ in 0..width-string.len()
+		for _ in 0..width-string_width
 		{
 			string.push(' ');
 		}
@@ -729,7 +3774,11 @@ enum YNOption
 	NewIgnoreModified, //when the user wants to create a new file, but the current one is unsaved
 	OpenIgnoreModified(String),//String -> path, like NewIgnoreModified, but after opening a file
 	SaveIgnoreExisting(String),//String -> path, when the user wants to write to an existing file that is NOT the original file
-	QuitIgnoreModified //when the user wants to exit, but the current file is unsaved
+	QuitIgnoreModified, //when the user wants to exit, but the current file is unsaved
+	CloseIgnoreModified, //when the user wants to close the current buffer, but it is unsaved
+	CreateFile(String), //String -> path, create an empty file
+	RenameFile(String, String), //(old path, new path), rename a file
+	DeleteFile(String) //String -> path, delete a file or an empty directory
 }
 
 struct YNQuestion
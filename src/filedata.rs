@@ -2,14 +2,79 @@ use std::io::Read;
 use std::io::Write;
 use std::io::Result;
 use std::fs::File;
+use text;
+use fold;
+use words::WordIndex;
+use std::collections::HashMap;
+use std::mem;
+
+pub const UNDO_LIMIT: usize = 100; //number of edits that can be undone
+pub const CHANGE_LIMIT: usize = 1000; //number of changes kept for language servers
+pub const BRACKET_LINES: usize = 2000; //how far to look for a matching bracket
+pub const PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')]; //the first three are brackets
+
+//the kind of an edit, consecutive edits of the same kind are undone together
+#[derive(Clone, Copy, PartialEq)]
+pub enum EditKind
+{
+	Insert, //typing
+	Delete, //backspace and remove
+	Other //everything else, never merged
+}
+
+//one change of the content: the lines of text removed at start were replaced with the inserted ones
+//an edit inside a line has one line on both sides, a line break is an empty line on each side of it
+struct Edit
+{
+	start: (usize, usize),
+	removed: Vec<Vec<char>>,
+	inserted: Vec<Vec<char>>
+}
+
+//the changes of an edit, undone together, and the cursor and folds from before them (after them on the redo stack)
+struct Step
+{
+	edits: Vec<Edit>,
+	cursor_line: usize,
+	cursor_char: usize,
+	folds: Vec<(usize, usize)>
+}
+
+//a change of the text the way language servers get it, columns are in UTF-16 code units
+pub struct Change
+{
+	pub version: usize, //the version of the text after the change
+	pub start: (usize, usize), //(line, column)
+	pub end: (usize, usize), //end of the replaced text before the change
+	pub text: String
+}
 
 pub struct FileData
 {
 	path: Option<String>, //the original path of the file, if provided
+	scratch: Option<String>, //name of a buffer that is not backed by a file, e.g. "[grep]"
 	content: Vec<Vec<char>>, //the content as a semi 2D-array of chars
 	cursor_line: usize, //the line the cursor is in
 	cursor_char: usize, //the character the cursor is in the current line
-	modified: bool //ind. wether data has been changed since last save
+	cursors: Vec<(usize, usize)>, //more cursors as (line, char), edits are applied at all of them
+	modified: bool, //ind. wether data has been changed since last save
+	mark: Option<(usize, usize)>, //(line, char) where the selection starts, the cursor is the other end
+	block: bool, //the selection is the rectangle of screen columns between the mark and the cursor
+	folds: Vec<(usize, usize)>, //(first, last) line of folded regions, the first line stays visible
+	fold_edit: Option<(usize, usize, usize)>, //first and last line changed by the last edit and the number of lines before it
+	expand_tab: bool, //indent with spaces instead of '\t'
+	tab_size: usize, //width of one indentation level in spaces
+	auto_pair: bool, //typing an opening bracket or quote inserts the closing one too
+	overtype: bool, //typing a closing bracket or quote in front of the same char moves over it
+	pair_delete: bool, //backspace between an empty pair removes both chars
+	undo_stack: Vec<Step>, //the last edits, newest last
+	redo_stack: Vec<Step>, //edits that were undone, newest last
+	last_edit: Option<EditKind>, //kind of the running edit, None after cursor movement
+	group: Option<bool>, //Some while an edit runs at several cursors, true once its undo step is started
+	version: usize, //counts changes of the content, to notice them from outside
+	changes: Vec<Change>, //the last changes of the content, oldest first
+	changes_from: usize, //changes are complete for anyone who knows this version or a later one
+	words: WordIndex //the words in the content, for completion
 }
 
 impl FileData
@@ -22,10 +87,29 @@ impl FileData
 		FileData
 		{
 			path: None,
+			scratch: None,
 			content: linevec,
 			cursor_line: 0,
 			cursor_char: 0,
-			modified: false
+			cursors: Vec::new(),
+			modified: false,
+			mark: None,
+			block: false,
+			folds: Vec::new(),
+			fold_edit: None,
+			expand_tab: false,
+			tab_size: 4,
+			auto_pair: false,
+			overtype: false,
+			pair_delete: false,
+			undo_stack: Vec::new(),
+			redo_stack: Vec::new(),
+			last_edit: None,
+			group: None,
+			version: 0,
+			changes: Vec::new(),
+			changes_from: 0,
+			words: WordIndex::new()
 		}
 	}
 
@@ -36,6 +120,14 @@ impl FileData
 		result
 	}
 
+	//a buffer for output like search results, it is never saved and never counts as modified
+	pub fn new_scratch(name: &str) -> FileData
+	{
+		let mut result = FileData::new();
+		result.scratch = Some(name.to_owned());
+		result
+	}
+
 	//load data from path
 	pub fn from(filepath: String) -> Result<FileData>
 	{
@@ -56,29 +148,58 @@ impl FileData
 			    charvec.push(ch);
 			}
 		}
-		if charvec.len() != 0
+		if charvec.is_empty() == false || linevec.is_empty()
 		{
 			linevec.push(charvec);
 		}
-		Ok(FileData
-		{
-			path: Some(filepath),
-			content: linevec,
-			cursor_line: 0,
-			cursor_char: 0,
-			modified: false
-		})
+		let mut result = FileData::new();
+		result.path = Some(filepath);
+		result.content = linevec;
+		Ok(result)
 	}
 
-	//reset to untitled document
-	pub fn clear(&mut self)
+	//replace the document with another one, but keep the settings
+	fn take_document(&mut self, dummy: FileData)
 	{
-		let dummy = FileData::new();
 		self.path = dummy.path;
+		self.scratch = dummy.scratch;
 		self.content = dummy.content;
 		self.cursor_line = dummy.cursor_line;
 		self.cursor_char = dummy.cursor_char;
+		self.cursors = dummy.cursors;
 		self.modified = dummy.modified;
+		self.mark = dummy.mark;
+		self.block = dummy.block;
+		self.folds = dummy.folds;
+		self.fold_edit = dummy.fold_edit;
+		self.undo_stack = dummy.undo_stack;
+		self.redo_stack = dummy.redo_stack;
+		self.last_edit = dummy.last_edit;
+		self.version += 1;
+		self.changes.clear();
+		self.changes_from = self.version;
+		self.words = WordIndex::new();
+	}
+
+	//reset to untitled document
+	pub fn clear(&mut self)
+	{
+		self.take_document(FileData::new());
+	}
+
+	//set how tab, enter and backspace treat indentation
+	pub fn set_indent(&mut self, expand_tab: bool, tab_size: usize)
+	{
+		self.expand_tab = expand_tab;
+		self.tab_size = tab_size;
+	}
+
+	//set how typing and backspace treat brackets and quotes
+	pub fn set_pairing(&mut self, auto_pair: bool, overtype: bool, pair_delete: bool)
+	{
+		self.auto_pair = auto_pair;
+		self.overtype = overtype;
+		self.pair_delete = pair_delete;
 	}
 
 	//return line at "line"
@@ -94,6 +215,11 @@ impl FileData
 		}
 	}
 
+	pub fn get_content(&self) -> &Vec<Vec<char>>
+	{
+		&self.content
+	}
+
 	pub fn get_cursor_line(&self) -> usize
 	{
 		self.cursor_line
@@ -116,10 +242,11 @@ impl FileData
 
 	pub fn get_title(&self) -> String
 	{
-		match self.path
+		match (&self.path, &self.scratch)
 		{
-			None => String::from("<Untitled>"),
-			Some(ref v) => v.clone()
+			(Some(v), _) => v.clone(),
+			(None, Some(v)) => v.clone(),
+			(None, None) => String::from("<Untitled>")
 		}
 	}
 
@@ -135,33 +262,96 @@ impl FileData
 
 	pub fn is_modified(&self) -> bool
 	{
-		self.modified
+		self.modified && self.scratch.is_none()
+	}
+
+	pub fn get_version(&self) -> usize
+	{
+		self.version
+	}
+
+	//the changes that lead from a version to the current one, None if they are not known anymore
+	pub fn changes_since(&self, version: usize) -> Option<&[Change]>
+	{
+		if version < self.changes_from
+		{
+			return None;
+		}
+		let first = self.changes.iter().position(|change| change.version > version).unwrap_or(self.changes.len());
+		Some(&self.changes[first..])
+	}
+
+	//the words in the buffer and how often they occur, the index is built on the first call
+	pub fn get_words(&mut self) -> &HashMap<String, usize>
+	{
+		if self.words.is_built() == false
+		{
+			self.words.build(&self.content);
+		}
+		self.words.get_counts()
+	}
+
+	pub fn is_scratch(&self) -> bool
+	{
+		self.scratch.is_some()
 	}
 
 	//CURSOR FUNCTIONS
 
+	//move the cursor to a position, positions outside of the text are clamped
+	pub fn set_cursor(&mut self, line: usize, ch: usize)
+	{
+		self.cursors.clear();
+		self.cursor_line = line;
+		if self.cursor_line >= self.content.len()
+		{
+			self.cursor_line = self.content.len() - 1;
+		}
+		self.cursor_char = ch;
+		self.clamp_cursor_char();
+		self.last_edit = None;
+		self.open_folds(self.cursor_line);
+	}
+
 	pub fn move_cursor_up(&mut self)
 	{
-		if self.cursor_line != 0
+		if self.cursors.is_empty() == false
 		{
-			self.cursor_line -= 1;
-			let line_len = self.content.get(self.cursor_line).unwrap().len();
-			if self.cursor_char > line_len
-			{
-				self.cursor_char = line_len;
-			}
+			return self.at_cursors(false, |data| data.move_cursor_up());
+		}
+		self.last_edit = None;
+		if let Some(line) = self.visible_line(self.cursor_line, false)
+		{
+			self.cursor_line = line;
+			self.clamp_cursor_char();
 		}
 	}
 
+	//keep the cursor inside the line and at the start of a grapheme cluster
+	fn clamp_cursor_char(&mut self)
+	{
+		let line = self.content.get(self.cursor_line).unwrap();
+		if self.cursor_char > line.len()
+		{
+			self.cursor_char = line.len();
+		}
+		self.cursor_char = text::snap_boundary(line, self.cursor_char);
+	}
+
 	pub fn move_cursor_left(&mut self)
 	{
+		if self.cursors.is_empty() == false
+		{
+			return self.at_cursors(false, |data| data.move_cursor_left());
+		}
+		self.last_edit = None;
 		if self.cursor_char != 0
 		{
-			self.cursor_char -= 1;
+			self.cursor_char = text::prev_boundary(self.content.get(self.cursor_line).unwrap(), self.cursor_char);
 		}
 		else 
 		{
-			if self.cursor_line != 0
+			if self.visible_line(self.cursor_line, false).is_some()
 			{
 				self.move_cursor_up();
 		    	self.cursor_char = self.get_line(self.get_cursor_line()).unwrap().len();
@@ -171,27 +361,33 @@ impl FileData
 
 	pub fn move_cursor_down(&mut self)
 	{
-		if self.cursor_line < self.get_lines() - 1
+		if self.cursors.is_empty() == false
 		{
-			self.cursor_line += 1;
-			let line_len = self.content.get(self.cursor_line).unwrap().len();
-			if self.cursor_char > line_len
-			{
-				self.cursor_char = line_len;
-			}
+			return self.at_cursors(false, |data| data.move_cursor_down());
+		}
+		self.last_edit = None;
+		if let Some(line) = self.visible_line(self.cursor_line, true)
+		{
+			self.cursor_line = line;
+			self.clamp_cursor_char();
 		}
 	}
 
 	pub fn move_cursor_right(&mut self)
 	{
+		if self.cursors.is_empty() == false
+		{
+			return self.at_cursors(false, |data| data.move_cursor_right());
+		}
+		self.last_edit = None;
 		let cur_line_len = self.get_line(self.get_cursor_line()).unwrap().len();
 		if self.cursor_char < cur_line_len
 		{
-			self.cursor_char += 1;
+			self.cursor_char = text::next_boundary(self.content.get(self.cursor_line).unwrap(), self.cursor_char);
 		}
 		else 
 		{
-		    if self.cursor_line < self.get_lines() - 1 
+		    if self.visible_line(self.cursor_line, true).is_some()
 		    {
 		    	self.move_cursor_down();
 		    	self.cursor_char = 0;
@@ -199,114 +395,1757 @@ impl FileData
 		}
 	}
 
-	//EDITING FUNCTIONS
+	pub fn move_cursor_home(&mut self)
+	{
+		if self.cursors.is_empty() == false
+		{
+			return self.at_cursors(false, |data| data.move_cursor_home());
+		}
+		self.last_edit = None;
+		self.cursor_char = 0;
+	}
 
-	pub fn write_char(&mut self, ch: char)
+	pub fn move_cursor_end(&mut self)
 	{
-		let cline = self.get_cursor_line();
-		let cchar = self.get_cursor_char();
-		self.content.get_mut(cline).unwrap().insert(cchar, ch);
-		self.move_cursor_right();
-		self.modified = true;
+		if self.cursors.is_empty() == false
+		{
+			return self.at_cursors(false, |data| data.move_cursor_end());
+		}
+		self.last_edit = None;
+		self.cursor_char = self.content.get(self.cursor_line).unwrap().len();
 	}
 
-	pub fn backspace(&mut self)
+	//move to the start of the previous word
+	pub fn move_word_left(&mut self)
 	{
-		let cline = self.get_cursor_line();
-		let cchar = self.get_cursor_char();
-		if cchar != 0
+		if self.cursors.is_empty() == false
 		{
-			self.content.get_mut(cline).unwrap().remove(cchar-1);
-			self.cursor_char -= 1;
+			return self.at_cursors(false, |data| data.move_word_left());
 		}
-		else if cline != 0
+		if self.cursor_char == 0
 		{
-		    let mut cur_line = self.content.get(cline).unwrap().clone();
-		    let new_char = self.content.get(cline-1).unwrap().len();
-		    self.content.get_mut(cline-1).unwrap().append(&mut cur_line);
-		    self.content.remove(cline);
-		    self.cursor_char = new_char;
-		    self.cursor_line -= 1;
+			self.move_cursor_left();
+			return;
 		}
-		self.modified = true;
+		self.last_edit = None;
+		let line = self.content.get(self.cursor_line).unwrap();
+		let mut pos = self.cursor_char;
+		while pos > 0 && is_word_char(line[pos - 1]) == false
+		{
+			pos -= 1;
+		}
+		while pos > 0 && is_word_char(line[pos - 1])
+		{
+			pos -= 1;
+		}
+		self.cursor_char = pos;
 	}
 
-	pub fn remove(&mut self)
+	//move behind the end of the next word
+	pub fn move_word_right(&mut self)
 	{
-		let cline = self.get_cursor_line();
-		let cchar = self.get_cursor_char();
-		let line_len = self.content.get(cline).unwrap().len();
-		if cchar != line_len
+		if self.cursors.is_empty() == false
 		{
-			self.content.get_mut(cline).unwrap().remove(cchar);
+			return self.at_cursors(false, |data| data.move_word_right());
 		}
-		else if cline != self.content.len()-1
+		let len = self.content.get(self.cursor_line).unwrap().len();
+		if self.cursor_char == len
 		{
-		    let mut next_line = self.content.get(cline+1).unwrap().clone();
-		    self.content.get_mut(cline).unwrap().append(&mut next_line);
-		    self.content.remove(cline + 1);
+			self.move_cursor_right();
+			return;
 		}
-		self.modified = true;
+		self.last_edit = None;
+		let line = self.content.get(self.cursor_line).unwrap();
+		let mut pos = self.cursor_char;
+		while pos < len && is_word_char(line[pos]) == false
+		{
+			pos += 1;
+		}
+		while pos < len && is_word_char(line[pos])
+		{
+			pos += 1;
+		}
+		self.cursor_char = text::snap_boundary(line, pos);
 	}
 
-	pub fn enter(&mut self)
+	//MULTIPLE CURSOR FUNCTIONS
+
+	//the cursors besides the main one
+	pub fn get_cursors(&self) -> &Vec<(usize, usize)>
 	{
-		let cline = self.get_cursor_line();
-		let cchar = self.get_cursor_char();
-		let clip = self.content.get_mut(cline).unwrap().split_off(cchar);
-		self.content.insert(cline+1, clip);
-		self.cursor_char = 0;
-		self.cursor_line += 1;
-		self.modified = true;
+		&self.cursors
 	}
 
-	//copy-move to string
-	pub fn to_string_copy(&self) -> String
+	pub fn clear_cursors(&mut self)
 	{
-		let mut result = String::new();
-		for line in &self.content
+		self.cursors.clear();
+	}
+
+	//run a movement or edit at every cursor, the last one first so the positions before it stay valid
+	//edit -> the cursors after the edited one are moved by the number of inserted or removed chars
+	fn at_cursors<F: Fn(&mut FileData)>(&mut self, edit: bool, action: F)
+	{
+		let main = (self.cursor_line, self.cursor_char);
+		let mut all = mem::take(&mut self.cursors);
+		all.push(main);
+		all.sort();
+		all.dedup();
+		if edit
 		{
-			for ch in line
+			self.group = Some(false); //all cursors change the text together, undo reverts them at once
+		}
+		let mut done: Vec<(usize, bool)> = Vec::new(); //offsets of the moved cursors, true for the main one
+		for &(line, ch) in all.iter().rev()
+		{
+			//positions can be stale after edits that don't move the cursors, keep them inside the text
+			self.cursor_line = if line < self.content.len() { line } else { self.content.len() - 1 };
+			self.cursor_char = ch;
+			self.clamp_cursor_char();
+			let len_before = self.text_len() as isize;
+			action(self);
+			let here = self.offset_of(self.cursor_line, self.cursor_char);
+			if edit
 			{
-				result.push(ch.clone());
+				let delta = self.text_len() as isize - len_before;
+				for entry in done.iter_mut()
+				{
+					let moved = entry.0 as isize + delta;
+					entry.0 = if moved > here as isize { moved as usize } else { here };
+				}
+			}
+			done.push((here, (line, ch) == main));
+		}
+		self.group = None;
+		let mut cursors = Vec::new();
+		for (offset, is_main) in done
+		{
+			let position = self.position_of(offset);
+			if is_main
+			{
+				self.cursor_line = position.0;
+				self.cursor_char = position.1;
+			}
+			else
+			{
+			    cursors.push(position);
+			}
+		}
+		let main = (self.cursor_line, self.cursor_char);
+		cursors.sort();
+		cursors.dedup();
+		cursors.retain(|cursor| *cursor != main); //cursors that ran into each other become one
+		self.cursors = cursors;
+	}
+
+	//number of chars in the text, counting line ends
+	fn text_len(&self) -> usize
+	{
+		self.content.iter().map(|line| line.len() + 1).sum()
+	}
+
+	fn offset_of(&self, line: usize, ch: usize) -> usize
+	{
+		self.content[..line].iter().map(|line| line.len() + 1).sum::<usize>() + ch
+	}
+
+	fn position_of(&self, mut offset: usize) -> (usize, usize)
+	{
+		for (index, line) in self.content.iter().enumerate()
+		{
+			if offset <= line.len()
+			{
+				return (index, offset);
+			}
+			offset -= line.len() + 1;
+		}
+		let last = self.content.len() - 1;
+		(last, self.content[last].len())
+	}
+
+	//add a cursor, returns false if there is one at the position already
+	fn add_cursor(&mut self, line: usize, ch: usize) -> bool
+	{
+		let content = self.content.get(line).unwrap();
+		let ch = text::snap_boundary(content, if ch > content.len() { content.len() } else { ch });
+		if (line, ch) == (self.cursor_line, self.cursor_char) || self.cursors.contains(&(line, ch))
+		{
+			return false;
+		}
+		self.cursors.push((line, ch));
+		self.cursors.sort();
+		true
+	}
+
+	//add a cursor on the line above the topmost or below the bottommost cursor
+	pub fn add_cursor_vertical(&mut self, up: bool) -> bool
+	{
+		let main = (self.cursor_line, self.cursor_char);
+		let edge = if up
+		{
+			self.cursors.iter().cloned().chain(Some(main)).min().unwrap()
+		}
+		else
+		{
+		    self.cursors.iter().cloned().chain(Some(main)).max().unwrap()
+		};
+		self.mark = None;
+		if up && edge.0 > 0
+		{
+			self.add_cursor(edge.0 - 1, self.cursor_char)
+		}
+		else if up == false && edge.0 + 1 < self.content.len()
+		{
+			self.add_cursor(edge.0 + 1, self.cursor_char)
+		}
+		else
+		{
+		    false
+		}
+	}
+
+	//put a cursor on every selected line, at the char of the main cursor
+	pub fn add_cursors_on_selection(&mut self) -> bool
+	{
+		if self.mark.is_none()
+		{
+			return false;
+		}
+		let (first, last) = self.selected_lines();
+		self.mark = None;
+		let ch = self.cursor_char;
+		for line in first .. last+1
+		{
+			self.add_cursor(line, ch);
+		}
+		true
+	}
+
+	//add a cursor at the next occurrence of the selected text or of the word at the cursor,
+	//in the same place inside the occurrence as the main cursor
+	pub fn add_next_occurrence(&mut self) -> bool
+	{
+		let line = self.content.get(self.cursor_line).unwrap().clone();
+		let (needle, inside, whole_word) = match self.get_selection()
+		{
+			Some((start, end)) if start.0 == end.0 && start.1 < end.1 => {
+				(line[start.1..end.1].to_vec(), self.cursor_char - start.1, false)
+			},
+			Some(_) => {return false;},
+			None => {
+				let mut start = self.cursor_char;
+				while start > 0 && is_word_char(line[start - 1])
+				{
+					start -= 1;
+				}
+				let mut end = self.cursor_char;
+				while end < line.len() && is_word_char(line[end])
+				{
+					end += 1;
+				}
+				if start == end
+				{
+					return false;
+				}
+				(line[start..end].to_vec(), self.cursor_char - start, true)
+			}
+		};
+		//search after the occurrence of the last cursor, wrapping around at the end
+		let last = self.cursors.iter().cloned().chain(Some((self.cursor_line, self.cursor_char))).max().unwrap();
+		let lines = self.content.len();
+		let mut from = last.1.saturating_sub(inside) + 1;
+		for step in 0 .. lines+1
+		{
+			let index = (last.0 + step) % lines;
+			let content = self.content.get(index).unwrap().clone();
+			let mut pos = from;
+			from = 0;
+			while pos + needle.len() <= content.len()
+			{
+				let word_bounds = whole_word == false || ((pos == 0 || is_word_char(content[pos - 1]) == false) &&
+					(pos + needle.len() == content.len() || is_word_char(content[pos + needle.len()]) == false));
+				if content[pos .. pos+needle.len()] == needle[..] && word_bounds && self.add_cursor(index, pos + inside)
+				{
+					self.mark = None;
+					return true;
+				}
+				pos += 1;
+			}
+		}
+		false
+	}
+
+	//FOLD FUNCTIONS
+
+	pub fn get_folds(&self) -> &Vec<(usize, usize)>
+	{
+		&self.folds
+	}
+
+	//replace the folds, e.g. with the ones remembered for the file, folds that don't fit the text are left out
+	pub fn set_folds(&mut self, folds: Vec<(usize, usize)>)
+	{
+		self.fold_edit = None;
+		self.folds = folds.into_iter().filter(|&(first, last)| first < last && last < self.content.len()).collect();
+		self.folds.sort();
+		self.folds.dedup_by_key(|fold| fold.0);
+		let line = self.cursor_line;
+		self.open_folds(line);
+	}
+
+	//move the folds along with lines added or removed by the last edits, folds that were edited are opened
+	pub fn update_folds(&mut self)
+	{
+		let (first, last, old_len) = match self.fold_edit.take()
+		{
+			Some(v) => v,
+			None => {return;}
+		};
+		let len = self.content.len();
+		if len != old_len
+		{
+			self.folds = self.folds.iter().filter_map(|&(start, end)| {
+				if start > last
+				{
+					Some(((start + len).saturating_sub(old_len), (end + len).saturating_sub(old_len)))
+				}
+				else if end + 1 < first
+				{
+					Some((start, end))
+				}
+				else
+				{
+				    None
+				}
+			}).collect();
+		}
+		self.folds.retain(|&(start, end)| start < end && end < len);
+	}
+
+	//the line shown for a line, the first line of the outermost fold hiding it or the line itself
+	pub fn shown_line(&self, line: usize) -> usize
+	{
+		let mut result = line;
+		for &(first, last) in &self.folds
+		{
+			if first < result && last >= result
+			{
+				result = first;
 			}
-			result.push('\n');
 		}
 		result
 	}
 
-	//load from file
-	pub fn open(&mut self, path: String) -> Result<()>
+	pub fn is_hidden(&self, line: usize) -> bool
 	{
-		let dummy = try!(FileData::from(path));
-		self.path = dummy.path;
-		self.content = dummy.content;
-		self.cursor_line = dummy.cursor_line;
-		self.cursor_char = dummy.cursor_char;
-		self.modified = dummy.modified;
-		Ok(())
+		self.folds.iter().any(|&(first, last)| first < line && last >= line)
 	}
 
-	//save to original file at self.path
-	pub fn save(&mut self) -> Result<()>
+	//the last line of the fold starting at a line
+	pub fn fold_end(&self, line: usize) -> Option<usize>
 	{
-		let dummy = String::new();
-		let path = match self.path
+		self.folds.iter().find(|&&(first, _)| first == line).map(|&(_, last)| last)
+	}
+
+	//the next visible line below or above a line, skipping folded lines
+	pub fn visible_line(&self, line: usize, down: bool) -> Option<usize>
+	{
+		if down
 		{
-			Some(ref v) => v.clone(),
-			None => dummy //should not happen! check this!
+			let mut next = line + 1;
+			while next < self.content.len() && self.is_hidden(next)
+			{
+				next = self.folds.iter().filter(|&&(first, last)| first < next && last >= next).map(|&(_, last)| last + 1).max().unwrap_or(next + 1);
+			}
+			if next < self.content.len() { Some(next) } else { None }
+		}
+		else
+		{
+		    if line == 0 { None } else { Some(self.shown_line(line - 1)) }
+		}
+	}
+
+	//fold the region at the cursor line or the one around it, or open the fold at the cursor line
+	//returns false if there is nothing to fold
+	pub fn toggle_fold(&mut self, by_brackets: bool) -> bool
+	{
+		self.update_folds();
+		let line = self.cursor_line;
+		if self.fold_end(line).is_some()
+		{
+			self.folds.retain(|&(first, _)| first != line);
+			return true;
+		}
+		let (first, last) = match fold::region(&self.content, line, by_brackets, self.tab_size)
+		{
+			Some(last) => (line, last),
+			None => match fold::enclosing(&self.content, line, by_brackets, self.tab_size)
+			{
+				Some(v) => v,
+				None => {return false;}
+			}
 		};
-		self.save_to(path)
+		self.folds.retain(|&(start, _)| start != first);
+		self.folds.push((first, last));
+		self.folds.sort();
+		self.cursors.clear();
+		self.cursor_line = first;
+		self.clamp_cursor_char();
+		true
 	}
 
-	//save to file other than original file at self.path
-	pub fn save_to(&mut self, path: String) -> Result<()>
+	//fold every region of the text, returns the number of folds
+	pub fn fold_all(&mut self, by_brackets: bool) -> usize
 	{
-		let content = self.to_string_copy();
-		let bytes = content.into_bytes();
-		let mut file = try!(File::create(path));
-		try!(file.write_all(&bytes[..]));
-		self.modified = false;
-		Ok(())
+		self.fold_edit = None;
+		self.folds = (0 .. self.content.len()).filter_map(|line| fold::region(&self.content, line, by_brackets, self.tab_size).map(|last| (line, last))).collect();
+		self.cursors.clear();
+		self.cursor_line = self.shown_line(self.cursor_line);
+		self.clamp_cursor_char();
+		self.folds.len()
+	}
+
+	pub fn unfold_all(&mut self)
+	{
+		self.fold_edit = None;
+		self.folds.clear();
 	}
-}
\ No newline at end of file
+
+	//open the folds that hide a line
+	fn open_folds(&mut self, line: usize)
+	{
+		self.update_folds();
+		self.folds.retain(|&(first, last)| first >= line || last < line);
+	}
+
+	//BRACKET FUNCTIONS
+
+	//the bracket at the cursor (or else the one before it) and the bracket matching it
+	//there is no syntax information, so brackets in strings and comments are counted too
+	pub fn matching_bracket(&self) -> Option<((usize, usize), (usize, usize))>
+	{
+		let line = self.content.get(self.cursor_line).unwrap();
+		let mut candidates = vec![self.cursor_char];
+		if self.cursor_char > 0
+		{
+			candidates.push(self.cursor_char - 1);
+		}
+		for ch in candidates
+		{
+			if ch >= line.len()
+			{
+				continue;
+			}
+			if let Some(other) = self.find_match(self.cursor_line, ch)
+			{
+				return Some(((self.cursor_line, ch), other));
+			}
+		}
+		None
+	}
+
+	//the bracket that closes or opens the one at a position, counting nested pairs
+	fn find_match(&self, line: usize, ch: usize) -> Option<(usize, usize)>
+	{
+		let bracket = self.content.get(line).unwrap()[ch];
+		let (open, close) = match PAIRS.iter().take(3).find(|&&(open, close)| open == bracket || close == bracket)
+		{
+			Some(&v) => v,
+			None => {return None;}
+		};
+		let mut depth = 0;
+		if bracket == open
+		{
+			let last = if line + BRACKET_LINES < self.content.len() { line + BRACKET_LINES } else { self.content.len() };
+			for index in line .. last
+			{
+				let content = self.content.get(index).unwrap();
+				let start = if index == line { ch } else { 0 };
+				for (position, current) in content.iter().enumerate().skip(start)
+				{
+					if *current == open
+					{
+						depth += 1;
+					}
+					else if *current == close
+					{
+						depth -= 1;
+						if depth == 0
+						{
+							return Some((index, position));
+						}
+					}
+				}
+			}
+		}
+		else
+		{
+		    for index in (line.saturating_sub(BRACKET_LINES) .. line+1).rev()
+		    {
+		    	let content = self.content.get(index).unwrap();
+		    	let end = if index == line { ch + 1 } else { content.len() };
+		    	for position in (0 .. end).rev()
+		    	{
+		    		if content[position] == close
+		    		{
+		    			depth += 1;
+		    		}
+		    		else if content[position] == open
+		    		{
+		    			depth -= 1;
+		    			if depth == 0
+		    			{
+		    				return Some((index, position));
+		    			}
+		    		}
+		    	}
+		    }
+		}
+		None
+	}
+
+	//BLOCK FUNCTIONS
+
+	//start a block selection at the cursor, turn a selection into a block or drop the block
+	pub fn toggle_block(&mut self)
+	{
+		if self.is_block()
+		{
+			self.mark = None;
+			self.block = false;
+			return;
+		}
+		if self.mark.is_none()
+		{
+			self.mark = Some((self.cursor_line, self.cursor_char));
+		}
+		self.block = true;
+	}
+
+	pub fn is_block(&self) -> bool
+	{
+		self.block && self.mark.is_some()
+	}
+
+	//(first line, last line, left column, right column) of the block, the right column is not part of it
+	pub fn get_block(&self) -> Option<(usize, usize, usize, usize)>
+	{
+		let mark = match self.mark
+		{
+			Some(v) if self.block => v,
+			_ => {return None;}
+		};
+		let mark_column = self.column_at(mark.0, mark.1);
+		let cursor_column = self.column_at(self.cursor_line, self.cursor_char);
+		let (first, last) = if mark.0 < self.cursor_line { (mark.0, self.cursor_line) } else { (self.cursor_line, mark.0) };
+		let (left, right) = if mark_column < cursor_column { (mark_column, cursor_column) } else { (cursor_column, mark_column) };
+		Some((first, last, left, right))
+	}
+
+	fn column_at(&self, line: usize, ch: usize) -> usize
+	{
+		text::column_of(self.content.get(line).unwrap(), ch, self.tab_size)
+	}
+
+	//the chars of a line that start in the columns left..right, a tab reaching into the block from the left is not part of it
+	fn block_range(&self, line: usize, left: usize, right: usize) -> (usize, usize)
+	{
+		let content = self.content.get(line).unwrap();
+		let cells = text::layout(content, self.tab_size);
+		let start = cells.iter().find(|cell| cell.column >= left).map(|cell| cell.start).unwrap_or(content.len());
+		let end = cells.iter().find(|cell| cell.column >= right).map(|cell| cell.start).unwrap_or(content.len());
+		(start, end)
+	}
+
+	//add spaces to a line that ends before a column
+	fn pad_to_column(&mut self, line: usize, column: usize)
+	{
+		let len = self.content.get(line).unwrap().len();
+		let width = self.column_at(line, len);
+		if width < column
+		{
+			self.change((line, len), (line, len), vec![vec![' '; column - width]]);
+		}
+	}
+
+	//the text of every row of the block, short lines give shorter or empty rows
+	pub fn block_text(&self) -> Option<Vec<String>>
+	{
+		let (first, last, left, right) = match self.get_block()
+		{
+			Some(v) => v,
+			None => {return None;}
+		};
+		Some((first .. last+1).map(|line| {
+			let (start, end) = self.block_range(line, left, right);
+			self.content.get(line).unwrap()[start..end].iter().collect()
+		}).collect())
+	}
+
+	//remove the text of the block as one edit, returns false if it has no width
+	pub fn delete_block(&mut self) -> bool
+	{
+		let (first, last, left, right) = match self.get_block()
+		{
+			Some(v) if v.2 < v.3 => v,
+			_ => {return false;}
+		};
+		self.begin_edit(EditKind::Other);
+		for line in first .. last+1
+		{
+			let (start, end) = self.block_range(line, left, right);
+			self.change((line, start), (line, end), vec![Vec::new()]);
+		}
+		let start = self.block_range(first, left, left).0;
+		self.cursor_line = first;
+		self.cursor_char = start;
+		self.mark = None;
+		self.block = false;
+		self.modified = true;
+		true
+	}
+
+	//insert rows of text at the cursor column on the cursor line and the lines below as one edit,
+	//short lines are filled up with spaces and missing lines are added
+	pub fn paste_block(&mut self, rows: &[String])
+	{
+		self.begin_edit(EditKind::Other);
+		self.fold_edit = Some((self.cursor_line, self.cursor_line + rows.len(), self.content.len())); //lines are only added at the end
+		self.cursors.clear();
+		let column = self.column_at(self.cursor_line, self.cursor_char);
+		for (index, row) in rows.iter().enumerate()
+		{
+			let line = self.cursor_line + index;
+			if line >= self.content.len()
+			{
+				let end = self.get_end();
+				self.change(end, end, vec![Vec::new(), Vec::new()]);
+			}
+			self.pad_to_column(line, column);
+			let start = self.block_range(line, column, column).0;
+			self.change((line, start), (line, start), vec![row.chars().collect()]);
+		}
+		self.clamp_cursor_char();
+		self.mark = None;
+		self.block = false;
+		self.modified = true;
+	}
+
+	//replace every column of the block with a char as one edit, returns false if it has no width
+	pub fn fill_block(&mut self, ch: char) -> bool
+	{
+		let (first, last, left, right) = match self.get_block()
+		{
+			Some(v) if v.2 < v.3 => v,
+			_ => {return false;}
+		};
+		self.begin_edit(EditKind::Other);
+		for line in first .. last+1
+		{
+			self.pad_to_column(line, left);
+			let (start, end) = self.block_range(line, left, right);
+			self.change((line, start), (line, end), vec![vec![ch; right - left]]);
+		}
+		self.clamp_cursor_char();
+		self.mark = None;
+		self.block = false;
+		self.modified = true;
+		true
+	}
+
+	//put a cursor at the left column of the block on every row, short lines are filled up with spaces
+	fn block_to_cursors(&mut self)
+	{
+		let (first, last, left, _) = match self.get_block()
+		{
+			Some(v) => v,
+			None => {return;}
+		};
+		let short = (first .. last+1).any(|line| self.column_at(line, self.content.get(line).unwrap().len()) < left);
+		if short
+		{
+			self.begin_edit(EditKind::Other);
+			for line in first .. last+1
+			{
+				self.pad_to_column(line, left);
+			}
+			self.modified = true;
+		}
+		self.mark = None;
+		self.block = false;
+		self.cursors.clear();
+		self.cursor_char = self.block_range(self.cursor_line, left, left).0;
+		for line in first .. last+1
+		{
+			let start = self.block_range(line, left, left).0;
+			self.add_cursor(line, start);
+		}
+	}
+
+	//SELECTION FUNCTIONS
+
+	//start a selection at the cursor or drop the current one
+	pub fn toggle_mark(&mut self)
+	{
+		self.mark = match self.mark
+		{
+			Some(_) => None,
+			None => Some((self.cursor_line, self.cursor_char))
+		};
+		self.block = false;
+	}
+
+	pub fn has_mark(&self) -> bool
+	{
+		self.mark.is_some()
+	}
+
+	//return (start, end) of the selection as (line, char), start is always before end
+	pub fn get_selection(&self) -> Option<((usize, usize), (usize, usize))>
+	{
+		let mark = match self.mark
+		{
+			Some(v) => v,
+			None => {return None;}
+		};
+		let cursor = (self.cursor_line, self.cursor_char);
+		if mark < cursor
+		{
+			Some((mark, cursor))
+		}
+		else 
+		{
+		    Some((cursor, mark))
+		}
+	}
+
+	//first and last line touched by the selection, or the cursor line if there is none
+	fn selected_lines(&self) -> (usize, usize)
+	{
+		match self.get_selection()
+		{
+			Some((start, end)) => {
+				if end.1 == 0 && end.0 > start.0 //selection ends at the start of a line, don't touch it
+				{
+					(start.0, end.0 - 1)
+				}
+				else 
+				{
+				    (start.0, end.0)
+				}
+			},
+			None => (self.cursor_line, self.cursor_line)
+		}
+	}
+
+	//INDENTATION FUNCTIONS
+
+	//the characters inserted for one level of indentation
+	fn indent_unit(&self) -> Vec<char>
+	{
+		if self.expand_tab
+		{
+			vec![' '; self.tab_size]
+		}
+		else 
+		{
+		    vec!['\t']
+		}
+	}
+
+	//the leading whitespace of a line
+	fn leading_whitespace(&self, line: usize) -> Vec<char>
+	{
+		self.content.get(line).unwrap().iter().take_while(|ch| **ch == ' ' || **ch == '\t').cloned().collect()
+	}
+
+	//true if a line ending with 'ch' opens a new block in the language of this file
+	fn opens_block(&self, ch: char) -> bool
+	{
+		match ch
+		{
+			'{' | '[' | '(' => true,
+			':' => {
+				match self.path
+				{
+					Some(ref v) => v.ends_with(".py"),
+					None => false
+				}
+			},
+			_ => false
+		}
+	}
+
+	//insert one level of indentation (or a tab) at the cursor
+	pub fn tab(&mut self)
+	{
+		for ch in self.indent_unit()
+		{
+			self.write_char(ch);
+		}
+	}
+
+	//indent every selected line by one level
+	pub fn indent_selection(&mut self)
+	{
+		self.begin_edit(EditKind::Other);
+		self.cursors.clear();
+		let (first, last) = self.selected_lines();
+		let unit = self.indent_unit();
+		for line in first .. last+1
+		{
+			if self.content.get(line).unwrap().is_empty()
+			{
+				continue;
+			}
+			self.change((line, 0), (line, 0), vec![unit.clone()]);
+			if self.cursor_line == line
+			{
+				self.cursor_char += unit.len();
+			}
+			if let Some((mark_line, mark_char)) = self.mark
+			{
+				if mark_line == line
+				{
+					self.mark = Some((mark_line, mark_char + unit.len()));
+				}
+			}
+			self.modified = true;
+		}
+	}
+
+	//remove one level of indentation from every selected line
+	pub fn outdent_selection(&mut self)
+	{
+		self.begin_edit(EditKind::Other);
+		self.cursors.clear();
+		let (first, last) = self.selected_lines();
+		for line in first .. last+1
+		{
+			let removed = {
+				let content = self.content.get(line).unwrap();
+				if content.first() == Some(&'\t')
+				{
+					1
+				}
+				else 
+				{
+				    content.iter().take(self.tab_size).take_while(|ch| **ch == ' ').count()
+				}
+			};
+			if removed == 0
+			{
+				continue;
+			}
+			self.change((line, 0), (line, removed), vec![Vec::new()]);
+			if self.cursor_line == line
+			{
+				self.cursor_char = self.cursor_char.saturating_sub(removed);
+			}
+			if let Some((mark_line, mark_char)) = self.mark
+			{
+				if mark_line == line
+				{
+					self.mark = Some((mark_line, mark_char.saturating_sub(removed)));
+				}
+			}
+			self.modified = true;
+		}
+	}
+
+	//number of chars a backspace at cchar removes:
+	//inside leading spaces a whole indentation level, otherwise one grapheme cluster
+	fn backspace_len(&self, line: usize, cchar: usize) -> usize
+	{
+		let content = self.content.get(line).unwrap();
+		let before = &content[..cchar];
+		let spaces = before.iter().rev().take_while(|ch| **ch == ' ').count();
+		if self.tab_size == 0 || spaces == 0 || before.iter().any(|ch| *ch != ' ' && *ch != '\t')
+		{
+			return cchar - text::prev_boundary(content, cchar);
+		}
+		(spaces - 1) % self.tab_size + 1
+	}
+
+	//UNDO FUNCTIONS
+
+	//replace the text between two positions with lines of text, returns what was replaced
+	//the only place the content is changed, besides loading a document
+	fn splice(&mut self, start: (usize, usize), end: (usize, usize), mut lines: Vec<Vec<char>>) -> Edit
+	{
+		let removed = self.lines_between(start, end);
+		let inserted = lines.clone();
+		self.record_change(start, &removed, &inserted);
+		if start.0 == end.0 && lines.len() == 1
+		{
+			self.content.get_mut(start.0).unwrap().splice(start.1..end.1, lines.pop().unwrap());
+		}
+		else
+		{
+		    let tail = self.content.get(end.0).unwrap()[end.1..].to_vec();
+		    let mut head = self.content.get(start.0).unwrap()[..start.1].to_vec();
+		    head.append(&mut lines[0]);
+		    lines[0] = head;
+		    lines.last_mut().unwrap().extend(tail);
+		    self.content.splice(start.0 .. end.0+1, lines);
+		}
+		self.words.replace(start.0, end.0 - start.0 + 1, &self.content[start.0 .. start.0 + inserted.len()]);
+		Edit
+		{
+			start,
+			removed,
+			inserted
+		}
+	}
+
+	fn record_change(&mut self, start: (usize, usize), removed: &[Vec<char>], inserted: &[Vec<char>])
+	{
+		if self.changes.len() >= CHANGE_LIMIT
+		{
+			self.changes.clear();
+			self.changes_from = self.version;
+		}
+		let column = utf16_len(&self.content.get(start.0).unwrap()[..start.1]);
+		let last = removed.len() - 1;
+		let end_column = if last == 0 { column + utf16_len(&removed[0]) } else { utf16_len(&removed[last]) };
+		let text: Vec<String> = inserted.iter().map(|line| line.iter().collect()).collect();
+		self.changes.push(Change
+		{
+			version: self.version,
+			start: (start.0, column),
+			end: (start.0 + last, end_column),
+			text: text.join("\n")
+		});
+	}
+
+	//change the text as part of the running edit, returns the position after the new text
+	fn change(&mut self, start: (usize, usize), end: (usize, usize), lines: Vec<Vec<char>>) -> (usize, usize)
+	{
+		let edit = self.splice(start, end, lines);
+		let end = end_of(edit.start, &edit.inserted);
+		if let Some(step) = self.undo_stack.last_mut()
+		{
+			step.edits.push(edit);
+		}
+		end
+	}
+
+	//the text between two positions as lines
+	fn lines_between(&self, start: (usize, usize), end: (usize, usize)) -> Vec<Vec<char>>
+	{
+		(start.0 .. end.0+1).map(|line| {
+			let content = self.content.get(line).unwrap();
+			let from = if line == start.0 { start.1 } else { 0 };
+			let to = if line == end.0 { end.1 } else { content.len() };
+			content[from..to].to_vec()
+		}).collect()
+	}
+
+	//start a new undo step, unless the edit continues the running edit of the same kind
+	pub fn begin_edit(&mut self, kind: EditKind)
+	{
+		self.update_folds();
+		self.fold_edit = Some((self.cursor_line, self.cursor_line, self.content.len()));
+		self.version += 1;
+		self.redo_stack.clear();
+		let joined = self.group == Some(true) || (kind != EditKind::Other && self.last_edit == Some(kind));
+		if self.group.is_some()
+		{
+			self.group = Some(true);
+		}
+		if joined
+		{
+			return;
+		}
+		let step = Step
+		{
+			edits: Vec::new(),
+			cursor_line: self.cursor_line,
+			cursor_char: self.cursor_char,
+			folds: self.folds.clone()
+		};
+		self.undo_stack.push(step);
+		if self.undo_stack.len() > UNDO_LIMIT
+		{
+			self.undo_stack.remove(0);
+		}
+		self.last_edit = Some(kind);
+	}
+
+	//apply the edits of a step forward or backward and swap the cursor and folds with the current ones
+	fn replay(&mut self, step: &mut Step, forward: bool)
+	{
+		self.update_folds();
+		self.version += 1;
+		if forward
+		{
+			for edit in step.edits.iter()
+			{
+				let end = end_of(edit.start, &edit.removed);
+				self.splice(edit.start, end, edit.inserted.clone());
+			}
+		}
+		else
+		{
+		    for edit in step.edits.iter().rev()
+		    {
+		    	let end = end_of(edit.start, &edit.inserted);
+		    	self.splice(edit.start, end, edit.removed.clone());
+		    }
+		}
+		mem::swap(&mut self.cursor_line, &mut step.cursor_line);
+		mem::swap(&mut self.cursor_char, &mut step.cursor_char);
+		mem::swap(&mut self.folds, &mut step.folds);
+		self.fold_edit = None;
+		self.cursors.clear();
+		self.modified = true;
+		self.mark = None;
+		self.last_edit = None;
+	}
+
+	//revert the last edit, returns false if there is nothing to undo
+	pub fn undo(&mut self) -> bool
+	{
+		match self.undo_stack.pop()
+		{
+			Some(mut step) => {
+				self.replay(&mut step, false);
+				self.redo_stack.push(step);
+				true
+			},
+			None => false
+		}
+	}
+
+	//repeat the last undone edit, returns false if there is nothing to redo
+	pub fn redo(&mut self) -> bool
+	{
+		match self.redo_stack.pop()
+		{
+			Some(mut step) => {
+				self.replay(&mut step, true);
+				self.undo_stack.push(step);
+				true
+			},
+			None => false
+		}
+	}
+
+	//REFLOW FUNCTIONS
+
+	//indentation and comment marker a line starts with, e.g. "    // "
+	fn line_prefix(&self, line: usize) -> Vec<char>
+	{
+		let content = self.content.get(line).unwrap();
+		let mut end = content.iter().take_while(|ch| **ch == ' ' || **ch == '\t').count();
+		for marker in ["//", "#", ">"].iter()
+		{
+			let marker: Vec<char> = marker.chars().collect();
+			if content[end..].starts_with(&marker)
+			{
+				end += marker.len();
+				end += content[end..].iter().take_while(|ch| **ch == ' ').count();
+				break;
+			}
+		}
+		content[..end].to_vec()
+	}
+
+	//true if a line has the same prefix (ignoring spaces after the marker) and text after it
+	fn in_paragraph(&self, line: usize, prefix: &[char]) -> bool
+	{
+		let content = self.content.get(line).unwrap();
+		let own_prefix = self.line_prefix(line);
+		//indentation alone is compared whole, it separates paragraphs like a marker does
+		let trim = |prefix: &[char]| prefix.iter().rposition(|ch| *ch != ' ' && *ch != '\t').map(|v| v + 1).unwrap_or(prefix.len());
+		own_prefix[..trim(&own_prefix)] == prefix[..trim(prefix)]
+			&& content[own_prefix.len()..].iter().any(|ch| *ch != ' ' && *ch != '\t')
+	}
+
+	//rewrap the selected lines or the paragraph around the cursor to width columns,
+	//keeping indentation and comment markers, returns false if there is no text
+	//blank lines and changes of the prefix separate the paragraphs of a selection
+	pub fn reflow(&mut self, width: usize) -> bool
+	{
+		let (first, last) = if self.mark.is_some()
+		{
+			self.selected_lines()
+		}
+		else 
+		{
+			let prefix = self.line_prefix(self.cursor_line);
+			if self.in_paragraph(self.cursor_line, &prefix) == false
+			{
+				return false;
+			}
+			let mut first = self.cursor_line;
+			while first > 0 && self.in_paragraph(first - 1, &prefix)
+			{
+				first -= 1;
+			}
+			let mut last = self.cursor_line;
+			while last + 1 < self.content.len() && self.in_paragraph(last + 1, &prefix)
+			{
+				last += 1;
+			}
+			(first, last)
+		};
+		let mut new_lines = Vec::new();
+		let mut found = false; //any text in the lines
+		let mut line = first;
+		while line <= last
+		{
+			let prefix = self.line_prefix(line);
+			if self.in_paragraph(line, &prefix) == false //blank, kept as it is
+			{
+				new_lines.push(self.content.get(line).unwrap().clone());
+				line += 1;
+				continue;
+			}
+			let mut end = line;
+			while end < last && self.in_paragraph(end + 1, &prefix)
+			{
+				end += 1;
+			}
+			new_lines.extend(self.wrap_paragraph(line, end, &prefix, width));
+			found = true;
+			line = end + 1;
+		}
+		if found == false
+		{
+			return false;
+		}
+		self.begin_edit(EditKind::Other);
+		self.cursors.clear();
+		let end = (last, self.content.get(last).unwrap().len());
+		let (cursor_line, cursor_char) = self.change((first, 0), end, new_lines);
+		self.cursor_line = cursor_line;
+		self.cursor_char = cursor_char;
+		self.mark = None;
+		self.modified = true;
+		true
+	}
+
+	//the words of the lines from first to last, wrapped to width columns behind the prefix
+	fn wrap_paragraph(&self, first: usize, last: usize, prefix: &[char], width: usize) -> Vec<Vec<char>>
+	{
+		let mut words = Vec::new();
+		for line in first .. last+1
+		{
+			let own_prefix = self.line_prefix(line).len();
+			let text: String = self.content.get(line).unwrap()[own_prefix..].iter().cloned().collect();
+			for word in text.split_whitespace()
+			{
+				words.push(word.to_owned());
+			}
+		}
+		let prefix_width = text::column_of(prefix, prefix.len(), self.tab_size);
+		let mut result = Vec::new();
+		let mut line = prefix.to_vec();
+		let mut line_width = prefix_width;
+		for word in words
+		{
+			let word_width = text::str_width(&word);
+			if line.len() > prefix.len() && line_width + 1 + word_width > width
+			{
+				result.push(line);
+				line = prefix.to_vec();
+				line_width = prefix_width;
+			}
+			if line.len() > prefix.len()
+			{
+				line.push(' ');
+				line_width += 1;
+			}
+			line.extend(word.chars());
+			line_width += word_width;
+		}
+		result.push(line);
+		result
+	}
+
+	//EDITING FUNCTIONS
+
+	pub fn write_char(&mut self, ch: char)
+	{
+		if self.is_block() //typing in a block inserts on every row
+		{
+			self.block_to_cursors();
+		}
+		if self.cursors.is_empty() == false
+		{
+			return self.at_cursors(true, |data| data.write_char(ch));
+		}
+		let cline = self.get_cursor_line();
+		let cchar = self.get_cursor_char();
+		let next = self.content.get(cline).unwrap().get(cchar).cloned();
+		if self.overtype && next == Some(ch) && PAIRS.iter().any(|&(_, close)| close == ch)
+		{
+			self.cursor_char += 1;
+			self.mark = None;
+			return;
+		}
+		self.begin_edit(EditKind::Insert);
+		self.change((cline, cchar), (cline, cchar), vec![vec![ch]]);
+		self.cursor_char += 1;
+		self.modified = true;
+		self.mark = None;
+		if self.auto_pair
+		{
+			let close = match PAIRS.iter().find(|&&(open, _)| open == ch)
+			{
+				Some(&(_, v)) => v,
+				None => {return;}
+			};
+			let previous = if cchar > 0 { self.content.get(cline).unwrap().get(cchar - 1).cloned() } else { None };
+			//quotes are not paired next to words (e.g. "don't"), brackets only in front of spaces and closers
+			let pair = if close == ch
+			{
+				previous.map(is_word_char) != Some(true) && next.map(is_word_char) != Some(true)
+			}
+			else
+			{
+			    next.map(|next| next.is_whitespace() || PAIRS.iter().any(|&(_, close)| close == next)).unwrap_or(true)
+			};
+			if pair
+			{
+				self.change((cline, cchar + 1), (cline, cchar + 1), vec![vec![close]]);
+			}
+		}
+	}
+
+	pub fn backspace(&mut self)
+	{
+		if self.is_block() //a block with columns is removed, a thin one edits every row
+		{
+			if self.delete_block()
+			{
+				return;
+			}
+			self.block_to_cursors();
+		}
+		if self.cursors.is_empty() == false
+		{
+			return self.at_cursors(true, |data| data.backspace());
+		}
+		self.begin_edit(EditKind::Delete);
+		let cline = self.get_cursor_line();
+		let cchar = self.get_cursor_char();
+		let in_pair = cchar != 0 && {
+			let line = self.content.get(cline).unwrap();
+			PAIRS.iter().any(|&(open, close)| line[cchar - 1] == open && line.get(cchar) == Some(&close))
+		};
+		if self.pair_delete && in_pair
+		{
+			self.change((cline, cchar-1), (cline, cchar+1), vec![Vec::new()]);
+			self.cursor_char -= 1;
+		}
+		else if cchar != 0
+		{
+			let count = self.backspace_len(cline, cchar);
+			self.change((cline, cchar-count), (cline, cchar), vec![Vec::new()]);
+			self.cursor_char -= count;
+		}
+		else if cline != 0
+		{
+		    let new_char = self.content.get(cline-1).unwrap().len();
+		    self.change((cline-1, new_char), (cline, 0), vec![Vec::new()]);
+		    self.cursor_char = new_char;
+		    self.cursor_line -= 1;
+		}
+		self.modified = true;
+		self.mark = None;
+	}
+
+	pub fn remove(&mut self)
+	{
+		if self.is_block() //a block with columns is removed, a thin one edits every row
+		{
+			if self.delete_block()
+			{
+				return;
+			}
+			self.block_to_cursors();
+		}
+		if self.cursors.is_empty() == false
+		{
+			return self.at_cursors(true, |data| data.remove());
+		}
+		self.begin_edit(EditKind::Delete);
+		let cline = self.get_cursor_line();
+		let cchar = self.get_cursor_char();
+		let line_len = self.content.get(cline).unwrap().len();
+		if cchar != line_len
+		{
+			let next = text::next_boundary(self.content.get(cline).unwrap(), cchar);
+			self.change((cline, cchar), (cline, next), vec![Vec::new()]);
+		}
+		else if cline != self.content.len()-1
+		{
+		    self.change((cline, cchar), (cline+1, 0), vec![Vec::new()]);
+		}
+		self.modified = true;
+		self.mark = None;
+	}
+
+	//split the line at the cursor, the new line keeps the indentation of the old one
+	//and gets one more level if the old one opened a block
+	pub fn enter(&mut self)
+	{
+		if self.cursors.is_empty() == false
+		{
+			return self.at_cursors(true, |data| data.enter());
+		}
+		self.begin_edit(EditKind::Other);
+		let cline = self.get_cursor_line();
+		let cchar = self.get_cursor_char();
+		let indent = self.leading_whitespace(cline);
+		let (clip_start, clip_first) = {
+			let clip = &self.content.get(cline).unwrap()[cchar..];
+			let spaces = clip.iter().take_while(|ch| **ch == ' ' || **ch == '\t').count();
+			(cchar + spaces, clip.get(spaces).cloned())
+		};
+		let last_char = self.content.get(cline).unwrap()[..cchar].iter().rev().find(|ch| **ch != ' ' && **ch != '\t').cloned();
+		let opens = match last_char
+		{
+			Some(ch) => self.opens_block(ch),
+			None => false
+		};
+		let mut new_line = indent.clone();
+		let mut lines = vec![Vec::new()];
+		if opens
+		{
+			new_line.append(&mut self.indent_unit());
+			let closes = matches!((last_char, clip_first), (Some('{'), Some('}')) | (Some('['), Some(']')) | (Some('('), Some(')')));
+			self.cursor_char = new_line.len();
+			lines.push(new_line);
+			if closes //cursor was between a pair of brackets, move the closing one to its own line
+			{
+				lines.push(indent);
+			}
+		}
+		else
+		{
+		    self.cursor_char = new_line.len();
+		    lines.push(new_line);
+		}
+		self.change((cline, cchar), (cline, clip_start), lines);
+		self.cursor_line += 1;
+		self.modified = true;
+		self.mark = None;
+	}
+
+	//the text between two (line, char) positions, lines are joined with '\n'
+	pub fn get_text(&self, start: (usize, usize), end: (usize, usize)) -> String
+	{
+		let mut result = String::new();
+		for line in start.0 .. end.0+1
+		{
+			let content = self.content.get(line).unwrap();
+			let from = if line == start.0 { start.1 } else { 0 };
+			let to = if line == end.0 { end.1 } else { content.len() };
+			result.extend(content[from..to].iter());
+			if line != end.0
+			{
+				result.push('\n');
+			}
+		}
+		result
+	}
+
+	//position after the last char
+	pub fn get_end(&self) -> (usize, usize)
+	{
+		let last = self.content.len() - 1;
+		(last, self.content.get(last).unwrap().len())
+	}
+
+	//replace the text between two positions as one edit, the cursor goes to the end of the new text
+	pub fn replace_text(&mut self, start: (usize, usize), end: (usize, usize), text: &str)
+	{
+		self.begin_edit(EditKind::Other);
+		self.fold_edit = Some((start.0, end.0, self.content.len()));
+		self.cursors.clear();
+		let new_lines: Vec<Vec<char>> = text.split('\n').map(|part| part.chars().collect()).collect();
+		let (cursor_line, cursor_char) = self.change(start, end, new_lines);
+		self.cursor_line = cursor_line;
+		self.cursor_char = cursor_char;
+		self.mark = None;
+		self.modified = true;
+	}
+
+	//replace the whole text as one edit, e.g. with formatted code
+	//the cursor stays on the line with the same content closest to its old line, or on the same line number
+	pub fn replace_all(&mut self, text: &str)
+	{
+		let old_line: String = self.content.get(self.cursor_line).unwrap().iter().collect();
+		let old_line = old_line.trim().to_owned();
+		let indent = self.leading_whitespace(self.cursor_line).len();
+		let offset = self.cursor_char.saturating_sub(indent);
+		let old_index = self.cursor_line;
+		let end = self.get_end();
+		self.replace_text((0, 0), end, text);
+		let distance = |index: usize| index.abs_diff(old_index);
+		let mut best: Option<usize> = None;
+		if old_line.is_empty() == false
+		{
+			for (index, line) in self.content.iter().enumerate()
+			{
+				let line: String = line.iter().collect();
+				if line.trim() == old_line && best.map(|best| distance(index) < distance(best)).unwrap_or(true)
+				{
+					best = Some(index);
+				}
+			}
+		}
+		let line = match best
+		{
+			Some(v) => v,
+			None => if old_index < self.content.len() { old_index } else { self.content.len() - 1 }
+		};
+		let indent = self.leading_whitespace(line).len();
+		self.set_cursor(line, indent + offset);
+	}
+
+	//replace count lines starting at first as one edit, the cursor goes to the first of them
+	pub fn replace_lines(&mut self, first: usize, count: usize, lines: Vec<Vec<char>>)
+	{
+		self.begin_edit(EditKind::Other);
+		self.fold_edit = Some((first, first + count, self.content.len()));
+		self.cursors.clear();
+		self.change_lines(first, count, lines);
+		self.cursor_line = if first < self.content.len() { first } else { self.content.len() - 1 };
+		self.cursor_char = 0;
+		self.mark = None;
+		self.modified = true;
+	}
+
+	//replace whole lines, as a change of the text between the line ends around them
+	fn change_lines(&mut self, first: usize, count: usize, mut lines: Vec<Vec<char>>)
+	{
+		let end = self.get_end();
+		if first + count < self.content.len()
+		{
+			lines.push(Vec::new());
+			self.change((first, 0), (first + count, 0), lines);
+		}
+		else if first > 0
+		{
+			lines.insert(0, Vec::new());
+			let start = (first - 1, self.content.get(first - 1).unwrap().len());
+			self.change(start, end, lines);
+		}
+		else
+		{
+		    if lines.is_empty()
+		    {
+		    	lines.push(Vec::new());
+		    }
+		    self.change((0, 0), end, lines);
+		}
+	}
+
+	pub fn insert_text(&mut self, text: &str)
+	{
+		let cursor = (self.cursor_line, self.cursor_char);
+		self.replace_text(cursor, cursor, text);
+	}
+
+	//add lines at the end without moving the cursor, for output shown in scratch buffers
+	//output is not an edit, it can't be undone and the edits before it are forgotten
+	pub fn append_lines(&mut self, lines: Vec<String>)
+	{
+		if lines.is_empty()
+		{
+			return;
+		}
+		self.version += 1;
+		self.undo_stack.clear();
+		self.redo_stack.clear();
+		self.last_edit = None;
+		let mut lines: Vec<Vec<char>> = lines.iter().map(|line| line.chars().collect()).collect();
+		let end = self.get_end();
+		if end != (0, 0)
+		{
+			lines.insert(0, Vec::new());
+		}
+		self.splice(end, end, lines);
+	}
+
+	//copy-move to string
+	pub fn to_string_copy(&self) -> String
+	{
+		let mut result = String::new();
+		for line in &self.content
+		{
+			for ch in line
+			{
+				result.push(ch.clone());
+			}
+			result.push('\n');
+		}
+		result
+	}
+
+	//load from file
+	pub fn open(&mut self, path: String) -> Result<()>
+	{
+		let dummy = try!(FileData::from(path));
+		self.take_document(dummy);
+		Ok(())
+	}
+
+	//save to original file at self.path
+	pub fn save(&mut self) -> Result<()>
+	{
+		let dummy = String::new();
+		let path = match self.path
+		{
+			Some(ref v) => v.clone(),
+			None => dummy //should not happen! check this!
+		};
+		self.save_to(path)
+	}
+
+	//save to file other than original file at self.path
+	pub fn save_to(&mut self, path: String) -> Result<()>
+	{
+		let content = self.to_string_copy();
+		let bytes = content.into_bytes();
+		let mut file = try!(File::create(path));
+		try!(file.write_all(&bytes[..]));
+		self.modified = false;
+		Ok(())
+	}
+}
+
+fn utf16_len(chars: &[char]) -> usize
+{
+	chars.iter().map(|ch| ch.len_utf16()).sum()
+}
+
+//the position after lines of text inserted at start
+fn end_of(start: (usize, usize), lines: &[Vec<char>]) -> (usize, usize)
+{
+	let last = lines.len() - 1;
+	if last == 0
+	{
+		(start.0, start.1 + lines[0].len())
+	}
+	else
+	{
+	    (start.0 + last, lines[last].len())
+	}
+}
+
+//chars that words are made of, for word movement
+pub fn is_word_char(ch: char) -> bool
+{
+	ch.is_alphanumeric() || ch == '_'
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn data(text: &str) -> FileData
+	{
+		let mut data = FileData::new();
+		data.insert_text(text);
+		data.set_cursor(0, 0);
+		data
+	}
+
+	#[test]
+	fn outdent_drops_extra_cursors()
+	{
+		let mut data = data("    foo\n    foo");
+		data.set_cursor(0, 5);
+		assert!(data.add_next_occurrence());
+		data.outdent_selection();
+		assert!(data.get_cursors().is_empty());
+		data.write_char('x');
+		assert_eq!(data.get_text((0, 0), data.get_end()), "fxoo\n    foo");
+	}
+
+	#[test]
+	fn stale_cursors_are_clamped()
+	{
+		let mut data = data("abc\nabc");
+		data.set_cursor(1, 3);
+		data.cursors = vec![(0, 3)];
+		data.content[0].truncate(1);
+		data.write_char('x');
+		assert_eq!(data.get_text((0, 0), data.get_end()), "ax\nabcx");
+	}
+
+	fn text(data: &FileData) -> String
+	{
+		data.get_text((0, 0), data.get_end())
+	}
+
+	#[test]
+	fn undo_and_redo_edits()
+	{
+		let mut data = data("fn main() {}");
+		data.set_cursor(0, 11);
+		data.enter();
+		data.write_char('x');
+		data.write_char('y');
+		assert_eq!(text(&data), "fn main() {\n\txy\n}");
+		assert!(data.undo());
+		assert_eq!(text(&data), "fn main() {\n\t\n}");
+		assert_eq!((data.get_cursor_line(), data.get_cursor_char()), (1, 1));
+		assert!(data.undo());
+		assert_eq!(text(&data), "fn main() {}");
+		assert_eq!((data.get_cursor_line(), data.get_cursor_char()), (0, 11));
+		assert!(data.redo());
+		assert!(data.redo());
+		assert_eq!(text(&data), "fn main() {\n\txy\n}");
+		assert_eq!((data.get_cursor_line(), data.get_cursor_char()), (1, 3));
+		assert!(data.redo() == false);
+	}
+
+	#[test]
+	fn undo_joins_lines_again()
+	{
+		let mut data = data("ab\ncd");
+		data.set_cursor(1, 0);
+		data.backspace();
+		data.set_cursor(0, 2);
+		data.remove();
+		assert_eq!(text(&data), "abd");
+		data.undo();
+		data.undo();
+		assert_eq!(text(&data), "ab\ncd");
+	}
+
+	#[test]
+	fn undo_edit_at_all_cursors_at_once()
+	{
+		let mut data = data("a\na\na");
+		data.set_cursor(0, 1);
+		data.add_cursor_vertical(false);
+		data.add_cursor_vertical(false);
+		data.write_char('b');
+		data.enter();
+		assert_eq!(text(&data), "ab\n\nab\n\nab\n");
+		data.undo();
+		assert_eq!(text(&data), "ab\nab\nab");
+		data.undo();
+		assert_eq!(text(&data), "a\na\na");
+	}
+
+	#[test]
+	fn replace_lines_anywhere()
+	{
+		let mut data = data("a\nb\nc");
+		data.replace_lines(1, 1, vec![vec!['x'], vec!['y']]);
+		assert_eq!(text(&data), "a\nx\ny\nc");
+		data.replace_lines(3, 1, Vec::new());
+		assert_eq!(text(&data), "a\nx\ny");
+		data.replace_lines(3, 0, vec![vec!['z']]);
+		assert_eq!(text(&data), "a\nx\ny\nz");
+		data.replace_lines(0, 4, Vec::new());
+		assert_eq!(text(&data), "");
+		for _ in 0 .. 4
+		{
+			data.undo();
+		}
+		assert_eq!(text(&data), "a\nb\nc");
+	}
+
+	#[test]
+	fn undo_block_paste_and_reflow()
+	{
+		let mut data = data("a\nb");
+		data.set_cursor(1, 1);
+		data.paste_block(&[String::from("x"), String::from("y"), String::from("z")]);
+		assert_eq!(text(&data), "a\nbx\n y\n z");
+		data.set_cursor(0, 0);
+		data.reflow(80);
+		assert_eq!(text(&data), "a bx\n y\n z"); //the indented rows are a paragraph of their own
+		data.undo();
+		data.undo();
+		assert_eq!(text(&data), "a\nb");
+	}
+
+	//apply changes the way a language server does, to lines of UTF-16 code units
+	fn apply(text: &str, changes: &[Change]) -> String
+	{
+		let mut lines: Vec<Vec<u16>> = text.split('\n').map(|line| line.encode_utf16().collect()).collect();
+		for change in changes
+		{
+			let mut tail = lines[change.end.0][change.end.1..].to_vec();
+			let mut new: Vec<Vec<u16>> = change.text.split('\n').map(|line| line.encode_utf16().collect()).collect();
+			let mut head = lines[change.start.0][..change.start.1].to_vec();
+			head.append(&mut new[0]);
+			new[0] = head;
+			new.last_mut().unwrap().append(&mut tail);
+			lines.splice(change.start.0 .. change.end.0+1, new);
+		}
+		let lines: Vec<String> = lines.iter().map(|line| String::from_utf16(line).unwrap()).collect();
+		lines.join("\n")
+	}
+
+	#[test]
+	fn changes_lead_to_the_current_text()
+	{
+		let mut data = data("f\u{1F600}o(\u{e9})\nbar");
+		let old = text(&data);
+		let version = data.get_version();
+		data.set_cursor(0, 4);
+		data.write_char('x');
+		data.enter();
+		data.set_cursor(2, 0);
+		data.backspace();
+		data.indent_selection();
+		data.undo();
+		data.set_cursor(0, 2);
+		data.remove();
+		assert_eq!(apply(&old, data.changes_since(version).unwrap()), text(&data));
+		let version = data.get_version();
+		data.write_char('y');
+		assert_eq!(data.changes_since(version).unwrap().len(), 1);
+		data.clear();
+		assert!(data.changes_since(version).is_none());
+	}
+
+	#[test]
+	fn reflow_paragraph_around_cursor()
+	{
+		let mut data = data("one two three\nfour five\n\nsix seven");
+		data.set_cursor(1, 2);
+		assert!(data.reflow(9));
+		assert_eq!(text(&data), "one two\nthree\nfour five\n\nsix seven");
+		data.set_cursor(3, 0);
+		assert!(data.reflow(9) == false);
+	}
+
+	#[test]
+	fn reflow_keeps_indentation_and_comment_markers()
+	{
+		let mut comment = data("\t// alpha beta\n\t//   gamma delta epsilon\n\tcode();");
+		assert!(comment.reflow(23)); //the tab counts as 4 columns
+		assert_eq!(text(&comment), "\t// alpha beta gamma\n\t// delta epsilon\n\tcode();");
+		let mut quote = data("> a b\n> c d e f g");
+		assert!(quote.reflow(8));
+		assert_eq!(text(&quote), "> a b c\n> d e f\n> g");
+	}
+
+	#[test]
+	fn reflow_selected_paragraphs_separately()
+	{
+		//blank lines and changes of indentation or marker end a paragraph
+		let mut mixed = data("a\nb\nc\n\nd\ne\n// f\n// g\n  h\n  i\nj");
+		mixed.mark = Some((1, 0));
+		mixed.set_cursor(9, 1);
+		assert!(mixed.reflow(80));
+		assert_eq!(text(&mixed), "a\nb c\n\nd e\n// f g\n  h i\nj");
+		let mut blank = data("a\n\n  \nb");
+		blank.mark = Some((1, 0));
+		blank.set_cursor(2, 1);
+		assert!(blank.reflow(80) == false);
+		assert_eq!(text(&blank), "a\n\n  \nb");
+	}
+}
@@ -0,0 +1,277 @@
+use std::fs::File;
+use std::io::Read;
+use std::mem;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::TryRecvError;
+use std::thread;
+use walk;
+
+pub const MAX_RESULTS: usize = 1000; //number of ranked paths kept
+pub const BATCH_SIZE: usize = 500; //paths sent from the indexing thread at once
+pub const PREVIEW_BYTES: usize = 16000; //bytes of the selected file read for the preview
+
+//the fuzzy file finder overlay
+pub struct Finder
+{
+	pub query: String, //what the user typed
+	files: Vec<String>, //all paths found so far, relative to the working directory
+	results: Vec<(i64, usize)>, //(score, index into files) of the best matches, best first
+	pub selected: usize, //index into results
+	receiver: Option<Receiver<Vec<String>>>, //paths from the indexing thread, None when done
+	preview: Option<(usize, Vec<String>)> //(file index, lines) of the selected file
+}
+
+impl Finder
+{
+	//start indexing the working directory in the background
+	pub fn new() -> Finder
+	{
+		let (sender, receiver) = mpsc::channel();
+		thread::spawn(move || {
+			let mut batch = Vec::new();
+			walk::walk(&PathBuf::from("."), &mut |path| {
+				batch.push(path.to_owned());
+				if batch.len() >= BATCH_SIZE
+				{
+					let full = mem::take(&mut batch);
+					return sender.send(full).is_ok();
+				}
+				true
+			});
+			let _ = sender.send(batch);
+		});
+		Finder
+		{
+			query: String::new(),
+			files: Vec::new(),
+			results: Vec::new(),
+			selected: 0,
+			receiver: Some(receiver),
+			preview: None
+		}
+	}
+
+	pub fn is_indexing(&self) -> bool
+	{
+		self.receiver.is_some()
+	}
+
+	pub fn file_count(&self) -> usize
+	{
+		self.files.len()
+	}
+
+	pub fn result_count(&self) -> usize
+	{
+		self.results.len()
+	}
+
+	//take the paths found by the indexing thread, returns true if there were new ones
+	pub fn poll(&mut self) -> bool
+	{
+		let mut changed = false;
+		loop
+		{
+			let batch = match self.receiver
+			{
+				Some(ref receiver) => receiver.try_recv(),
+				None => {break;}
+			};
+			match batch
+			{
+				Ok(paths) => {
+					let first = self.files.len();
+					self.files.extend(paths);
+					self.rank_from(first);
+					self.update_preview();
+					changed = true;
+				},
+				Err(TryRecvError::Empty) => {break;},
+				Err(TryRecvError::Disconnected) => {
+					self.receiver = None;
+					changed = true;
+				}
+			}
+		}
+		changed
+	}
+
+	pub fn set_query(&mut self, query: String)
+	{
+		self.query = query;
+		self.results.clear();
+		self.selected = 0;
+		self.rank_from(0);
+		self.update_preview();
+	}
+
+	//score the files starting at first and merge them into the results
+	fn rank_from(&mut self, first: usize)
+	{
+		let query: Vec<char> = self.query.to_lowercase().chars().collect();
+		for index in first .. self.files.len()
+		{
+			if let Some((score, _)) = score(&query, &self.files[index])
+			{
+				self.results.push((score, index));
+			}
+		}
+		//best score first, shorter paths first among equal scores
+		let files = &self.files;
+		self.results.sort_by(|a, b| b.0.cmp(&a.0).then(files[a.1].len().cmp(&files[b.1].len())));
+		self.results.truncate(MAX_RESULTS);
+		if self.selected >= self.results.len()
+		{
+			self.selected = if self.results.is_empty() { 0 } else { self.results.len() - 1 };
+		}
+	}
+
+	//the path of the result at index and the positions of the matched chars in it
+	pub fn get_result(&self, index: usize) -> Option<(&String, Vec<usize>)>
+	{
+		let query: Vec<char> = self.query.to_lowercase().chars().collect();
+		match self.results.get(index)
+		{
+			Some(&(_, file)) => {
+				let path = &self.files[file];
+				let positions = match score(&query, path)
+				{
+					Some((_, v)) => v,
+					None => Vec::new()
+				};
+				Some((path, positions))
+			},
+			None => None
+		}
+	}
+
+	pub fn get_selected_path(&self) -> Option<String>
+	{
+		self.results.get(self.selected).map(|&(_, file)| self.files[file].clone())
+	}
+
+	pub fn move_selection(&mut self, up: bool)
+	{
+		if up && self.selected > 0
+		{
+			self.selected -= 1;
+		}
+		else if up == false && self.selected + 1 < self.results.len()
+		{
+			self.selected += 1;
+		}
+		self.update_preview();
+	}
+
+	//read the first lines of the selected file, again only if the selection changed
+	pub fn update_preview(&mut self)
+	{
+		let file = self.results.get(self.selected).map(|&(_, file)| file);
+		let previewed = self.preview.as_ref().map(|&(file, _)| file);
+		if file != previewed
+		{
+			self.preview = file.map(|file| (file, read_preview(&self.files[file])));
+		}
+	}
+
+	pub fn get_preview(&self) -> Option<&Vec<String>>
+	{
+		self.preview.as_ref().map(|(_, lines)| lines)
+	}
+}
+
+//rank how well the lowercase query matches a path, None if it doesn't contain the query chars in order
+//returns the score and the char positions of the matches
+pub fn score(query: &[char], path: &str) -> Option<(i64, Vec<usize>)>
+{
+	let chars: Vec<char> = path.chars().collect();
+	let name_start = match chars.iter().rposition(|ch| *ch == '/')
+	{
+		Some(v) => v + 1,
+		None => 0
+	};
+	let mut positions = Vec::new();
+	let mut score: i64 = 0;
+	let mut pos = 0;
+	for query_ch in query
+	{
+		let found = (pos..chars.len()).find(|index| chars[*index].to_lowercase().eq(query_ch.to_lowercase()));
+		let index = match found
+		{
+			Some(v) => v,
+			None => {return None;}
+		};
+		score += 1;
+		if positions.last().map(|last| last + 1 == index).unwrap_or(false)
+		{
+			score += 8; //consecutive chars
+		}
+		if index == 0 || "/_-. ".contains(chars[index - 1])
+		{
+			score += 6; //start of a word
+		}
+		if index >= name_start
+		{
+			score += 2; //in the file name instead of the directory
+		}
+		score -= (index - pos) as i64 / 4; //small penalty for gaps
+		positions.push(index);
+		pos = index + 1;
+	}
+	Some((score, positions))
+}
+
+//read the start of a file for the preview
+fn read_preview(path: &str) -> Vec<String>
+{
+	let mut bytes = Vec::new();
+	match File::open(path)
+	{
+		Ok(file) => {
+			if file.take(PREVIEW_BYTES as u64).read_to_end(&mut bytes).is_err()
+			{
+				return vec![String::from("<unreadable file>")];
+			}
+		},
+		Err(e) => {
+			return vec![format!("<{}>", e)];
+		}
+	}
+	if walk::is_binary(&bytes)
+	{
+		return vec![String::from("<binary file>")];
+	}
+	String::from_utf8_lossy(&bytes).lines().map(|line| line.replace('\t', "    ")).collect()
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn rank(query: &str, path: &str) -> Option<i64>
+	{
+		score(&query.chars().collect::<Vec<char>>(), path).map(|(score, _)| score)
+	}
+
+	#[test]
+	fn matches_in_order()
+	{
+		assert_eq!(score(&['m', 'r'], "src/main.rs").map(|(_, positions)| positions), Some(vec![4, 9]));
+		assert_eq!(rank("nm", "src/main.rs"), None);
+		assert_eq!(rank("x", "src/main.rs"), None);
+		assert_eq!(rank("", "src/main.rs"), Some(0));
+		assert!(rank("MAIN", "src/main.rs").is_some());
+	}
+
+	#[test]
+	fn better_matches_score_higher()
+	{
+		//consecutive chars, word starts and the file name count
+		assert!(rank("main", "src/main.rs") > rank("main", "src/my_animation.rs"));
+		assert!(rank("fd", "src/file_data.rs") > rank("fd", "src/filled.rs"));
+		assert!(rank("disp", "src/display.rs") > rank("disp", "display/src/mod.rs"));
+	}
+}
@@ -0,0 +1,133 @@
+use text;
+
+//the last line of the region that can be folded below a line, None if there is none
+//by indentation the region are the lines indented deeper than the line, by brackets
+//it reaches to the bracket closing the first one left open in the line
+pub fn region(content: &[Vec<char>], line: usize, by_brackets: bool, tab_size: usize) -> Option<usize>
+{
+	let last = if by_brackets { bracket_region(content, line) } else { indent_region(content, line, tab_size) };
+	last.filter(|last| *last > line)
+}
+
+//the region of the nearest line at or above a line whose region contains it
+pub fn enclosing(content: &[Vec<char>], line: usize, by_brackets: bool, tab_size: usize) -> Option<(usize, usize)>
+{
+	for header in (0 .. line+1).rev()
+	{
+		match region(content, header, by_brackets, tab_size)
+		{
+			Some(last) if last >= line => {return Some((header, last));},
+			_ => {}
+		}
+	}
+	None
+}
+
+fn indent_region(content: &[Vec<char>], line: usize, tab_size: usize) -> Option<usize>
+{
+	let indent = match indentation(content.get(line).unwrap(), tab_size)
+	{
+		Some(v) => v,
+		None => {return None;} //empty lines start nothing
+	};
+	let mut last = None;
+	for index in line+1 .. content.len()
+	{
+		match indentation(content.get(index).unwrap(), tab_size)
+		{
+			Some(v) if v <= indent => {break;},
+			Some(_) => last = Some(index),
+			None => {} //empty lines inside the region belong to it, the ones after it don't
+		}
+	}
+	last
+}
+
+//the width of the leading whitespace, None for lines with nothing else
+fn indentation(line: &[char], tab_size: usize) -> Option<usize>
+{
+	line.iter().position(|ch| *ch != ' ' && *ch != '\t').map(|start| text::column_of(line, start, tab_size))
+}
+
+fn bracket_region(content: &[Vec<char>], line: usize) -> Option<usize>
+{
+	//the first bracket of the line that is still open at its end
+	let mut open = Vec::new();
+	for ch in content.get(line).unwrap()
+	{
+		match *ch
+		{
+			'(' | '[' | '{' => open.push(*ch),
+			')' | ']' | '}' => {open.pop();},
+			_ => {}
+		}
+	}
+	if open.is_empty()
+	{
+		return None;
+	}
+	let mut depth = open.len();
+	for index in line+1 .. content.len()
+	{
+		let current = content.get(index).unwrap();
+		for (position, ch) in current.iter().enumerate()
+		{
+			match *ch
+			{
+				'(' | '[' | '{' => depth += 1,
+				')' | ']' | '}' => {
+					depth -= 1;
+					if depth == 0
+					{
+						//a closing bracket that starts its line stays visible, like in the indentation mode
+						let starts_line = current[..position].iter().all(|ch| *ch == ' ' || *ch == '\t');
+						return Some(if starts_line { index - 1 } else { index });
+					}
+				},
+				_ => {}
+			}
+		}
+	}
+	None
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn content(text: &str) -> Vec<Vec<char>>
+	{
+		text.split('\n').map(|line| line.chars().collect()).collect()
+	}
+
+	#[test]
+	fn by_indentation()
+	{
+		let content = content("fn a()\n\tlet x;\n\n\tif x\n\t\ty;\nfn b()\n\n");
+		assert_eq!(region(&content, 0, false, 4), Some(4));
+		assert_eq!(region(&content, 3, false, 4), Some(4));
+		assert_eq!(region(&content, 1, false, 4), None);
+		assert_eq!(region(&content, 2, false, 4), None);
+		assert_eq!(region(&content, 5, false, 4), None);
+	}
+
+	#[test]
+	fn by_brackets()
+	{
+		let content = content("fn a() {\n\tf(1,\n\t  2);\n}\nlet v = [1,\n2];");
+		assert_eq!(region(&content, 0, true, 4), Some(2));
+		assert_eq!(region(&content, 1, true, 4), Some(2));
+		assert_eq!(region(&content, 2, true, 4), None);
+		assert_eq!(region(&content, 4, true, 4), Some(5));
+	}
+
+	#[test]
+	fn nearest_enclosing_region()
+	{
+		let content = content("a\n\tb\n\t\tc\n\td\ne");
+		assert_eq!(enclosing(&content, 2, false, 4), Some((1, 2)));
+		assert_eq!(enclosing(&content, 3, false, 4), Some((0, 3)));
+		assert_eq!(enclosing(&content, 4, false, 4), None);
+	}
+}
@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::TryRecvError;
+use std::thread;
+use diff;
+use diff::Hunk;
+
+pub const COMMIT_BUFFER: &str = "[commit]";
+pub const BLAME_WIDTH: usize = 29; //"hash7 author.... yyyy-mm-dd"
+
+//what happened to the lines of a hunk, shown in the gutter
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Change
+{
+	Added,
+	Modified,
+	Deleted //marked on the line above the removed lines
+}
+
+//the changes of a buffer compared to the version of its file in the local git repository
+pub struct GitDiff
+{
+	base: Option<Vec<Vec<char>>>, //lines in the index, None -> the file is not tracked or not read yet
+	loading: Option<Receiver<Option<String>>>, //the file as git has it, while it is read in the background
+	hunks: Vec<Hunk>,
+	version: Option<usize> //version of the buffer the hunks belong to
+}
+
+impl GitDiff
+{
+	//read the version of the file from the index, or from HEAD if the index has none
+	//git runs in the background, there are no changes until poll took its answer
+	pub fn load(path: &str) -> GitDiff
+	{
+		let (dir, name) = split_path(path);
+		let (sender, receiver) = mpsc::channel();
+		thread::spawn(move || {
+			//":./name" and "HEAD:./name" are relative to the directory git runs in
+			let base = show(&dir, &format!(":./{}", name)).or_else(|| show(&dir, &format!("HEAD:./{}", name)));
+			let _ = sender.send(base);
+		});
+		GitDiff
+		{
+			base: None,
+			loading: Some(receiver),
+			hunks: Vec::new(),
+			version: None
+		}
+	}
+
+	//take the file read by git, returns true when it arrived
+	pub fn poll(&mut self) -> bool
+	{
+		let base = match self.loading
+		{
+			Some(ref receiver) => match receiver.try_recv()
+			{
+				Ok(v) => v,
+				Err(TryRecvError::Empty) => {return false;},
+				Err(TryRecvError::Disconnected) => None
+			},
+			None => {return false;}
+		};
+		self.loading = None;
+		self.base = base.map(|text| split_lines(&text));
+		self.version = None; //diffed on the next update
+		true
+	}
+
+	pub fn is_tracked(&self) -> bool
+	{
+		self.base.is_some()
+	}
+
+	//diff the content again if the buffer changed since the last update
+	pub fn update(&mut self, content: &[Vec<char>], version: usize)
+	{
+		if self.version == Some(version)
+		{
+			return;
+		}
+		self.version = Some(version);
+		self.hunks = match self.base
+		{
+			Some(ref base) => diff::diff(base, content),
+			None => Vec::new()
+		};
+	}
+
+	//the marked lines of the buffer
+	pub fn changes(&self) -> Vec<(usize, Change)>
+	{
+		let mut result = Vec::new();
+		for hunk in &self.hunks
+		{
+			if hunk.new_len == 0
+			{
+				result.push((mark_line(hunk), Change::Deleted));
+				continue;
+			}
+			let change = if hunk.old_len == 0 { Change::Added } else { Change::Modified };
+			for line in hunk.new_start .. hunk.new_start + hunk.new_len
+			{
+				result.push((line, change));
+			}
+		}
+		result
+	}
+
+	//the hunk marked at a line of the buffer
+	pub fn hunk_at(&self, line: usize) -> Option<Hunk>
+	{
+		self.hunks.iter().find(|hunk| {
+			if hunk.new_len == 0
+			{
+				mark_line(hunk) == line
+			}
+			else
+			{
+			    line >= hunk.new_start && line < hunk.new_start + hunk.new_len
+			}
+		}).cloned()
+	}
+
+	//the first line of the next or previous hunk after a line, wrapping around at the ends
+	pub fn step(&self, line: usize, forward: bool) -> Option<usize>
+	{
+		let lines: Vec<usize> = self.hunks.iter().map(|hunk| if hunk.new_len == 0 { mark_line(hunk) } else { hunk.new_start }).collect();
+		if forward
+		{
+			lines.iter().find(|start| **start > line).or(lines.first()).cloned()
+		}
+		else
+		{
+		    lines.iter().rev().find(|start| **start < line).or(lines.last()).cloned()
+		}
+	}
+
+	//the lines of the hunk as they are in the repository
+	pub fn base_lines(&self, hunk: &Hunk) -> Vec<Vec<char>>
+	{
+		match self.base
+		{
+			Some(ref base) => base[hunk.old_start .. hunk.old_start + hunk.old_len].to_vec(),
+			None => Vec::new()
+		}
+	}
+}
+
+//who changed a line last
+#[derive(Clone, PartialEq, Debug)]
+pub struct BlameLine
+{
+	pub hash: String,
+	pub author: String,
+	pub date: String //yyyy-mm-dd in the author's time zone
+}
+
+impl BlameLine
+{
+	//false for lines changed in the buffer or the working tree
+	pub fn is_committed(&self) -> bool
+	{
+		self.hash.chars().any(|ch| ch != '0')
+	}
+
+	//the text shown in the blame column
+	pub fn label(&self) -> String
+	{
+		if self.is_committed() == false
+		{
+			return String::from("not committed yet");
+		}
+		let author: String = self.author.chars().take(10).collect();
+		format!("{} {:<10} {}", &self.hash[..7], author, self.date)
+	}
+}
+
+//the lines of a finished git blame, or its error
+type BlameResult = Result<Vec<BlameLine>, String>;
+
+//git blame of a buffer, run again in the background when the buffer changed
+pub struct Blame
+{
+	path: String,
+	lines: Vec<BlameLine>, //by line of the buffer
+	version: Option<usize>, //version of the buffer the lines belong to
+	running: Option<(usize, Receiver<BlameResult>)> //version and result of a running blame
+}
+
+impl Blame
+{
+	pub fn new(path: String) -> Blame
+	{
+		Blame
+		{
+			path,
+			lines: Vec::new(),
+			version: None,
+			running: None
+		}
+	}
+
+	//blame the text of a buffer, the lines that are not committed are marked as such
+	pub fn start(&mut self, text: String, version: usize)
+	{
+		let (sender, receiver) = mpsc::channel();
+		let (dir, name) = split_path(&self.path);
+		thread::spawn(move || {
+			let _ = sender.send(run_blame(&dir, &name, text));
+		});
+		self.running = Some((version, receiver));
+	}
+
+	pub fn is_running(&self) -> bool
+	{
+		self.running.is_some()
+	}
+
+	//true if the lines are for this version of the buffer, or are being computed for it
+	pub fn is_current(&self, version: usize) -> bool
+	{
+		self.version == Some(version) || self.running.as_ref().map(|running| running.0 == version).unwrap_or(false)
+	}
+
+	//take the result of a finished blame, None if it is still running
+	pub fn poll(&mut self) -> Option<Result<(), String>>
+	{
+		let result = match self.running
+		{
+			Some((_, ref receiver)) => match receiver.try_recv()
+			{
+				Ok(v) => v,
+				Err(TryRecvError::Empty) => {return None;},
+				Err(TryRecvError::Disconnected) => Err(String::from("git blame stopped"))
+			},
+			None => {return None;}
+		};
+		let version = self.running.take().map(|running| running.0);
+		match result
+		{
+			Ok(lines) => {
+				self.lines = lines;
+				self.version = version;
+				Some(Ok(()))
+			},
+			Err(e) => Some(Err(e))
+		}
+	}
+
+	pub fn line(&self, line: usize) -> Option<&BlameLine>
+	{
+		self.lines.get(line)
+	}
+}
+
+//the header and message of a commit, as lines for a scratch buffer
+pub fn commit_message(path: &str, hash: &str) -> Result<Vec<String>, String>
+{
+	let (dir, _) = split_path(path);
+	let output = match Command::new("git").arg("show").arg("--no-patch").arg("--format=fuller").arg(hash).current_dir(&dir).output()
+	{
+		Ok(v) => v,
+		Err(e) => {return Err(format!("{}", e));}
+	};
+	if output.status.success() == false
+	{
+		return Err(first_error(&output.stderr, "git show failed"));
+	}
+	Ok(String::from_utf8_lossy(&output.stdout).lines().map(|line| line.to_owned()).collect())
+}
+
+//run git blame with the text on stdin instead of the file in the working tree
+fn run_blame(dir: &Path, name: &str, text: String) -> BlameResult
+{
+	let mut child = match Command::new("git").arg("blame").arg("--porcelain").arg("--contents").arg("-").arg("--").arg(name)
+		.current_dir(dir)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+	{
+		Ok(v) => v,
+		Err(e) => {return Err(format!("{}", e));}
+	};
+	let writer = child.stdin.take().map(|mut stdin| thread::spawn(move || {
+		let _ = stdin.write_all(text.as_bytes());
+	}));
+	let output = match child.wait_with_output()
+	{
+		Ok(v) => v,
+		Err(e) => {return Err(format!("{}", e));}
+	};
+	if let Some(writer) = writer
+	{
+		let _ = writer.join();
+	}
+	if output.status.success() == false
+	{
+		return Err(first_error(&output.stderr, "git blame failed"));
+	}
+	Ok(parse_blame(&String::from_utf8_lossy(&output.stdout)))
+}
+
+//read the output of git blame --porcelain, the author of a commit is only given at its first line
+pub fn parse_blame(output: &str) -> Vec<BlameLine>
+{
+	let mut result: Vec<BlameLine> = Vec::new();
+	let mut commits: HashMap<String, (String, String)> = HashMap::new(); //hash -> (author, date)
+	let mut current: Option<(String, usize)> = None; //hash and line of the entry being read
+	let (mut author, mut time, mut zone) = (String::new(), 0, String::new());
+	for line in output.lines()
+	{
+		if line.starts_with('\t') //the content of the line ends an entry
+		{
+			if let Some((hash, number)) = current.take()
+			{
+				let (author, date) = commits.entry(hash.clone()).or_insert_with(|| (author.clone(), format_date(time, &zone))).clone();
+				let empty = BlameLine { hash: String::new(), author: String::new(), date: String::new() };
+				while result.len() < number
+				{
+					result.push(empty.clone());
+				}
+				result[number - 1] = BlameLine { hash, author, date };
+			}
+			continue;
+		}
+		let mut parts = line.splitn(2, ' ');
+		let key = parts.next().unwrap_or("");
+		let value = parts.next().unwrap_or("");
+		if current.is_none() //"hash original-line final-line [lines in group]"
+		{
+			let number = value.split(' ').nth(1).and_then(|number| number.parse::<usize>().ok());
+			if let (40, Some(number)) = (key.len(), number)
+			{
+				if number > 0
+				{
+					current = Some((key.to_owned(), number));
+				}
+			}
+			continue;
+		}
+		match key
+		{
+			"author" => author = value.to_owned(),
+			"author-time" => time = value.parse::<i64>().unwrap_or(0),
+			"author-tz" => zone = value.to_owned(),
+			_ => {}
+		}
+	}
+	result
+}
+
+//a unix time as yyyy-mm-dd in a time zone like "+0200"
+fn format_date(time: i64, zone: &str) -> String
+{
+	let mut offset = 0;
+	if zone.len() == 5
+	{
+		let hours = zone[1..3].parse::<i64>().unwrap_or(0);
+		let minutes = zone[3..5].parse::<i64>().unwrap_or(0);
+		offset = (hours * 60 + minutes) * 60;
+		if zone.starts_with('-')
+		{
+			offset = -offset;
+		}
+	}
+	//days since 1970 to a date in the proleptic gregorian calendar
+	let days = (time + offset).div_euclid(86400);
+	let z = days + 719468;
+	let era = z.div_euclid(146097);
+	let day_of_era = z - era * 146097;
+	let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let month_index = (5 * day_of_year + 2) / 153;
+	let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+	let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+	let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+	format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn first_error(stderr: &[u8], default: &str) -> String
+{
+	let stderr = String::from_utf8_lossy(stderr);
+	match stderr.lines().find(|line| line.trim().is_empty() == false)
+	{
+		Some(v) => v.trim().to_owned(),
+		None => default.to_owned()
+	}
+}
+
+//the directory to run git in and the file name relative to it
+fn split_path(path: &str) -> (PathBuf, String)
+{
+	let path = Path::new(path);
+	let dir = match path.parent()
+	{
+		Some(v) if v.as_os_str().is_empty() == false => v.to_path_buf(),
+		_ => PathBuf::from(".")
+	};
+	let name = match path.file_name()
+	{
+		Some(v) => v.to_string_lossy().into_owned(),
+		None => String::new()
+	};
+	(dir, name)
+}
+
+fn mark_line(hunk: &Hunk) -> usize
+{
+	hunk.new_start.saturating_sub(1)
+}
+
+//the content of a git object like ":./main.rs", None if git fails or the file is not tracked
+fn show(dir: &Path, object: &str) -> Option<String>
+{
+	let output = match Command::new("git").arg("show").arg(object).current_dir(dir).output()
+	{
+		Ok(v) => v,
+		Err(_) => {return None;}
+	};
+	if output.status.success() == false
+	{
+		return None;
+	}
+	Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+//split text into lines the way FileData reads a file
+fn split_lines(text: &str) -> Vec<Vec<char>>
+{
+	let mut result: Vec<Vec<char>> = text.split('\n').map(|line| line.chars().collect()).collect();
+	if result.len() > 1 && result.last().map(|line| line.is_empty()).unwrap_or(false)
+	{
+		result.pop();
+	}
+	result
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::env;
+	use std::fs;
+	use std::process;
+	use std::time::Duration;
+	use std::time::Instant;
+
+	const A: &str = "1111111111111111111111111111111111111111";
+	const B: &str = "2222222222222222222222222222222222222222";
+
+	#[test]
+	fn blame_porcelain()
+	{
+		//the author of a commit is only given at its first line
+		let output = format!("{a} 1 1 2\nauthor Ann\nauthor-time 1700000000\nauthor-tz +0000\nsummary first\nfilename main.rs\n\tfn main()\n\
+			{a} 2 2\n\t{{\n\
+			{b} 5 3 1\nauthor Bob\nauthor-time 1700000000\nauthor-tz +0200\nsummary second\nprevious {a} main.rs\nfilename main.rs\n\t}}\n", a = A, b = B);
+		let blame = parse_blame(&output);
+		assert_eq!(blame, vec![
+			BlameLine { hash: A.to_owned(), author: String::from("Ann"), date: String::from("2023-11-14") },
+			BlameLine { hash: A.to_owned(), author: String::from("Ann"), date: String::from("2023-11-14") },
+			BlameLine { hash: B.to_owned(), author: String::from("Bob"), date: String::from("2023-11-15") }
+		]);
+	}
+
+	#[test]
+	fn blame_lines_out_of_order()
+	{
+		let output = format!("{a} 1 3 1\nauthor Ann\nauthor-time 0\nauthor-tz +0000\n\tc\n", a = A);
+		let blame = parse_blame(&output);
+		assert_eq!(blame.len(), 3);
+		assert_eq!(blame[0].hash, "");
+		assert_eq!(blame[2].date, "1970-01-01");
+		assert_eq!(parse_blame("fatal: no such path\n"), vec![]);
+	}
+
+	#[test]
+	fn dates()
+	{
+		assert_eq!(format_date(0, "+0000"), "1970-01-01");
+		assert_eq!(format_date(-1, "+0000"), "1969-12-31");
+		assert_eq!(format_date(951782400, "+0000"), "2000-02-29");
+		assert_eq!(format_date(1709164800, ""), "2024-02-29");
+		assert_eq!(format_date(1704067140, "+0000"), "2023-12-31");
+		assert_eq!(format_date(1704067140, "+0130"), "2024-01-01");
+		assert_eq!(format_date(1704070800, "-0200"), "2023-12-31");
+	}
+
+	fn loaded(path: &str) -> GitDiff
+	{
+		let mut git = GitDiff::load(path);
+		let start = Instant::now();
+		while git.poll() == false
+		{
+			assert!(start.elapsed() < Duration::from_secs(5), "git did not answer");
+			thread::sleep(Duration::from_millis(10));
+		}
+		git
+	}
+
+	#[test]
+	fn changes_against_the_index()
+	{
+		let dir = env::temp_dir().join(format!("mice-git-{}", process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		fs::write(dir.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+		let git = |args: &[&str]| Command::new("git").args(args).current_dir(&dir).output().unwrap();
+		git(&["init", "-q"]);
+		git(&["add", "a.txt"]);
+		let path = dir.join("a.txt").to_string_lossy().into_owned();
+		let mut diff = GitDiff::load(&path);
+		assert!(diff.is_tracked() == false); //nothing until git answered
+		diff = loaded(&path);
+		assert!(diff.is_tracked());
+		let content: Vec<Vec<char>> = ["one", "2", "three", "four"].iter().map(|line| line.chars().collect()).collect();
+		diff.update(&content, 1);
+		assert_eq!(diff.changes(), vec![(1, Change::Modified), (3, Change::Added)]);
+		fs::write(dir.join("b.txt"), "").unwrap();
+		assert!(loaded(&dir.join("b.txt").to_string_lossy()).is_tracked() == false);
+		let _ = fs::remove_dir_all(&dir);
+	}
+
+	#[test]
+	fn paths_and_errors()
+	{
+		assert_eq!(split_path("src/main.rs"), (PathBuf::from("src"), String::from("main.rs")));
+		assert_eq!(split_path("main.rs"), (PathBuf::from("."), String::from("main.rs")));
+		assert_eq!(first_error(b"\n  fatal: not a git repository\nmore\n", "failed"), "fatal: not a git repository");
+		assert_eq!(first_error(b"", "failed"), "failed");
+	}
+}
@@ -0,0 +1,145 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::TryRecvError;
+use std::thread;
+use walk;
+
+pub const GREP_BUFFER: &str = "[grep]";
+pub const PREVIEW_LEN: usize = 200; //chars of a matching line shown in the results
+
+//a search through all files below a directory, running in another thread
+pub struct Grep
+{
+	receiver: Option<Receiver<Vec<String>>>, //result lines per file, None when done
+	pub matches: usize, //number of matching lines found so far
+	pub files: usize //number of files with matches found so far
+}
+
+impl Grep
+{
+	//search for pattern in the files below root, case insensitive if the pattern is all lowercase
+	pub fn start(pattern: String, root: PathBuf) -> Grep
+	{
+		let (sender, receiver) = mpsc::channel();
+		thread::spawn(move || {
+			let ignore_case = pattern.chars().any(|ch| ch.is_uppercase()) == false;
+			let pattern = if ignore_case { pattern.to_lowercase() } else { pattern };
+			let show_root = root != Path::new(".");
+			walk::walk(&root, &mut |rel| {
+				let path = if show_root { root.join(rel).to_string_lossy().into_owned() } else { rel.to_owned() };
+				let lines = search_file(&path, &pattern, ignore_case);
+				if lines.is_empty()
+				{
+					return true;
+				}
+				sender.send(lines).is_ok()
+			});
+		});
+		Grep
+		{
+			receiver: Some(receiver),
+			matches: 0,
+			files: 0
+		}
+	}
+
+	pub fn is_running(&self) -> bool
+	{
+		self.receiver.is_some()
+	}
+
+	//take the result lines found since the last call
+	pub fn poll(&mut self) -> Vec<String>
+	{
+		let mut result = Vec::new();
+		loop
+		{
+			let lines = match self.receiver
+			{
+				Some(ref receiver) => receiver.try_recv(),
+				None => {break;}
+			};
+			match lines
+			{
+				Ok(lines) => {
+					self.matches += lines.len();
+					self.files += 1;
+					result.extend(lines);
+				},
+				Err(TryRecvError::Empty) => {break;},
+				Err(TryRecvError::Disconnected) => {
+					self.receiver = None;
+				}
+			}
+		}
+		result
+	}
+}
+
+//the matching lines of a file as "path:line:column: text", binary files are skipped
+fn search_file(path: &str, pattern: &str, ignore_case: bool) -> Vec<String>
+{
+	let mut result = Vec::new();
+	let mut bytes = Vec::new();
+	match File::open(path)
+	{
+		Ok(mut file) => {
+			if file.read_to_end(&mut bytes).is_err()
+			{
+				return result;
+			}
+		},
+		Err(_) => {return result;}
+	}
+	if walk::is_binary(&bytes)
+	{
+		return result;
+	}
+	let content = String::from_utf8_lossy(&bytes);
+	for (index, line) in content.lines().enumerate()
+	{
+		let found = if ignore_case
+		{
+			line.to_lowercase().find(pattern)
+		}
+		else
+		{
+		    line.find(pattern)
+		};
+		if let Some(byte_pos) = found
+		{
+			//lowercasing can change byte lengths, the column is only exact for plain text
+			let column = line.char_indices().take_while(|&(pos, _)| pos < byte_pos).count();
+			let preview: String = line.trim().chars().take(PREVIEW_LEN).collect();
+			result.push(format!("{}:{}:{}: {}", path, index + 1, column + 1, preview));
+		}
+	}
+	result
+}
+
+//read "path:line[:column]" from the start of a result or compiler message,
+//returns the path and the zero based line and column
+pub fn parse_location(text: &str) -> Option<(String, usize, usize)>
+{
+	let mut parts = text.splitn(4, ':');
+	let path = match parts.next()
+	{
+		Some(v) if v.trim().is_empty() == false => v.trim().to_owned(),
+		_ => {return None;}
+	};
+	let line = match parts.next().and_then(|v| v.trim().parse::<usize>().ok())
+	{
+		Some(v) if v > 0 => v - 1,
+		_ => {return None;}
+	};
+	let column = match parts.next().and_then(|v| v.trim().parse::<usize>().ok())
+	{
+		Some(v) if v > 0 => v - 1,
+		_ => 0
+	};
+	Some((path, line, column))
+}
@@ -0,0 +1,209 @@
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::path::PathBuf;
+use config;
+
+pub const HISTORY_FILE: &str = ".mice_history";
+pub const HISTORY_LIMIT: usize = 500; //number of commands kept on disk
+
+pub struct History
+{
+	entries: Vec<String>, //executed commands, oldest first
+	position: Option<usize>, //entry currently shown in the commandline, None -> the draft
+	draft: String, //what the user typed before browsing the history
+	search: Option<String> //prefix of a running ^R search
+}
+
+impl History
+{
+	pub fn new() -> History
+	{
+		History
+		{
+			entries: Vec::new(),
+			position: None,
+			draft: String::new(),
+			search: None
+		}
+	}
+
+	//load the history from ~/.mice_history, a missing file means an empty history
+	pub fn load() -> History
+	{
+		let mut history = History::new();
+		let path = match history_path()
+		{
+			Some(v) => v,
+			None => {return history;}
+		};
+		let mut content = String::new();
+		if let Ok(mut file) = File::open(path)
+		{
+			if file.read_to_string(&mut content).is_ok()
+			{
+				history.entries = content.lines().filter(|line| line.is_empty() == false).map(|line| line.to_owned()).collect();
+			}
+		}
+		history
+	}
+
+	fn save(&self)
+	{
+		let path = match history_path()
+		{
+			Some(v) => v,
+			None => {return;}
+		};
+		let mut content = String::new();
+		for entry in &self.entries
+		{
+			content.push_str(entry);
+			content.push('\n');
+		}
+		if let Ok(mut file) = File::create(path)
+		{
+			let _ = file.write_all(content.as_bytes());
+		}
+	}
+
+	//remember an executed command, repeated commands are only stored once
+	pub fn add(&mut self, command: String)
+	{
+		self.reset();
+		if command.trim().is_empty()
+		{
+			return;
+		}
+		self.entries.retain(|entry| entry != &command);
+		self.entries.push(command);
+		if self.entries.len() > HISTORY_LIMIT
+		{
+			let excess = self.entries.len() - HISTORY_LIMIT;
+			self.entries.drain(0..excess);
+		}
+		self.save();
+	}
+
+	//stop browsing, the next step starts at the newest entry again
+	pub fn reset(&mut self)
+	{
+		self.position = None;
+		self.search = None;
+	}
+
+	//step to an older entry, current is the text in the commandline
+	pub fn older(&mut self, current: String) -> Option<String>
+	{
+		self.search = None;
+		let next = match self.position
+		{
+			None => {
+				self.draft = current;
+				self.entries.len()
+			},
+			Some(v) => v
+		};
+		if next == 0
+		{
+			return None;
+		}
+		self.position = Some(next - 1);
+		Some(self.entries[next - 1].clone())
+	}
+
+	//step to a newer entry, after the newest one the draft is shown again
+	pub fn newer(&mut self) -> Option<String>
+	{
+		self.search = None;
+		match self.position
+		{
+			None => None,
+			Some(v) => {
+				if v + 1 < self.entries.len()
+				{
+					self.position = Some(v + 1);
+					Some(self.entries[v + 1].clone())
+				}
+				else
+				{
+				    self.position = None;
+					Some(self.draft.clone())
+				}
+			}
+		}
+	}
+
+	//^R: find the next older entry starting with what was typed when the search began
+	pub fn search(&mut self, current: String) -> Option<String>
+	{
+		if self.search.is_none()
+		{
+			self.position = None;
+			self.draft = current.clone();
+			self.search = Some(current);
+		}
+		let prefix = self.search.clone().unwrap();
+		let mut index = match self.position
+		{
+			Some(v) => v,
+			None => self.entries.len()
+		};
+		while index > 0
+		{
+			index -= 1;
+			if self.entries[index].starts_with(&prefix)
+			{
+				self.position = Some(index);
+				return Some(self.entries[index].clone());
+			}
+		}
+		None
+	}
+}
+
+fn history_path() -> Option<PathBuf>
+{
+	config::home_dir().map(|home| home.join(HISTORY_FILE))
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	//add() writes ~/.mice_history, the tests fill the entries directly
+	fn history(entries: &[&str]) -> History
+	{
+		let mut history = History::new();
+		history.entries = entries.iter().map(|entry| entry.to_string()).collect();
+		history
+	}
+
+	#[test]
+	fn browse()
+	{
+		let mut history = history(&["open a", "save", "quit"]);
+		assert_eq!(history.newer(), None);
+		assert_eq!(history.older(String::from("dra")), Some(String::from("quit")));
+		assert_eq!(history.older(String::from("quit")), Some(String::from("save")));
+		assert_eq!(history.older(String::from("save")), Some(String::from("open a")));
+		assert_eq!(history.older(String::from("open a")), None);
+		assert_eq!(history.newer(), Some(String::from("save")));
+		assert_eq!(history.newer(), Some(String::from("quit")));
+		assert_eq!(history.newer(), Some(String::from("dra")));
+		assert_eq!(history.newer(), None);
+	}
+
+	#[test]
+	fn search_by_prefix()
+	{
+		let mut history = history(&["open a", "save", "open b"]);
+		assert_eq!(history.search(String::from("op")), Some(String::from("open b")));
+		assert_eq!(history.search(String::from("open b")), Some(String::from("open a")));
+		assert_eq!(history.search(String::from("open a")), None);
+		history.reset();
+		assert_eq!(history.search(String::from("x")), None);
+		assert_eq!(history.older(String::from("x")), Some(String::from("open b")));
+	}
+}
@@ -0,0 +1,947 @@
+use serde_json;
+use serde_json::Map;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::path::Path;
+use std::process::Child;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::sync::mpsc::Sender;
+use std::sync::mpsc::TryRecvError;
+use std::thread;
+use filedata::FileData;
+
+pub const REFERENCES_BUFFER: &str = "[references]";
+
+//a problem reported by a language server
+pub struct LspDiagnostic
+{
+	pub line: usize, //zero based
+	pub message: String,
+	pub is_error: bool //false for warnings, hints and information
+}
+
+//a place in a file, as returned for definitions and references
+pub struct Location
+{
+	pub path: String,
+	pub line: usize,
+	pub column: usize //in UTF-16 code units
+}
+
+//an answer of the server to one of our requests
+pub enum Response
+{
+	Hover(Vec<String>), //lines of the hover text
+	Definition(Vec<Location>),
+	References(Vec<Location>),
+	Completion(Vec<(String, String)>) //(label, text to insert)
+}
+
+//something that happened since the last poll
+pub enum Event
+{
+	Response(Response),
+	Diagnostics, //new diagnostics for some file
+	Message(String), //an error of the server, or a message it wants to show
+	Exited
+}
+
+#[derive(Clone, Copy)]
+enum Request
+{
+	Initialize,
+	Hover,
+	Definition,
+	References,
+	Completion
+}
+
+//a language server running in another process
+pub struct Client
+{
+	child: Child,
+	writer: Sender<String>, //messages for the writing thread
+	reader: Option<Receiver<Value>>, //messages from the reading thread, None after the server exited
+	next_id: u64,
+	pending: HashMap<u64, Request>, //requests waiting for an answer
+	initialized: bool, //the server answered the initialize request
+	incremental: bool, //the server takes changed ranges instead of the whole text
+	queue: Vec<String>, //messages held back until the server is initialized
+	documents: HashMap<String, (usize, i64)>, //path -> (version of the buffer, version sent to the server)
+	diagnostics: HashMap<String, Vec<LspDiagnostic>> //path -> diagnostics
+}
+
+impl Client
+{
+	//start a server with a shell command, the working directory is the project root
+	pub fn start(command: &str, root: &Path) -> Result<Client, String>
+	{
+		let mut child = match Command::new("sh").arg("-c").arg(command)
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.stderr(Stdio::null())
+			.spawn()
+		{
+			Ok(v) => v,
+			Err(e) => {return Err(format!("{}", e));}
+		};
+		let (stdin, stdout) = match (child.stdin.take(), child.stdout.take())
+		{
+			(Some(stdin), Some(stdout)) => (stdin, stdout),
+			_ => {return Err(String::from("no pipes to the server"));}
+		};
+		//the editor never waits for the server, reading and writing happen in their own threads
+		let (writer, to_write) = mpsc::channel::<String>();
+		thread::spawn(move || {
+			let mut stdin = stdin;
+			for message in to_write
+			{
+				let frame = format!("Content-Length: {}\r\n\r\n{}", message.len(), message);
+				if stdin.write_all(frame.as_bytes()).and_then(|_| stdin.flush()).is_err()
+				{
+					break;
+				}
+			}
+		});
+		let (sender, reader) = mpsc::channel();
+		thread::spawn(move || {
+			let mut stdout = BufReader::new(stdout);
+			while let Some(message) = read_message(&mut stdout)
+			{
+				if sender.send(message).is_err()
+				{
+					break;
+				}
+			}
+		});
+		let mut client = Client
+		{
+			child,
+			writer,
+			reader: Some(reader),
+			next_id: 1,
+			pending: HashMap::new(),
+			initialized: false,
+			incremental: false,
+			queue: Vec::new(),
+			documents: HashMap::new(),
+			diagnostics: HashMap::new()
+		};
+		let root_uri = path_to_uri(&root.to_string_lossy());
+		let params = object(vec![
+			("processId", Value::Null),
+			("rootUri", Value::String(root_uri)),
+			("capabilities", object(vec![
+				("textDocument", object(vec![
+					("hover", object(vec![("contentFormat", Value::Array(vec![Value::String(String::from("plaintext"))]))])),
+					("publishDiagnostics", object(vec![])),
+					("completion", object(vec![]))
+				]))
+			]))
+		]);
+		client.request(Request::Initialize, "initialize", params);
+		Ok(client)
+	}
+
+	fn send(&mut self, message: Value)
+	{
+		let _ = self.writer.send(message.to_string());
+	}
+
+	fn request(&mut self, kind: Request, method: &str, params: Value)
+	{
+		let id = self.next_id;
+		self.next_id += 1;
+		self.pending.insert(id, kind);
+		let message = object(vec![
+			("jsonrpc", Value::String(String::from("2.0"))),
+			("id", Value::from(id)),
+			("method", Value::String(method.to_owned())),
+			("params", params)
+		]);
+		match kind
+		{
+			Request::Initialize => self.send(message),
+			_ => self.send_when_ready(message)
+		}
+	}
+
+	fn notification(&mut self, method: &str, params: Value)
+	{
+		let message = object(vec![
+			("jsonrpc", Value::String(String::from("2.0"))),
+			("method", Value::String(method.to_owned())),
+			("params", params)
+		]);
+		self.send_when_ready(message);
+	}
+
+	//nothing but initialize may be sent before the server answered it
+	fn send_when_ready(&mut self, message: Value)
+	{
+		if self.initialized
+		{
+			self.send(message);
+		}
+		else
+		{
+		    self.queue.push(message.to_string());
+		}
+	}
+
+	//true if the server knows this version of a buffer
+	pub fn is_synced(&self, path: &str, version: usize) -> bool
+	{
+		self.documents.get(path).map(|&(sent, _)| sent == version).unwrap_or(false)
+	}
+
+	//tell the server about the content of a buffer, opens it on the first call
+	//the buffer's version is its own change counter, nothing is sent if it did not change
+	//servers that want it get the changed ranges, the others (and all before initialize) the whole text
+	pub fn sync(&mut self, path: &str, language: &str, data: &FileData)
+	{
+		let version = data.get_version();
+		let sent = self.documents.get(path).cloned();
+		match sent
+		{
+			Some((old_version, _)) if old_version == version => {},
+			Some((old_version, server_version)) => {
+				self.documents.insert(path.to_owned(), (version, server_version + 1));
+				//a lower version comes from another buffer of the file, its changes say nothing about ours
+				let changes = match data.changes_since(old_version)
+				{
+					Some(changes) if self.incremental && version > old_version => changes.iter().map(|change| object(vec![
+						("range", object(vec![
+							("start", position(change.start)),
+							("end", position(change.end))
+						])),
+						("text", Value::String(change.text.clone()))
+					])).collect(),
+					_ => vec![object(vec![("text", Value::String(data.to_string_copy()))])]
+				};
+				let params = object(vec![
+					("textDocument", object(vec![
+						("uri", Value::String(path_to_uri(path))),
+						("version", Value::from(server_version + 1))
+					])),
+					("contentChanges", Value::Array(changes))
+				]);
+				self.notification("textDocument/didChange", params);
+			},
+			None => {
+				self.documents.insert(path.to_owned(), (version, 1));
+				let params = object(vec![
+					("textDocument", object(vec![
+						("uri", Value::String(path_to_uri(path))),
+						("languageId", Value::String(language.to_owned())),
+						("version", Value::from(1)),
+						("text", Value::String(data.to_string_copy()))
+					]))
+				]);
+				self.notification("textDocument/didOpen", params);
+			}
+		}
+	}
+
+	//tell the server a file is no longer edited, the next sync opens it again
+	pub fn close(&mut self, path: &str)
+	{
+		if self.documents.remove(path).is_none()
+		{
+			return;
+		}
+		self.diagnostics.remove(path);
+		let params = object(vec![("textDocument", object(vec![("uri", Value::String(path_to_uri(path)))]))]);
+		self.notification("textDocument/didClose", params);
+	}
+
+	fn position_params(&self, path: &str, line: usize, column: usize) -> Vec<(&'static str, Value)>
+	{
+		vec![
+			("textDocument", object(vec![("uri", Value::String(path_to_uri(path)))])),
+			("position", position((line, column)))
+		]
+	}
+
+	//column in UTF-16 code units, see utf16_column
+	pub fn hover(&mut self, path: &str, line: usize, column: usize)
+	{
+		let params = object(self.position_params(path, line, column));
+		self.request(Request::Hover, "textDocument/hover", params);
+	}
+
+	pub fn definition(&mut self, path: &str, line: usize, column: usize)
+	{
+		let params = object(self.position_params(path, line, column));
+		self.request(Request::Definition, "textDocument/definition", params);
+	}
+
+	pub fn references(&mut self, path: &str, line: usize, column: usize)
+	{
+		let mut params = self.position_params(path, line, column);
+		params.push(("context", object(vec![("includeDeclaration", Value::Bool(true))])));
+		self.request(Request::References, "textDocument/references", object(params));
+	}
+
+	pub fn completion(&mut self, path: &str, line: usize, column: usize)
+	{
+		let params = object(self.position_params(path, line, column));
+		self.request(Request::Completion, "textDocument/completion", params);
+	}
+
+	pub fn get_diagnostics(&self, path: &str) -> Option<&Vec<LspDiagnostic>>
+	{
+		self.diagnostics.get(path)
+	}
+
+	//handle the messages the server sent since the last call
+	pub fn poll(&mut self) -> Vec<Event>
+	{
+		let mut events = Vec::new();
+		loop
+		{
+			let message = match self.reader
+			{
+				Some(ref reader) => reader.try_recv(),
+				None => {break;}
+			};
+			match message
+			{
+				Ok(message) => {
+					if let Some(event) = self.handle(message)
+					{
+						events.push(event);
+					}
+				},
+				Err(TryRecvError::Empty) => {break;},
+				Err(TryRecvError::Disconnected) => {
+					self.reader = None;
+					events.push(Event::Exited);
+				}
+			}
+		}
+		events
+	}
+
+	fn handle(&mut self, message: Value) -> Option<Event>
+	{
+		let method = message.get("method").and_then(|v| v.as_str()).map(|v| v.to_owned());
+		let id = message.get("id").cloned();
+		match (method, id)
+		{
+			(Some(method), Some(id)) => {
+				//a request of the server, answer it so it does not wait for us
+				let result = if method == "workspace/configuration"
+				{
+					let count = message.pointer("/params/items").and_then(|v| v.as_array()).map(|v| v.len()).unwrap_or(0);
+					Value::Array(vec![Value::Null; count])
+				}
+				else
+				{
+				    Value::Null
+				};
+				let answer = object(vec![
+					("jsonrpc", Value::String(String::from("2.0"))),
+					("id", id),
+					("result", result)
+				]);
+				self.send(answer);
+				None
+			},
+			(Some(method), None) => {
+				match &method[..]
+				{
+					"textDocument/publishDiagnostics" => {
+						self.read_diagnostics(&message);
+						Some(Event::Diagnostics)
+					},
+					"window/showMessage" => {
+						message.pointer("/params/message").and_then(|v| v.as_str()).map(|v| Event::Message(v.to_owned()))
+					},
+					_ => None
+				}
+			},
+			(None, Some(id)) => {
+				let kind = match id.as_u64().and_then(|id| self.pending.remove(&id))
+				{
+					Some(v) => v,
+					None => {return None;}
+				};
+				if let Some(error) = message.pointer("/error/message").and_then(|v| v.as_str())
+				{
+					return Some(Event::Message(error.to_owned()));
+				}
+				let result = match message.get("result")
+				{
+					Some(v) => v,
+					None => {return None;}
+				};
+				match kind
+				{
+					Request::Initialize => {
+						self.initialized = true;
+						//textDocumentSync is a TextDocumentSyncKind or options with one, 2 is Incremental
+						let sync = result.pointer("/capabilities/textDocumentSync");
+						let kind = sync.and_then(|v| v.as_u64().or(v.get("change").and_then(|v| v.as_u64())));
+						self.incremental = kind == Some(2);
+						self.send(object(vec![
+							("jsonrpc", Value::String(String::from("2.0"))),
+							("method", Value::String(String::from("initialized"))),
+							("params", object(vec![]))
+						]));
+						for queued in self.queue.drain(..)
+						{
+							let _ = self.writer.send(queued);
+						}
+						None
+					},
+					Request::Hover => Some(Event::Response(Response::Hover(read_hover(result)))),
+					Request::Definition => Some(Event::Response(Response::Definition(read_locations(result)))),
+					Request::References => Some(Event::Response(Response::References(read_locations(result)))),
+					Request::Completion => Some(Event::Response(Response::Completion(read_completion(result))))
+				}
+			},
+			(None, None) => None
+		}
+	}
+
+	fn read_diagnostics(&mut self, message: &Value)
+	{
+		let path = match message.pointer("/params/uri").and_then(|v| v.as_str())
+		{
+			Some(v) => uri_to_path(v),
+			None => {return;}
+		};
+		let mut result = Vec::new();
+		if let Some(list) = message.pointer("/params/diagnostics").and_then(|v| v.as_array())
+		{
+			for diagnostic in list
+			{
+				let line = match read_position(diagnostic.pointer("/range/start")).map(|(line, _)| line)
+				{
+					Some(v) => v,
+					None => {continue;}
+				};
+				let message = diagnostic.get("message").and_then(|v| v.as_str()).unwrap_or("");
+				result.push(LspDiagnostic
+				{
+					line,
+					message: message.lines().next().unwrap_or("").to_owned(),
+					is_error: diagnostic.get("severity").and_then(|v| v.as_u64()).unwrap_or(1) == 1
+				});
+			}
+		}
+		self.diagnostics.insert(path, result);
+	}
+}
+
+impl Drop for Client
+{
+	fn drop(&mut self)
+	{
+		let _ = self.child.kill();
+		let _ = self.child.wait();
+	}
+}
+
+//read one "Content-Length: n\r\n\r\n<json>" message, None when the stream ends
+fn read_message<R: BufRead>(reader: &mut R) -> Option<Value>
+{
+	loop
+	{
+		let mut length = None;
+		loop
+		{
+			let mut header = String::new();
+			match reader.read_line(&mut header)
+			{
+				Ok(0) | Err(_) => {return None;},
+				Ok(_) => {}
+			}
+			let header = header.trim();
+			if header.is_empty()
+			{
+				break;
+			}
+			if header.to_lowercase().starts_with("content-length:")
+			{
+				length = header["content-length:".len()..].trim().parse::<usize>().ok();
+			}
+		}
+		let length = match length
+		{
+			Some(v) => v,
+			None => {continue;}
+		};
+		let mut body = vec![0; length];
+		if reader.read_exact(&mut body).is_err()
+		{
+			return None;
+		}
+		match serde_json::from_slice(&body)
+		{
+			Ok(v) => {return Some(v);},
+			Err(_) => {continue;} //skip broken messages
+		}
+	}
+}
+
+fn object(pairs: Vec<(&str, Value)>) -> Value
+{
+	let mut map = Map::new();
+	for (key, value) in pairs
+	{
+		map.insert(key.to_owned(), value);
+	}
+	Value::Object(map)
+}
+
+fn position((line, column): (usize, usize)) -> Value
+{
+	object(vec![("line", Value::from(line as u64)), ("character", Value::from(column as u64))])
+}
+
+fn read_position(position: Option<&Value>) -> Option<(usize, usize)>
+{
+	let position = match position
+	{
+		Some(v) => v,
+		None => {return None;}
+	};
+	match (position.get("line").and_then(|v| v.as_u64()), position.get("character").and_then(|v| v.as_u64()))
+	{
+		(Some(line), Some(column)) => Some((line as usize, column as usize)),
+		_ => None
+	}
+}
+
+//hover contents are a string, a {value} object or a list of those
+fn read_hover(result: &Value) -> Vec<String>
+{
+	let contents = match result.get("contents")
+	{
+		Some(v) => v,
+		None => {return Vec::new();}
+	};
+	let parts: Vec<&Value> = match contents.as_array()
+	{
+		Some(v) => v.iter().collect(),
+		None => vec![contents]
+	};
+	let mut lines = Vec::new();
+	for part in parts
+	{
+		let text = match part.as_str()
+		{
+			Some(v) => v,
+			None => part.get("value").and_then(|v| v.as_str()).unwrap_or("")
+		};
+		for line in text.lines()
+		{
+			if line.starts_with("```") == false
+			{
+				lines.push(line.to_owned());
+			}
+		}
+	}
+	lines
+}
+
+//a Location, a list of them or a list of LocationLinks
+fn read_locations(result: &Value) -> Vec<Location>
+{
+	let list: Vec<&Value> = match result.as_array()
+	{
+		Some(v) => v.iter().collect(),
+		None if result.is_null() => Vec::new(),
+		None => vec![result]
+	};
+	let mut locations = Vec::new();
+	for item in list
+	{
+		let uri = item.get("uri").or(item.get("targetUri")).and_then(|v| v.as_str());
+		let start = item.pointer("/range/start").or(item.pointer("/targetSelectionRange/start"));
+		if let (Some(uri), Some((line, column))) = (uri, read_position(start))
+		{
+			locations.push(Location
+			{
+				path: uri_to_path(uri),
+				line,
+				column
+			});
+		}
+	}
+	locations
+}
+
+//a list of CompletionItems or a CompletionList
+fn read_completion(result: &Value) -> Vec<(String, String)>
+{
+	let items = match result.as_array()
+	{
+		Some(v) => v,
+		None => match result.get("items").and_then(|v| v.as_array())
+		{
+			Some(v) => v,
+			None => {return Vec::new();}
+		}
+	};
+	let mut completions = Vec::new();
+	for item in items
+	{
+		let label = match item.get("label").and_then(|v| v.as_str())
+		{
+			Some(v) => v.trim().to_owned(),
+			None => {continue;}
+		};
+		let insert = item.pointer("/textEdit/newText").or(item.get("insertText")).and_then(|v| v.as_str());
+		let insert = match insert
+		{
+			Some(v) => strip_snippet(v),
+			None => label.clone()
+		};
+		completions.push((label, insert));
+	}
+	completions
+}
+
+//snippets are inserted without their tab stops, "foo(${1:x})$0" -> "foo(x)"
+fn strip_snippet(snippet: &str) -> String
+{
+	let chars: Vec<char> = snippet.chars().collect();
+	let mut result = String::new();
+	let mut index = 0;
+	let mut depth = 0; //open placeholders
+	while index < chars.len()
+	{
+		match (chars[index], chars.get(index + 1))
+		{
+			('\\', Some(&next)) => {
+				result.push(next);
+				index += 2;
+			},
+			('$', Some(&'{')) => {
+				//keep the placeholder text after the ':'
+				index += 2;
+				while index < chars.len() && chars[index].is_ascii_digit()
+				{
+					index += 1;
+				}
+				if index < chars.len() && chars[index] == ':'
+				{
+					index += 1;
+				}
+				depth += 1;
+			},
+			('$', Some(next)) if next.is_ascii_digit() => {
+				index += 1;
+				while index < chars.len() && chars[index].is_ascii_digit()
+				{
+					index += 1;
+				}
+			},
+			('}', _) if depth > 0 => {
+				depth -= 1;
+				index += 1;
+			},
+			(ch, _) => {
+				result.push(ch);
+				index += 1;
+			}
+		}
+	}
+	result
+}
+
+//the languageId of a file extension
+pub fn language_id(extension: &str) -> String
+{
+	let id = match extension
+	{
+		"rs" => "rust",
+		"py" => "python",
+		"js" => "javascript",
+		"ts" => "typescript",
+		"h" => "c",
+		"cc" | "cxx" | "hpp" => "cpp",
+		"sh" => "shellscript",
+		"md" => "markdown",
+		"rb" => "ruby",
+		"cs" => "csharp",
+		_ => extension
+	};
+	id.to_owned()
+}
+
+//"file://" uri of an absolute path, with the chars that need it percent encoded
+pub fn path_to_uri(path: &str) -> String
+{
+	let mut result = String::from("file://");
+	for byte in path.bytes()
+	{
+		if (byte as char).is_ascii_alphanumeric() || b"/-_.~".contains(&byte)
+		{
+			result.push(byte as char);
+		}
+		else
+		{
+		    result.push_str(&format!("%{:02X}", byte));
+		}
+	}
+	result
+}
+
+pub fn uri_to_path(uri: &str) -> String
+{
+	let encoded = uri.strip_prefix("file://").unwrap_or(uri);
+	let bytes = encoded.as_bytes();
+	let mut result = Vec::new();
+	let mut index = 0;
+	while index < bytes.len()
+	{
+		if bytes[index] == b'%' && index + 3 <= bytes.len()
+		{
+			let hex = String::from_utf8_lossy(&bytes[index+1..index+3]).into_owned();
+			if let Ok(byte) = u8::from_str_radix(&hex, 16)
+			{
+				result.push(byte);
+				index += 3;
+				continue;
+			}
+		}
+		result.push(bytes[index]);
+		index += 1;
+	}
+	String::from_utf8_lossy(&result).into_owned()
+}
+
+//language servers count columns in UTF-16 code units
+pub fn utf16_column(line: &[char], ch: usize) -> usize
+{
+	line[..ch.min(line.len())].iter().map(|ch| ch.len_utf16()).sum()
+}
+
+pub fn char_column(line: &[char], column: usize) -> usize
+{
+	let mut units = 0;
+	for (index, ch) in line.iter().enumerate()
+	{
+		if units >= column
+		{
+			return index;
+		}
+		units += ch.len_utf16();
+	}
+	line.len()
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use filedata::FileData;
+	use std::env;
+	use std::time::Duration;
+	use std::time::Instant;
+
+	const PATH: &str = "/tmp/mice test/a.rs";
+
+	//examples/mock_lsp.rs, cargo test builds it next to the test binary
+	fn server(args: &str) -> Client
+	{
+		let exe = env::current_exe().unwrap();
+		let path = exe.parent().unwrap().parent().unwrap().join("examples").join("mock_lsp");
+		assert!(path.exists(), "{} is missing, it is built by cargo test", path.display());
+		Client::start(&format!("'{}' {}", path.display(), args), Path::new("/tmp")).unwrap()
+	}
+
+	//poll until an event passes a check, panics after a few seconds
+	fn wait<F: Fn(&Event) -> bool>(client: &mut Client, check: F) -> Event
+	{
+		let start = Instant::now();
+		while start.elapsed() < Duration::from_secs(5)
+		{
+			for event in client.poll()
+			{
+				if let Event::Message(ref message) = event
+				{
+					panic!("message from the server: {}", message);
+				}
+				if check(&event)
+				{
+					return event;
+				}
+			}
+			thread::sleep(Duration::from_millis(10));
+		}
+		panic!("no answer from the server");
+	}
+
+	fn is_diagnostics(event: &Event) -> bool
+	{
+		matches!(*event, Event::Diagnostics)
+	}
+
+	fn data(text: &str) -> FileData
+	{
+		let mut data = FileData::new();
+		data.insert_text(text);
+		data
+	}
+
+	#[test]
+	fn initialize_handshake()
+	{
+		let mut client = server("");
+		assert!(client.initialized == false);
+		client.sync(PATH, "rust", &data("fn main() {}"));
+		wait(&mut client, is_diagnostics);
+		assert!(client.initialized);
+		assert!(client.incremental);
+	}
+
+	#[test]
+	fn requests_wait_for_initialize()
+	{
+		//the server answers initialize late and complains about anything it gets before
+		let mut client = server("");
+		client.sync(PATH, "rust", &data("bad"));
+		client.hover(PATH, 0, 1);
+		assert_eq!(client.queue.len(), 2);
+		wait(&mut client, |event| matches!(*event, Event::Response(Response::Hover(_))));
+		assert!(client.queue.is_empty());
+	}
+
+	#[test]
+	fn diagnostics_follow_changes()
+	{
+		let mut client = server("");
+		let mut data = data("\u{1F600} ok\nfine");
+		client.sync(PATH, "rust", &data);
+		wait(&mut client, is_diagnostics);
+		assert!(client.get_diagnostics(PATH).unwrap().is_empty());
+		data.set_cursor(0, 2);
+		for ch in "bad".chars()
+		{
+			data.write_char(ch);
+		}
+		data.set_cursor(1, 0);
+		data.enter();
+		client.sync(PATH, "rust", &data);
+		wait(&mut client, is_diagnostics);
+		{
+			let diagnostics = client.get_diagnostics(PATH).unwrap();
+			assert_eq!(diagnostics.len(), 1);
+			assert_eq!((diagnostics[0].line, &diagnostics[0].message[..], diagnostics[0].is_error), (0, "\u{1F600} badok", true));
+		}
+		data.undo();
+		data.undo();
+		client.sync(PATH, "rust", &data);
+		wait(&mut client, is_diagnostics);
+		assert!(client.get_diagnostics(PATH).unwrap().is_empty());
+	}
+
+	//the message of the only diagnostic of PATH, after the server sent new ones
+	fn diagnostic(client: &mut Client) -> Option<String>
+	{
+		wait(client, is_diagnostics);
+		let diagnostics = client.get_diagnostics(PATH).unwrap();
+		assert!(diagnostics.len() <= 1);
+		diagnostics.first().map(|diagnostic| diagnostic.message.clone())
+	}
+
+	fn edit(data: &mut FileData, text: &str)
+	{
+		data.set_cursor(0, 0);
+		for ch in text.chars()
+		{
+			data.write_char(ch);
+		}
+	}
+
+	#[test]
+	fn close_and_reopen()
+	{
+		//the new buffer of the file counts its versions from the start again
+		let mut client = server("");
+		let mut old = data("bad");
+		edit(&mut old, "very ");
+		client.sync(PATH, "rust", &old);
+		assert_eq!(diagnostic(&mut client), Some(String::from("very bad")));
+		client.close(PATH);
+		assert!(client.get_diagnostics(PATH).is_none());
+		let mut new = data("bad");
+		assert!(new.get_version() < old.get_version());
+		client.sync(PATH, "rust", &new);
+		assert_eq!(diagnostic(&mut client), Some(String::from("bad")));
+		edit(&mut new, "x");
+		client.sync(PATH, "rust", &new);
+		assert_eq!(diagnostic(&mut client), Some(String::from("xbad")));
+		client.close(PATH);
+		client.close(PATH);
+	}
+
+	#[test]
+	fn lower_version_sends_whole_text()
+	{
+		let mut client = server("");
+		let mut old = data("fine");
+		edit(&mut old, "very ");
+		client.sync(PATH, "rust", &old);
+		assert_eq!(diagnostic(&mut client), None);
+		let new = data("bad");
+		client.sync(PATH, "rust", &new);
+		assert_eq!(diagnostic(&mut client), Some(String::from("bad")));
+	}
+
+	#[test]
+	fn hover_definition_and_completion()
+	{
+		let mut client = server("");
+		client.sync(PATH, "rust", &data("fn main() {}"));
+		client.hover(PATH, 0, 3);
+		match wait(&mut client, |event| matches!(*event, Event::Response(_)))
+		{
+			Event::Response(Response::Hover(lines)) => assert_eq!(lines, vec![String::from("hover 0:3")]),
+			_ => panic!("no hover")
+		}
+		client.definition(PATH, 0, 3);
+		match wait(&mut client, |event| matches!(*event, Event::Response(_)))
+		{
+			Event::Response(Response::Definition(locations)) => {
+				assert_eq!(locations.len(), 1);
+				assert_eq!((&locations[0].path[..], locations[0].line, locations[0].column), (PATH, 1, 2));
+			},
+			_ => panic!("no definition")
+		}
+		client.completion(PATH, 0, 3);
+		match wait(&mut client, |event| matches!(*event, Event::Response(_)))
+		{
+			Event::Response(Response::Completion(items)) => assert_eq!(items, vec![(String::from("foo"), String::from("foo(x)"))]),
+			_ => panic!("no completion")
+		}
+	}
+
+	#[test]
+	fn server_exit()
+	{
+		let mut client = server("exit");
+		wait(&mut client, |event| matches!(*event, Event::Exited));
+		assert!(client.poll().is_empty());
+	}
+
+	#[test]
+	fn uri_round_trip()
+	{
+		assert_eq!(path_to_uri("/tmp/a b/ü.rs"), "file:///tmp/a%20b/%C3%BC.rs");
+		assert_eq!(uri_to_path("file:///tmp/a%20b/%C3%BC.rs"), "/tmp/a b/ü.rs");
+		assert_eq!(uri_to_path("file:///tmp/dir%20"), "/tmp/dir ");
+		assert_eq!(uri_to_path("file:///tmp/100%"), "/tmp/100%");
+	}
+}
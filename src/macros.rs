@@ -0,0 +1,204 @@
+use std::char;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::path::PathBuf;
+use config;
+
+pub const MACRO_FILE: &str = ".mice_macros";
+pub const DEFAULT_REGISTER: &str = "q";
+pub const MAX_DEPTH: usize = 10; //macros may play macros, but not endlessly
+
+//key presses recorded into named registers, kept in ~/.mice_macros
+pub struct Macros
+{
+	registers: HashMap<String, Vec<(u16, char)>>,
+	recording: Option<(String, Vec<(u16, char)>)>, //register and keys of a running recording
+	text_mark: usize, //number of keys recorded before the last key pressed in the text
+	pub last: String, //register recorded or played last
+	pub playing: usize //number of macros being played, one inside the other
+}
+
+impl Macros
+{
+	pub fn new() -> Macros
+	{
+		Macros
+		{
+			registers: HashMap::new(),
+			recording: None,
+			text_mark: 0,
+			last: String::from(DEFAULT_REGISTER),
+			playing: 0
+		}
+	}
+
+	//load the registers from ~/.mice_macros
+	pub fn load() -> Macros
+	{
+		let path = match macro_path()
+		{
+			Some(v) => v,
+			None => {return Macros::new();}
+		};
+		let mut content = String::new();
+		if let Ok(mut file) = File::open(path)
+		{
+			if file.read_to_string(&mut content).is_err()
+			{
+				return Macros::new();
+			}
+		}
+		Macros::parse(&content)
+	}
+
+	//one "name<tab>key:char key:char ..." per line, broken keys are skipped
+	fn parse(content: &str) -> Macros
+	{
+		let mut macros = Macros::new();
+		for line in content.lines()
+		{
+			let mut split = line.splitn(2, '\t');
+			let name = split.next().unwrap_or("");
+			let keys: Vec<(u16, char)> = split.next().unwrap_or("").split(' ').filter_map(|entry| {
+				let mut parts = entry.splitn(2, ':');
+				let key = parts.next().and_then(|key| key.parse::<u16>().ok());
+				let ch = parts.next().and_then(|ch| ch.parse::<u32>().ok()).and_then(char::from_u32);
+				match (key, ch)
+				{
+					(Some(key), Some(ch)) => Some((key, ch)),
+					_ => None
+				}
+			}).collect();
+			if name.is_empty() == false && keys.is_empty() == false
+			{
+				macros.registers.insert(name.to_owned(), keys);
+			}
+		}
+		macros
+	}
+
+	fn format(&self) -> String
+	{
+		let mut names: Vec<&String> = self.registers.keys().collect();
+		names.sort();
+		let mut content = String::new();
+		for name in names
+		{
+			let keys: Vec<String> = self.registers[name].iter().map(|&(key, ch)| format!("{}:{}", key, ch as u32)).collect();
+			content.push_str(&format!("{}\t{}\n", name, keys.join(" ")));
+		}
+		content
+	}
+
+	fn save(&self)
+	{
+		let path = match macro_path()
+		{
+			Some(v) => v,
+			None => {return;}
+		};
+		if let Ok(mut file) = File::create(path)
+		{
+			let _ = file.write_all(self.format().as_bytes());
+		}
+	}
+
+	pub fn is_recording(&self) -> bool
+	{
+		self.recording.is_some()
+	}
+
+	pub fn start(&mut self, name: &str)
+	{
+		self.recording = Some((name.to_owned(), Vec::new()));
+		self.text_mark = 0;
+		self.last = name.to_owned();
+	}
+
+	//remember a key press, in_text -> it was pressed in the text and not in the commandline
+	pub fn record(&mut self, key: u16, character: char, in_text: bool)
+	{
+		if let Some((_, ref mut keys)) = self.recording
+		{
+			if in_text
+			{
+				self.text_mark = keys.len();
+			}
+			keys.push((key, character));
+		}
+	}
+
+	//end the recording and store it, returns the register and the number of keys
+	//if it was ended with a command, the keys from opening the commandline on are left out
+	pub fn stop(&mut self, from_commandline: bool) -> Option<(String, usize)>
+	{
+		let (name, mut keys) = match self.recording.take()
+		{
+			Some(v) => v,
+			None => {return None;}
+		};
+		if from_commandline
+		{
+			keys.truncate(self.text_mark);
+		}
+		let len = keys.len();
+		if len > 0
+		{
+			self.registers.insert(name.clone(), keys);
+			self.save();
+		}
+		Some((name, len))
+	}
+
+	pub fn get(&self, name: &str) -> Option<Vec<(u16, char)>>
+	{
+		self.registers.get(name).cloned()
+	}
+}
+
+fn macro_path() -> Option<PathBuf>
+{
+	config::home_dir().map(|home| home.join(MACRO_FILE))
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn macro_file_round_trip()
+	{
+		let content = "a\t13:0 0:104 0:233\nq\t65517:0\n";
+		let macros = Macros::parse(content);
+		assert_eq!(macros.get("a"), Some(vec![(13, '\0'), (0, 'h'), (0, '\u{e9}')]));
+		assert_eq!(macros.get("q"), Some(vec![(65517, '\0')]));
+		assert_eq!(macros.format(), content);
+	}
+
+	#[test]
+	fn macro_file_skips_broken_entries()
+	{
+		let macros = Macros::parse("a\t0:104 x:1 0:55296 0\nb\t\n\t0:104\n");
+		assert_eq!(macros.get("a"), Some(vec![(0, 'h')]));
+		assert_eq!(macros.get("b"), None);
+		assert_eq!(macros.format(), "a\t0:104\n");
+	}
+
+	#[test]
+	fn commands_are_not_recorded()
+	{
+		let mut macros = Macros::new();
+		macros.start("w");
+		assert!(macros.is_recording());
+		macros.record(5, '\0', true); //opens the commandline
+		macros.record(0, 'x', false);
+		//the keys from opening the commandline on are left out
+		assert_eq!(macros.stop(true), Some((String::from("w"), 0)));
+		assert!(macros.is_recording() == false);
+		assert_eq!(macros.get("w"), None);
+		assert_eq!(macros.stop(false), None);
+	}
+}
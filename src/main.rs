@@ -1,4 +1,7 @@
 extern crate rustbox;
+extern crate unicode_width;
+extern crate unicode_segmentation;
+extern crate serde_json;
 
 use std::env;
 use std::io::Result;
@@ -10,9 +13,44 @@ use filedata::FileData;
 mod display;
 use display::Display;
 
+mod config;
+mod text;
+mod history;
+mod complete;
+mod walk;
+mod finder;
+mod browser;
+mod grep;
+mod shell;
+mod build;
+mod lsp;
+mod popup;
+mod words;
+mod diff;
+mod git;
+mod conflict;
+mod macros;
+mod fold;
+mod state;
+
 fn main() 
 {
-	let fd = match init_data()
+	let mut args: Vec<String> = env::args().skip(1).collect();
+	//mice --session <name> [file]
+	let session = match args.iter().position(|arg| arg == "--session")
+	{
+		Some(index) if index + 1 < args.len() => {
+			let name = args.remove(index + 1);
+			args.remove(index);
+			Some(name)
+		},
+		Some(_) => {
+			println!("usage: mice --session <name> [file]");
+			return;
+		},
+		None => None
+	};
+	let fd = match init_data(args)
 	{
 		Ok(v) => v,
 		Err(e) => {
@@ -20,14 +58,14 @@ fn main()
 			return;
 		}
 	};
-	let display = Display::new(fd);
+	let display = Display::new(fd, session);
 	display.run();
 }
 
 //create a new data object from cmd args
-fn init_data() -> Result<FileData>
+fn init_data(args: Vec<String>) -> Result<FileData>
 {
-	let mut args = env::args().skip(1);
+	let mut args = args.into_iter();
 	if args.len() > 0
 	{	
 		let path_str = args.next().unwrap_or(String::new());
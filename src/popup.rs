@@ -0,0 +1,110 @@
+pub const POPUP_ROWS: usize = 10; //entries shown at once
+pub const POPUP_WIDTH: usize = 60; //maximum width of the box
+
+//a box below the cursor with information or completions to choose from
+pub struct Popup
+{
+	items: Vec<(String, String)>, //all (label, text to insert)
+	pub shown: Vec<usize>, //indices into items that match the typed prefix
+	pub selected: Option<usize>, //index into shown, None -> only information, nothing to choose
+	pub scroll: usize, //first visible index into shown
+	pub line: usize, //line of the completed word
+	pub word_start: usize //char index of the start of the completed word
+}
+
+impl Popup
+{
+	//a box with lines of text, e.g. hover information
+	pub fn info(lines: Vec<String>) -> Popup
+	{
+		Popup
+		{
+			shown: (0..lines.len()).collect(),
+			items: lines.into_iter().map(|line| (line, String::new())).collect(),
+			selected: None,
+			scroll: 0,
+			line: 0,
+			word_start: 0
+		}
+	}
+
+	//a list of completions for the word starting at (line, word_start)
+	pub fn completion(items: Vec<(String, String)>, line: usize, word_start: usize) -> Popup
+	{
+		Popup
+		{
+			shown: (0..items.len()).collect(),
+			items,
+			selected: Some(0),
+			scroll: 0,
+			line,
+			word_start
+		}
+	}
+
+	pub fn is_completion(&self) -> bool
+	{
+		self.selected.is_some()
+	}
+
+	pub fn get_label(&self, index: usize) -> &str
+	{
+		&self.items[self.shown[index]].0
+	}
+
+	//the text to insert for the selected completion
+	pub fn get_selected(&self) -> Option<&str>
+	{
+		match self.selected
+		{
+			Some(v) if v < self.shown.len() => Some(&self.items[self.shown[v]].1),
+			_ => None
+		}
+	}
+
+	//move the selection, wrapping around at the ends; without selection scroll the text
+	pub fn move_selection(&mut self, up: bool)
+	{
+		let len = self.shown.len();
+		if len == 0
+		{
+			return;
+		}
+		match self.selected
+		{
+			Some(v) => {
+				let next = if up { (v + len - 1) % len } else { (v + 1) % len };
+				self.selected = Some(next);
+				if next < self.scroll
+				{
+					self.scroll = next;
+				}
+				if next >= self.scroll + POPUP_ROWS
+				{
+					self.scroll = next + 1 - POPUP_ROWS;
+				}
+			},
+			None => {
+				if up && self.scroll > 0
+				{
+					self.scroll -= 1;
+				}
+				else if up == false && self.scroll + POPUP_ROWS < len
+				{
+					self.scroll += 1;
+				}
+			}
+		}
+	}
+
+	//show only the completions starting with prefix, returns false if none is left
+	pub fn filter(&mut self, prefix: &str) -> bool
+	{
+		let prefix = prefix.to_lowercase();
+		let items = &self.items;
+		self.shown = (0..items.len()).filter(|index| items[*index].0.to_lowercase().starts_with(&prefix)).collect();
+		self.selected = Some(0);
+		self.scroll = 0;
+		self.shown.is_empty() == false
+	}
+}
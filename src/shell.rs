@@ -0,0 +1,89 @@
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+use std::thread;
+
+pub const SHELL_BUFFER: &str = "[shell]";
+
+//run a command line with sh, feeding input to its stdin
+//returns the output and the first line it wrote to stderr, e.g. a warning,
+//or an error message if it exited with an error
+pub fn run(command: &str, input: Option<String>) -> Result<(String, Option<String>), String>
+{
+	let mut child = match Command::new("sh").arg("-c").arg(command)
+		.stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+	{
+		Ok(v) => v,
+		Err(e) => {return Err(format!("{}", e));}
+	};
+	//write from another thread, a filter may fill its stdout before reading all of its input
+	let writer = match (input, child.stdin.take())
+	{
+		(Some(input), Some(mut stdin)) => Some(thread::spawn(move || {
+			let _ = stdin.write_all(input.as_bytes());
+		})),
+		_ => None
+	};
+	let output = match child.wait_with_output()
+	{
+		Ok(v) => v,
+		Err(e) => {return Err(format!("{}", e));}
+	};
+	if let Some(writer) = writer
+	{
+		let _ = writer.join();
+	}
+	let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+	let first_error = stderr.lines().find(|line| line.trim().is_empty() == false).map(|line| line.trim().to_owned());
+	if output.status.success() == false
+	{
+		let status = match output.status.code()
+		{
+			Some(v) => format!("exit code {}", v),
+			None => String::from("killed")
+		};
+		return match first_error
+		{
+			Some(v) => Err(format!("{}: {}", status, v)),
+			None => Err(status)
+		};
+	}
+	Ok((String::from_utf8_lossy(&output.stdout).into_owned(), first_error))
+}
+
+//run a command line with sh and collect stdout and stderr together, as a build tool prints them
+//returns if the command succeeded and its output
+pub fn run_combined(command: &str) -> Result<(bool, String), String>
+{
+	let output = match Command::new("sh").arg("-c").arg(format!("exec 2>&1\n{}", command))
+		.stdin(Stdio::null())
+		.output()
+	{
+		Ok(v) => v,
+		Err(e) => {return Err(format!("{}", e));}
+	};
+	Ok((output.status.success(), String::from_utf8_lossy(&output.stdout).into_owned()))
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn stderr_is_no_failure()
+	{
+		assert_eq!(run("echo out; echo warning: careful >&2", None), Ok((String::from("out\n"), Some(String::from("warning: careful")))));
+		assert_eq!(run("tr a b", Some(String::from("aa"))), Ok((String::from("bb"), None)));
+	}
+
+	#[test]
+	fn exit_status_is_failure()
+	{
+		assert_eq!(run("echo out; echo broken >&2; exit 3", None), Err(String::from("exit code 3: broken")));
+		assert_eq!(run("exit 1", None), Err(String::from("exit code 1")));
+	}
+}
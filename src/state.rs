@@ -0,0 +1,326 @@
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::path::PathBuf;
+use config;
+use filedata::FileData;
+
+pub const STATE_FILE: &str = ".mice_state";
+pub const STATE_LIMIT: usize = 500; //number of files remembered
+pub const SESSION_FILE: &str = ".mice_sessions";
+
+//what is remembered about a file between sessions
+#[derive(Clone, PartialEq, Debug)]
+pub struct FileState
+{
+	pub cursor: (usize, usize), //(line, char)
+	pub scroll: usize, //first line shown
+	pub folds: Vec<(usize, usize)> //(first, last) line of folded regions
+}
+
+//the states of recently edited files, kept in ~/.mice_state
+//the last cursor position, scroll position and folds of a file are restored when it is opened
+pub struct States
+{
+	files: Vec<(String, FileState)> //absolute path and state, most recently changed last
+}
+
+impl States
+{
+	pub fn new() -> States
+	{
+		States
+		{
+			files: Vec::new()
+		}
+	}
+
+	//load ~/.mice_state
+	pub fn load() -> States
+	{
+		let path = match state_path()
+		{
+			Some(v) => v,
+			None => {return States::new();}
+		};
+		let mut content = String::new();
+		if let Ok(mut file) = File::open(path)
+		{
+			if file.read_to_string(&mut content).is_err()
+			{
+				return States::new();
+			}
+		}
+		States::parse(&content)
+	}
+
+	//one "path<tab>name=value<tab>..." per file, unknown values are skipped
+	fn parse(content: &str) -> States
+	{
+		let mut states = States::new();
+		for line in content.lines()
+		{
+			let mut split = line.split('\t');
+			let file = match split.next()
+			{
+				Some(v) if v.is_empty() == false => v.to_owned(),
+				_ => {continue;}
+			};
+			let mut state = FileState { cursor: (0, 0), scroll: 0, folds: Vec::new() };
+			for field in split
+			{
+				let mut parts = field.splitn(2, '=');
+				match (parts.next(), parts.next())
+				{
+					(Some("cursor"), Some(value)) => state.cursor = parse_pairs(value, ':').pop().unwrap_or((0, 0)),
+					(Some("scroll"), Some(value)) => state.scroll = value.parse::<usize>().unwrap_or(0),
+					(Some("folds"), Some(value)) => state.folds = parse_pairs(value, '-'),
+					_ => {}
+				}
+			}
+			states.files.push((file, state));
+		}
+		states
+	}
+
+	fn format(&self) -> String
+	{
+		let mut content = String::new();
+		for (file, state) in &self.files
+		{
+			let folds: Vec<String> = state.folds.iter().map(|&(first, last)| format!("{}-{}", first, last)).collect();
+			content.push_str(&format!("{}\tcursor={}:{}\tscroll={}\tfolds={}\n", file, state.cursor.0, state.cursor.1, state.scroll, folds.join(",")));
+		}
+		content
+	}
+
+	fn save(&self)
+	{
+		let path = match state_path()
+		{
+			Some(v) => v,
+			None => {return;}
+		};
+		if let Ok(mut file) = File::create(path)
+		{
+			let _ = file.write_all(self.format().as_bytes());
+		}
+	}
+
+	fn get(&self, path: &str) -> Option<FileState>
+	{
+		let key = absolute(path);
+		self.files.iter().find(|(file, _)| file == &key).map(|(_, state)| state.clone())
+	}
+
+	//remember the state of the file of a buffer, buffers without a file are skipped
+	pub fn remember(&mut self, data: &FileData, scroll: usize)
+	{
+		if let Some(path) = data.get_path()
+		{
+			let state = FileState
+			{
+				cursor: (data.get_cursor_line(), data.get_cursor_char()),
+				scroll,
+				folds: data.get_folds().clone()
+			};
+			self.set(&path, state);
+		}
+	}
+
+	//bring a freshly opened buffer back into the state its file was left in, returns the first line to show
+	pub fn restore(&self, data: &mut FileData) -> usize
+	{
+		match data.get_path().and_then(|path| self.get(&path))
+		{
+			Some(state) => {
+				data.set_folds(state.folds);
+				data.set_cursor(state.cursor.0, state.cursor.1);
+				state.scroll
+			},
+			None => 0
+		}
+	}
+
+	//the first line shown when the file of a buffer was left
+	pub fn scroll(&self, data: &FileData) -> usize
+	{
+		data.get_path().and_then(|path| self.get(&path)).map(|state| state.scroll).unwrap_or(0)
+	}
+
+	//remember the state of a file and write all states to disk if it changed
+	fn set(&mut self, path: &str, state: FileState)
+	{
+		let key = absolute(path);
+		if self.files.iter().any(|(file, known)| file == &key && known == &state)
+		{
+			return;
+		}
+		self.files.retain(|(file, _)| file != &key);
+		self.files.push((key, state));
+		if self.files.len() > STATE_LIMIT
+		{
+			self.files.remove(0);
+		}
+		self.save();
+	}
+}
+
+//"1-5,8-12" to [(1, 5), (8, 12)], broken entries are skipped
+fn parse_pairs(value: &str, separator: char) -> Vec<(usize, usize)>
+{
+	value.split(',').filter_map(|entry| {
+		let mut parts = entry.splitn(2, separator);
+		let first = parts.next().and_then(|first| first.parse::<usize>().ok());
+		let last = parts.next().and_then(|last| last.parse::<usize>().ok());
+		match (first, last)
+		{
+			(Some(first), Some(last)) => Some((first, last)),
+			_ => None
+		}
+	}).collect()
+}
+
+//files are remembered by their absolute path, so they are found from any directory
+pub fn absolute(path: &str) -> String
+{
+	match fs::canonicalize(path)
+	{
+		Ok(v) => v.to_string_lossy().into_owned(),
+		Err(_) => path.to_owned()
+	}
+}
+
+fn state_path() -> Option<PathBuf>
+{
+	config::home_dir().map(|home| home.join(STATE_FILE))
+}
+
+//the files of a session, the one shown first, from ~/.mice_sessions
+//sessions are named, or keyed by the directory mice was started in
+pub fn load_session(key: &str) -> Vec<String>
+{
+	let path = match session_path()
+	{
+		Some(v) => v,
+		None => {return Vec::new();}
+	};
+	let mut content = String::new();
+	if let Ok(mut file) = File::open(path)
+	{
+		if file.read_to_string(&mut content).is_err()
+		{
+			return Vec::new();
+		}
+	}
+	session_files(&content, key)
+}
+
+//replace the files of a session in ~/.mice_sessions
+pub fn save_session(key: &str, files: Vec<String>)
+{
+	let path = match session_path()
+	{
+		Some(v) => v,
+		None => {return;}
+	};
+	let mut content = String::new();
+	if let Ok(mut file) = File::open(&path)
+	{
+		if file.read_to_string(&mut content).is_err()
+		{
+			return;
+		}
+	}
+	let files: Vec<String> = files.iter().map(|file| absolute(file)).collect();
+	let content = replace_session(&content, key, &files);
+	if let Ok(mut file) = File::create(path)
+	{
+		let _ = file.write_all(content.as_bytes());
+	}
+}
+
+//one "key<tab>file<tab>file..." per session
+fn session_files(content: &str, key: &str) -> Vec<String>
+{
+	for line in content.lines()
+	{
+		let mut split = line.split('\t');
+		if split.next() == Some(key)
+		{
+			return split.filter(|file| file.is_empty() == false).map(|file| file.to_owned()).collect();
+		}
+	}
+	Vec::new()
+}
+
+//the session file with the line of a session replaced, or dropped if it has no files
+fn replace_session(content: &str, key: &str, files: &[String]) -> String
+{
+	let mut lines: Vec<String> = content.lines().filter(|line| line.split('\t').next() != Some(key)).map(|line| line.to_owned()).collect();
+	if files.is_empty() == false
+	{
+		lines.push(format!("{}\t{}", key, files.join("\t")));
+	}
+	let mut content = String::new();
+	for line in lines
+	{
+		content.push_str(&line);
+		content.push('\n');
+	}
+	content
+}
+
+fn session_path() -> Option<PathBuf>
+{
+	config::home_dir().map(|home| home.join(SESSION_FILE))
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn pairs()
+	{
+		assert_eq!(parse_pairs("1-5,8-12", '-'), vec![(1, 5), (8, 12)]);
+		assert_eq!(parse_pairs("3:4", ':'), vec![(3, 4)]);
+		assert_eq!(parse_pairs("1-x,7,,2-3", '-'), vec![(2, 3)]);
+		assert_eq!(parse_pairs("", '-'), vec![]);
+	}
+
+	#[test]
+	fn state_file_round_trip()
+	{
+		let content = "/a.rs\tcursor=3:4\tscroll=2\tfolds=1-5,8-12\n/b.rs\tcursor=0:0\tscroll=0\tfolds=\n";
+		let states = States::parse(content);
+		assert_eq!(states.files, vec![
+			(String::from("/a.rs"), FileState { cursor: (3, 4), scroll: 2, folds: vec![(1, 5), (8, 12)] }),
+			(String::from("/b.rs"), FileState { cursor: (0, 0), scroll: 0, folds: Vec::new() })
+		]);
+		assert_eq!(states.format(), content);
+	}
+
+	#[test]
+	fn state_file_skips_unknown_and_broken_values()
+	{
+		let states = States::parse("\tcursor=1:1\n/a.rs\tcolor=red\tscroll=x\tcursor=5:6\n");
+		assert_eq!(states.files, vec![(String::from("/a.rs"), FileState { cursor: (5, 6), scroll: 0, folds: Vec::new() })]);
+	}
+
+	#[test]
+	fn sessions()
+	{
+		let content = replace_session("", "/home/a", &[String::from("/x.rs"), String::from("/y.rs")]);
+		let content = replace_session(&content, "notes", &[String::from("/n.txt")]);
+		assert_eq!(content, "/home/a\t/x.rs\t/y.rs\nnotes\t/n.txt\n");
+		assert_eq!(session_files(&content, "/home/a"), vec![String::from("/x.rs"), String::from("/y.rs")]);
+		assert_eq!(session_files(&content, "/home"), Vec::<String>::new());
+		let content = replace_session(&content, "/home/a", &[String::from("/z.rs")]);
+		assert_eq!(content, "notes\t/n.txt\n/home/a\t/z.rs\n");
+		let content = replace_session(&content, "notes", &[]);
+		assert_eq!(content, "/home/a\t/z.rs\n");
+	}
+}
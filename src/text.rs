@@ -0,0 +1,286 @@
+use unicode_width::UnicodeWidthChar;
+use unicode_width::UnicodeWidthStr;
+use unicode_segmentation::UnicodeSegmentation;
+
+//one grapheme cluster of a line as it appears on screen
+pub struct Cell
+{
+	pub start: usize, //index of the first char of the cluster in the line
+	pub column: usize, //screen column the cluster starts at
+	pub width: usize, //number of screen columns the cluster occupies
+	pub ch: char //the char that represents the cluster on screen, termbox can't show more than one per cell
+}
+
+//a part of a line that is drawn in one screen row when lines are wrapped
+pub struct Row
+{
+	pub start: usize, //index of the first char in the row
+	pub end: usize, //index after the last char in the row
+	pub column: usize //screen column of the first char if the line was not wrapped
+}
+
+//number of screen columns a single char occupies, control chars are shown as one cell
+pub fn char_width(ch: char) -> usize
+{
+	UnicodeWidthChar::width(ch).unwrap_or(1)
+}
+
+//number of screen columns a string occupies
+pub fn str_width(string: &str) -> usize
+{
+	UnicodeWidthStr::width(string)
+}
+
+//char indices where the grapheme clusters of a line start, followed by the line length
+pub fn grapheme_boundaries(line: &[char]) -> Vec<usize>
+{
+	let string: String = line.iter().cloned().collect();
+	let mut result = Vec::new();
+	let mut index = 0;
+	for grapheme in UnicodeSegmentation::graphemes(&string[..], true)
+	{
+		result.push(index);
+		index += grapheme.chars().count();
+	}
+	result.push(index);
+	result
+}
+
+//the cluster boundary before pos (or 0)
+pub fn prev_boundary(line: &[char], pos: usize) -> usize
+{
+	let mut result = 0;
+	for boundary in grapheme_boundaries(line)
+	{
+		if boundary >= pos
+		{
+			break;
+		}
+		result = boundary;
+	}
+	result
+}
+
+//the cluster boundary after pos (or the line length)
+pub fn next_boundary(line: &[char], pos: usize) -> usize
+{
+	for boundary in grapheme_boundaries(line)
+	{
+		if boundary > pos
+		{
+			return boundary;
+		}
+	}
+	line.len()
+}
+
+//the start of the cluster pos is in
+pub fn snap_boundary(line: &[char], pos: usize) -> usize
+{
+	let mut result = 0;
+	for boundary in grapheme_boundaries(line)
+	{
+		if boundary > pos
+		{
+			break;
+		}
+		result = boundary;
+	}
+	result
+}
+
+//number of columns a tab starting at column takes to reach the next tab stop
+pub fn tab_width(column: usize, tab_size: usize) -> usize
+{
+	if tab_size == 0
+	{
+		return 1;
+	}
+	tab_size - column % tab_size
+}
+
+//split a line into clusters and assign screen columns to them
+pub fn layout(line: &[char], tab_size: usize) -> Vec<Cell>
+{
+	let boundaries = grapheme_boundaries(line);
+	let mut result = Vec::new();
+	let mut column = 0;
+	for index in 0..boundaries.len()-1
+	{
+		let (start, end) = (boundaries[index], boundaries[index+1]);
+		let first = line[start];
+		let width = if first == '\t'
+		{
+			tab_width(column, tab_size)
+		}
+		else
+		{
+			//combining marks add nothing, emoji sequences never take more than two cells
+			let sum: usize = line[start..end].iter().map(|ch| char_width(*ch)).sum();
+			match sum
+			{
+				0 => 1,
+				1 | 2 => sum,
+				_ => 2
+			}
+		};
+		result.push(Cell
+		{
+			start,
+			column,
+			width,
+			ch: first
+		});
+		column += width;
+	}
+	result
+}
+
+//screen column of the char at pos (or the end of the line)
+pub fn column_of(line: &[char], pos: usize, tab_size: usize) -> usize
+{
+	let mut column = 0;
+	for cell in layout(line, tab_size)
+	{
+		if cell.start >= pos
+		{
+			return cell.column;
+		}
+		column = cell.column + cell.width;
+	}
+	column
+}
+
+//number of screen columns the cluster at pos occupies, 1 at the end of the line
+pub fn width_at(line: &[char], pos: usize, tab_size: usize) -> usize
+{
+	for cell in layout(line, tab_size)
+	{
+		if cell.start == pos
+		{
+			return cell.width;
+		}
+	}
+	1
+}
+
+//split a line into rows of at most width columns, at spaces if word_wrap is set
+pub fn wrap(line: &[char], tab_size: usize, width: usize, word_wrap: bool) -> Vec<Row>
+{
+	let cells = layout(line, tab_size);
+	let mut rows = Vec::new();
+	let mut row_start = 0;
+	let mut index = 0;
+	while index < cells.len()
+	{
+		let row_column = cells[row_start].column;
+		if index > row_start && cells[index].column + cells[index].width - row_column > width
+		{
+			let mut break_at = index;
+			if word_wrap
+			{
+				//break after the last space in the row, if there is one
+				if let Some(v) = (row_start+1 .. index+1).rev().find(|i| cells[*i-1].ch == ' ')
+				{
+					break_at = v;
+				}
+			}
+			rows.push(Row
+			{
+				start: cells[row_start].start,
+				end: cells[break_at].start,
+				column: row_column
+			});
+			row_start = break_at;
+			index = break_at;
+			continue;
+		}
+		index += 1;
+	}
+	let (start, column) = match cells.get(row_start)
+	{
+		Some(v) => (v.start, v.column),
+		None => (0, 0)
+	};
+	rows.push(Row
+	{
+		start,
+		end: line.len(),
+		column
+	});
+	rows
+}
+
+//index of the row pos is in
+pub fn row_of(rows: &[Row], pos: usize) -> usize
+{
+	let mut result = 0;
+	for (index, row) in rows.iter().enumerate()
+	{
+		if row.start <= pos
+		{
+			result = index;
+		}
+	}
+	result
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn chars(text: &str) -> Vec<char>
+	{
+		text.chars().collect()
+	}
+
+	#[test]
+	fn clusters()
+	{
+		//"e" with a combining acute accent, a family emoji joined with ZWJ
+		let line = chars("ae\u{301}\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}b");
+		assert_eq!(grapheme_boundaries(&line), vec![0, 1, 3, 8, 9]);
+		assert_eq!(next_boundary(&line, 1), 3);
+		assert_eq!(prev_boundary(&line, 8), 3);
+		assert_eq!(snap_boundary(&line, 5), 3);
+		assert_eq!(next_boundary(&line, 9), 9);
+		assert_eq!(prev_boundary(&line, 0), 0);
+	}
+
+	#[test]
+	fn widths()
+	{
+		assert_eq!(str_width("abc"), 3);
+		assert_eq!(str_width("日本"), 4);
+		assert_eq!(char_width('\u{7}'), 1);
+		let line = chars("\tx日e\u{301}\u{1F600}");
+		let cells: Vec<(usize, usize, usize, char)> = layout(&line, 4).iter().map(|cell| (cell.start, cell.column, cell.width, cell.ch)).collect();
+		assert_eq!(cells, vec![(0, 0, 4, '\t'), (1, 4, 1, 'x'), (2, 5, 2, '日'), (3, 7, 1, 'e'), (5, 8, 2, '\u{1F600}')]);
+		assert_eq!(column_of(&line, 3, 4), 7);
+		assert_eq!(column_of(&line, 6, 4), 10);
+		assert_eq!(width_at(&line, 2, 4), 2);
+		assert_eq!(width_at(&line, 6, 4), 1);
+		assert_eq!(tab_width(6, 4), 2);
+		assert_eq!(tab_width(6, 0), 1);
+	}
+
+	#[test]
+	fn wrapping()
+	{
+		let line = chars("one two three");
+		let rows: Vec<(usize, usize, usize)> = wrap(&line, 4, 8, false).iter().map(|row| (row.start, row.end, row.column)).collect();
+		assert_eq!(rows, vec![(0, 8, 0), (8, 13, 8)]);
+		let rows = wrap(&line, 4, 8, true);
+		let rows: Vec<(usize, usize)> = rows.iter().map(|row| (row.start, row.end)).collect();
+		assert_eq!(rows, vec![(0, 8), (8, 13)]);
+		let rows = wrap(&chars("aaaa bbbbbb"), 4, 8, true);
+		assert_eq!(rows.iter().map(|row| (row.start, row.end)).collect::<Vec<(usize, usize)>>(), vec![(0, 5), (5, 11)]);
+		assert_eq!(row_of(&rows, 4), 0);
+		assert_eq!(row_of(&rows, 5), 1);
+		//a wide char does not fit into the last column
+		let rows = wrap(&chars("abc日"), 4, 4, false);
+		assert_eq!(rows.iter().map(|row| (row.start, row.end)).collect::<Vec<(usize, usize)>>(), vec![(0, 3), (3, 4)]);
+		assert_eq!(wrap(&Vec::new(), 4, 8, false).len(), 1);
+	}
+}
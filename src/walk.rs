@@ -0,0 +1,309 @@
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+pub const IGNORE_FILE: &str = ".gitignore";
+pub const BINARY_CHECK_LEN: usize = 8000; //bytes looked at to decide if a file is binary
+
+//one line of a .gitignore file
+struct IgnoreRule
+{
+	base: String, //directory of the .gitignore, relative to the root, "" or ending with '/'
+	pattern: Vec<char>, //the glob without '!', leading and trailing '/'
+	negate: bool, //'!' -> matching paths are not ignored
+	dir_only: bool, //trailing '/' -> only matches directories
+	anchored: bool //contains '/' -> matched against the path relative to base instead of the name
+}
+
+//call found for every file below root that is not ignored by a .gitignore, with its path relative to root
+//found returns false to stop walking
+pub fn walk<F>(root: &Path, found: &mut F) where F: FnMut(&str) -> bool
+{
+	let mut rules = Vec::new();
+	walk_dir(root, "", &mut rules, found);
+}
+
+//returns false if walking was stopped
+fn walk_dir<F>(root: &Path, rel: &str, rules: &mut Vec<IgnoreRule>, found: &mut F) -> bool where F: FnMut(&str) -> bool
+{
+	let dir = root.join(rel);
+	let rule_count = rules.len();
+	load_rules(&dir.join(IGNORE_FILE), rel, rules);
+	let mut entries: Vec<(String, bool)> = match fs::read_dir(&dir)
+	{
+		Ok(v) => v.filter_map(|entry| entry.ok()).map(|entry| {
+			let is_dir = entry.file_type().map(|file_type| file_type.is_dir()).unwrap_or(false);
+			(entry.file_name().to_string_lossy().into_owned(), is_dir)
+		}).collect(),
+		Err(_) => Vec::new()
+	};
+	entries.sort();
+	let mut go_on = true;
+	for (name, is_dir) in entries
+	{
+		if name == ".git"
+		{
+			continue;
+		}
+		let path = format!("{}{}", rel, name);
+		if is_ignored(rules, &path, is_dir)
+		{
+			continue;
+		}
+		go_on = if is_dir
+		{
+			walk_dir(root, &format!("{}/", path), rules, found)
+		}
+		else
+		{
+		    found(&path)
+		};
+		if go_on == false
+		{
+			break;
+		}
+	}
+	rules.truncate(rule_count);
+	go_on
+}
+
+//read the rules of a .gitignore in the directory base
+fn load_rules(path: &Path, base: &str, rules: &mut Vec<IgnoreRule>)
+{
+	let mut content = String::new();
+	match File::open(path)
+	{
+		Ok(mut file) => {
+			if file.read_to_string(&mut content).is_err()
+			{
+				return;
+			}
+		},
+		Err(_) => {return;}
+	}
+	for line in content.lines()
+	{
+		let mut line = line.trim_end();
+		if line.is_empty() || line.starts_with('#')
+		{
+			continue;
+		}
+		let negate = line.starts_with('!');
+		if negate
+		{
+			line = &line[1..];
+		}
+		let dir_only = line.ends_with('/');
+		let line = line.trim_end_matches('/');
+		let anchored = line.contains('/');
+		let line = line.trim_start_matches('/');
+		if line.is_empty()
+		{
+			continue;
+		}
+		rules.push(IgnoreRule
+		{
+			base: base.to_owned(),
+			pattern: line.chars().collect(),
+			negate,
+			dir_only,
+			anchored
+		});
+	}
+}
+
+//the last matching rule decides
+fn is_ignored(rules: &Vec<IgnoreRule>, path: &str, is_dir: bool) -> bool
+{
+	let mut result = false;
+	for rule in rules
+	{
+		if rule.dir_only && is_dir == false
+		{
+			continue;
+		}
+		if path.starts_with(&rule.base) == false
+		{
+			continue;
+		}
+		let rel = &path[rule.base.len()..];
+		let subject: Vec<char> = if rule.anchored
+		{
+			rel.chars().collect()
+		}
+		else
+		{
+		    rel.rsplit('/').next().unwrap_or(rel).chars().collect()
+		};
+		if glob_match(&rule.pattern, &subject)
+		{
+			result = rule.negate == false;
+		}
+	}
+	result
+}
+
+//match a glob with *, **, ? and [...] against text, only ** matches '/'
+pub fn glob_match(pattern: &[char], text: &[char]) -> bool
+{
+	match pattern.first()
+	{
+		None => text.is_empty(),
+		Some(&'*') => {
+			if pattern.get(1) == Some(&'*')
+			{
+				if pattern.get(2) == Some(&'/')
+				{
+					//'**/' matches whole directories, also none at all
+					let rest = &pattern[3..];
+					(0..text.len()+1).any(|skip| (skip == 0 || text[skip - 1] == '/') && glob_match(rest, &text[skip..]))
+				}
+				else
+				{
+				    (0..text.len()+1).any(|skip| glob_match(&pattern[2..], &text[skip..]))
+				}
+			}
+			else
+			{
+				let rest = &pattern[1..];
+				let mut skip = 0;
+				loop
+				{
+					if glob_match(rest, &text[skip..])
+					{
+						return true;
+					}
+					if skip == text.len() || text[skip] == '/'
+					{
+						return false;
+					}
+					skip += 1;
+				}
+			}
+		},
+		Some(&'?') => {
+			match text.first()
+			{
+				Some(ch) if *ch != '/' => glob_match(&pattern[1..], &text[1..]),
+				_ => false
+			}
+		},
+		Some(&'[') if class_len(pattern).is_some() => {
+			let len = class_len(pattern).unwrap();
+			match text.first()
+			{
+				Some(ch) if *ch != '/' && class_match(&pattern[..len], *ch) => glob_match(&pattern[len..], &text[1..]),
+				_ => false
+			}
+		},
+		Some(ch) => {
+			match text.first()
+			{
+				Some(text_ch) if text_ch == ch => glob_match(&pattern[1..], &text[1..]),
+				_ => false
+			}
+		}
+	}
+}
+
+//length of the class "[...]" at the start of a pattern, None if it is not closed
+//a ']' right after the '[' (or after the '!' or '^' of a negated class) is part of it
+fn class_len(pattern: &[char]) -> Option<usize>
+{
+	let mut start = 1;
+	if pattern.get(1) == Some(&'!') || pattern.get(1) == Some(&'^')
+	{
+		start += 1;
+	}
+	pattern.iter().skip(start + 1).position(|ch| *ch == ']').map(|end| start + 1 + end + 1)
+}
+
+//true if a char is in a class like "[abc]", "[a-z0-9]" or "[!.]"
+fn class_match(class: &[char], ch: char) -> bool
+{
+	let negate = class[1] == '!' || class[1] == '^';
+	let set = &class[if negate { 2 } else { 1 } .. class.len() - 1];
+	let mut index = 0;
+	let mut found = false;
+	while index < set.len()
+	{
+		if index + 2 < set.len() && set[index + 1] == '-'
+		{
+			found = found || (set[index] <= ch && ch <= set[index + 2]);
+			index += 3;
+		}
+		else
+		{
+		    found = found || set[index] == ch;
+		    index += 1;
+		}
+	}
+	found != negate
+}
+
+//a file is treated as binary if its start contains a zero byte
+pub fn is_binary(bytes: &[u8]) -> bool
+{
+	let len = if bytes.len() < BINARY_CHECK_LEN { bytes.len() } else { BINARY_CHECK_LEN };
+	bytes[..len].contains(&0)
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn matches(pattern: &str, text: &str) -> bool
+	{
+		glob_match(&pattern.chars().collect::<Vec<char>>(), &text.chars().collect::<Vec<char>>())
+	}
+
+	#[test]
+	fn stars_and_question_marks()
+	{
+		assert!(matches("*.rs", "main.rs"));
+		assert!(matches("*.rs", "src/main.rs") == false);
+		assert!(matches("src/*", "src/main.rs"));
+		assert!(matches("ma?n.rs", "main.rs"));
+		assert!(matches("a?b", "a/b") == false);
+		assert!(matches("target", "target"));
+		assert!(matches("target", "targets") == false);
+	}
+
+	#[test]
+	fn double_stars()
+	{
+		assert!(matches("**/build", "build"));
+		assert!(matches("**/build", "a/b/build"));
+		assert!(matches("**/build", "mybuild") == false);
+		assert!(matches("**/build", "a/mybuild") == false);
+		assert!(matches("a/**/b", "a/b"));
+		assert!(matches("a/**/b", "a/x/y/b"));
+		assert!(matches("a/**/b", "a/xb") == false);
+		assert!(matches("doc/**", "doc/a/b.txt"));
+	}
+
+	#[test]
+	fn classes()
+	{
+		assert!(matches("*.py[cod]", "x.pyc"));
+		assert!(matches("*.py[cod]", "x.pyd"));
+		assert!(matches("*.py[cod]", "x.py") == false);
+		assert!(matches("*.py[cod]", "x.pyx") == false);
+		assert!(matches("file[0-9].txt", "file7.txt"));
+		assert!(matches("file[0-9].txt", "filex.txt") == false);
+		assert!(matches("[!.]*", "main.rs"));
+		assert!(matches("[!.]*", ".git") == false);
+		assert!(matches("[]a]", "]"));
+		assert!(matches("a[/]b", "a/b") == false);
+		assert!(matches("[abc", "[abc"));
+	}
+
+	#[test]
+	fn binary()
+	{
+		assert!(is_binary(b"abc\0def"));
+		assert!(is_binary(b"plain text\n") == false);
+	}
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use filedata;
+use filedata::FileData;
+
+pub const MIN_WORD_LEN: usize = 3; //shorter words are not offered
+pub const MIN_PREFIX_LEN: usize = 2; //chars typed before completions are offered
+pub const NEARBY_LINES: usize = 100; //lines around the cursor that count as close
+pub const MAX_COMPLETIONS: usize = 50;
+
+//counts of the words in a buffer, built when they are first asked for
+//and then kept up to date by reading the lines of every change again
+pub struct WordIndex
+{
+	lines: Option<Vec<Vec<String>>>, //words of every line, None until the index is built
+	counts: HashMap<String, usize> //word -> number of occurrences
+}
+
+impl WordIndex
+{
+	pub fn new() -> WordIndex
+	{
+		WordIndex
+		{
+			lines: None,
+			counts: HashMap::new()
+		}
+	}
+
+	pub fn is_built(&self) -> bool
+	{
+		self.lines.is_some()
+	}
+
+	pub fn get_counts(&self) -> &HashMap<String, usize>
+	{
+		&self.counts
+	}
+
+	//index every line of a text
+	pub fn build(&mut self, content: &[Vec<char>])
+	{
+		self.lines = Some(Vec::new());
+		self.counts.clear();
+		self.replace(0, 0, content);
+	}
+
+	//count lines of text that replaced count lines starting at first, nothing happens before the index is built
+	pub fn replace(&mut self, first: usize, count: usize, lines: &[Vec<char>])
+	{
+		if self.lines.is_none()
+		{
+			return;
+		}
+		let new_lines: Vec<Vec<String>> = lines.iter().map(|line| words_of(line)).collect();
+		for words in &new_lines
+		{
+			for word in words
+			{
+				*self.counts.entry(word.clone()).or_insert(0) += 1;
+			}
+		}
+		let old_lines: Vec<Vec<String>> = self.lines.as_mut().unwrap().splice(first .. first+count, new_lines).collect();
+		for word in old_lines.into_iter().flatten()
+		{
+			let gone = match self.counts.get_mut(&word)
+			{
+				Some(count) => {
+					*count -= 1;
+					*count == 0
+				},
+				None => false
+			};
+			if gone
+			{
+				self.counts.remove(&word);
+			}
+		}
+	}
+}
+
+//identifiers in a line, numbers are left out
+fn words_of(line: &[char]) -> Vec<String>
+{
+	let mut result = Vec::new();
+	let mut start = 0;
+	while start < line.len()
+	{
+		if filedata::is_word_char(line[start]) == false
+		{
+			start += 1;
+			continue;
+		}
+		let mut end = start;
+		while end < line.len() && filedata::is_word_char(line[end])
+		{
+			end += 1;
+		}
+		if end - start >= MIN_WORD_LEN && line[start].is_numeric() == false
+		{
+			result.push(line[start..end].iter().collect());
+		}
+		start = end;
+	}
+	result
+}
+
+//distance in lines from the cursor line to the closest occurrence of every word near it
+pub fn nearby(data: &FileData, cursor_line: usize) -> HashMap<String, usize>
+{
+	let mut result = HashMap::new();
+	let first = cursor_line.saturating_sub(NEARBY_LINES);
+	let last = cursor_line + NEARBY_LINES;
+	for line in first .. last+1
+	{
+		let content = match data.get_line(line)
+		{
+			Some(v) => v,
+			None => {break;}
+		};
+		let distance = line.abs_diff(cursor_line);
+		for word in words_of(content)
+		{
+			let closest = result.entry(word).or_insert(distance);
+			if distance < *closest
+			{
+				*closest = distance;
+			}
+		}
+	}
+	result
+}
+
+//the words starting with prefix, words used often and close to the cursor first
+pub fn rank(counts: &HashMap<String, usize>, nearby: &HashMap<String, usize>, prefix: &str) -> Vec<String>
+{
+	let mut scored: Vec<(usize, &String)> = counts.iter().filter(|&(word, _)| word.starts_with(prefix) && word != prefix).map(|(word, count)| {
+		let frequency = if *count < 20 { *count } else { 20 };
+		let proximity = match nearby.get(word)
+		{
+			Some(distance) => (NEARBY_LINES + 1 - distance) / 5,
+			None => 0
+		};
+		(frequency + proximity, word)
+	}).collect();
+	scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+	scored.into_iter().take(MAX_COMPLETIONS).map(|(_, word)| word.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn count(data: &mut FileData, word: &str) -> usize
+	{
+		data.get_words().get(word).cloned().unwrap_or(0)
+	}
+
+	#[test]
+	fn counts_follow_edits()
+	{
+		let mut data = FileData::new();
+		data.insert_text("let value = other_value;\nvalue += 1;\n");
+		assert_eq!(count(&mut data, "value"), 2);
+		assert_eq!(count(&mut data, "other_value"), 1);
+		assert_eq!(count(&mut data, "let"), 1);
+		data.set_cursor(1, 0);
+		data.insert_text("let ");
+		data.set_cursor(0, 4);
+		data.remove();
+		assert_eq!(count(&mut data, "let"), 2);
+		assert_eq!(count(&mut data, "value"), 1);
+		assert_eq!(count(&mut data, "alue"), 1);
+		data.undo();
+		data.undo();
+		assert_eq!(count(&mut data, "let"), 1);
+		assert_eq!(count(&mut data, "value"), 2);
+		assert!(data.get_words().contains_key("alue") == false);
+		data.clear();
+		assert!(data.get_words().is_empty());
+	}
+
+	#[test]
+	fn words_leave_out_numbers_and_short_words()
+	{
+		assert_eq!(words_of(&"a_b x1 123abc foo(bar)".chars().collect::<Vec<char>>()), vec!["a_b", "foo", "bar"]);
+	}
+
+	#[test]
+	fn rank_by_count_and_distance()
+	{
+		let mut counts = HashMap::new();
+		counts.insert(String::from("format"), 3);
+		counts.insert(String::from("fold"), 1);
+		counts.insert(String::from("for"), 1);
+		counts.insert(String::from("bar"), 9);
+		let mut nearby = HashMap::new();
+		nearby.insert(String::from("fold"), 1);
+		assert_eq!(rank(&counts, &nearby, "fo"), vec!["fold", "format", "for"]);
+		assert_eq!(rank(&counts, &nearby, "for"), vec!["format"]);
+	}
+}